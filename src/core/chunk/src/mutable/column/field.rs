@@ -2,14 +2,17 @@ use std::ops::Range;
 
 use common::{
     array::{fixed::OptionalFixedListArray, Array},
-    column::field::Field,
+    column::field::{Field, FieldType, FieldValue},
     context::Context,
+    primitive::Primitive,
     scalar::{list::OptionalFixedList, ScalarRef},
     try_yield,
 };
 use croaring::Bitmap;
 use paste::paste;
 
+use crate::mutable::{column::WriteError, zone::ZoneMaps};
+
 pub type UInt8Field = OptionalFixedListArray<u8>;
 pub type UInt16Field = OptionalFixedListArray<u16>;
 pub type UInt32Field = OptionalFixedListArray<u32>;
@@ -37,24 +40,59 @@ type ArrayImpl = Field<
 >;
 
 #[derive(Debug)]
-#[repr(transparent)]
-pub struct FieldImpl(ArrayImpl);
+pub struct FieldImpl {
+    array: ArrayImpl,
+    zone: ZoneMaps,
+}
 
 impl From<ArrayImpl> for FieldImpl {
     #[inline]
-    fn from(value: ArrayImpl) -> Self {
-        Self(value)
+    fn from(array: ArrayImpl) -> Self {
+        macro_rules! zone {
+            ($($field_type:ident), *) => {
+                paste! {
+                match &array {
+                    $(
+                    Field::$field_type(column) => {
+                        let mut zone = ZoneMaps::default();
+                        for (row, item) in column.iter().enumerate() {
+                            zone.push_nulls(row, item.len());
+                            for value in item.into_iter().flatten() {
+                                zone.set(row, true, Some(value.as_f64()));
+                            }
+                        }
+                        zone
+                    }
+                    )*
+                }
+                }
+            };
+        }
+
+        let zone =
+            zone!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64, Bool);
+        Self { array, zone }
     }
 }
 
 impl FieldImpl {
+    #[inline]
+    pub fn zone(&self) -> &ZoneMaps {
+        &self.zone
+    }
+
     #[inline]
     pub fn push_zero(&mut self) {
         macro_rules! push {
             ($($label_type:ident), *) => {
                 paste! {
-                match &mut self.0 {
-                    $(Field::$label_type(column) => column.push_zero(),)*
+                match &mut self.array {
+                    $(
+                    Field::$label_type(column) => {
+                        self.zone.push_nulls(column.len(), column.list_size());
+                        column.push_zero();
+                    }
+                    )*
                 }
                 }
             };
@@ -63,21 +101,60 @@ impl FieldImpl {
         push!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64, Bool);
     }
 
+    pub fn set(
+        &mut self,
+        row: usize,
+        step: usize,
+        value: Option<FieldValue>,
+    ) -> Result<(), WriteError> {
+        macro_rules! set {
+            ($($field_type:ident), *) => {
+                paste! {
+                match &mut self.array {
+                    $(
+                    Field::$field_type(column) => {
+                        let value = value
+                            .map(|value| match value {
+                                Field::$field_type(value) => Ok(value),
+                                others => Err(WriteError::TypeMismatch {
+                                    expected: FieldType::from(Field::$field_type(())),
+                                    found: others.r#type(),
+                                }),
+                            })
+                            .transpose()?;
+                        if row >= column.len() {
+                            return Err(WriteError::MissingRow { row });
+                        }
+                        let mut item = column.get_mut(row).unwrap();
+                        let was_null = item.get(step).unwrap().is_none();
+                        item.set(step, value);
+                        self.zone.set(row, was_null, value.map(Primitive::as_f64));
+                    }
+                    )*
+                }
+                }
+            };
+        }
+
+        set!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64, Bool);
+        Ok(())
+    }
+
     #[inline]
     pub async fn map(&self, cx: &mut Context, row_set: &Bitmap, range: Range<usize>) -> Self {
         macro_rules! map {
             ($($field_type:ident), *) => {
                 paste! {
-                match &self.0 {
+                match &self.array {
                     $(
                     Field::$field_type(column) => {
                         let mut field = [<$field_type Field>]::with_capacity(
                             row_set.cardinality() as usize,
-                            column.list_size() as u32,
+                            range.len() as u32,
                         );
 
-                        let mut iter = column.iter();
-                        while let Some(item) = iter.next() {
+                        for row in row_set.iter() {
+                            let item = unsafe { column.get_unchecked(row as usize) };
                             field.push(ScalarRef::to_owned(item.slice(range.clone())));
                             try_yield!(cx);
                         }
@@ -92,12 +169,65 @@ impl FieldImpl {
         map!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64, Bool)
     }
 
+    pub fn get(&self, row: usize) -> Option<FieldItemImpl> {
+        macro_rules! get {
+            ($($field_type:ident), *) => {
+                paste! {
+                match &self.array {
+                    $(
+                    Field::$field_type(column) => column
+                        .get(row)
+                        .map(|item| Field::$field_type(ScalarRef::to_owned(item))),
+                    )*
+                }
+                }
+            };
+        }
+
+        get!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64, Bool)
+    }
+
+    pub fn get_f64(&self, row: usize) -> Option<Vec<Option<f64>>> {
+        macro_rules! get {
+            ($($field_type:ident), *) => {
+                paste! {
+                match &self.array {
+                    $(
+                    Field::$field_type(column) => column.get(row).map(|item| {
+                        item.into_iter()
+                            .map(|value| value.map(|value| value.as_f64()))
+                            .collect()
+                    }),
+                    )*
+                }
+                }
+            };
+        }
+
+        get!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64, Bool)
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        macro_rules! width {
+            ($($field_type:ident), *) => {
+                paste! {
+                match &self.array {
+                    $(Field::$field_type(column) => column.list_size(),)*
+                }
+                }
+            };
+        }
+
+        width!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64, Bool)
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         macro_rules! len {
             ($($field_type:ident), *) => {
                 paste! {
-                match &self.0 {
+                match &self.array {
                     $(Field::$field_type(column) => column.len(),)*
                 }.into()
                 }
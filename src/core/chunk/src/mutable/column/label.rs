@@ -1,4 +1,8 @@
-use std::hash::Hash;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::RangeBounds,
+};
 
 use common::{
     array::{
@@ -6,7 +10,7 @@ use common::{
     },
     column::label::{Label, LabelType, LabelValue},
     context::Context,
-    query::MatcherOp,
+    query::{Cidr, MatcherOp},
     scalar::{Scalar, ScalarRef},
     try_yield,
 };
@@ -15,6 +19,7 @@ use paste::paste;
 use regex::Regex;
 
 use super::FilterError;
+use crate::mutable::stats::{Sketch, SKETCH_BITS};
 
 pub trait AsStr {
     fn as_str(&self) -> &str;
@@ -116,6 +121,81 @@ where
         self.array.push(item)
     }
 
+    pub(crate) async fn predicate_match<F>(
+        &self,
+        cx: &mut Context,
+        positive: bool,
+        mut predicate: F,
+        superset: &mut Bitmap,
+    ) where
+        F: FnMut(A::ItemRef<'_>) -> bool,
+    {
+        let mut set = Bitmap::create();
+        for row_id in superset.iter() {
+            let matched = unsafe { self.array.get_unchecked(row_id as usize) }
+                .map(&mut predicate)
+                .unwrap_or(false);
+            if !(positive ^ matched) {
+                set.add(row_id);
+            }
+            try_yield!(cx);
+        }
+
+        *superset = set;
+    }
+
+    #[inline]
+    pub fn cardinality(&self) -> usize {
+        self.array.cardinality()
+    }
+
+    pub fn sketch(&self) -> Sketch {
+        let mut sketch = Sketch::new(SKETCH_BITS);
+        for (_, value) in self.array.values() {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            sketch.add(&hasher.finish());
+        }
+        sketch
+    }
+
+    pub(crate) fn row_value_ids(&self, rows: &Bitmap) -> Bitmap {
+        let mut ids = Bitmap::create();
+        for row in rows.iter() {
+            match self.array.id(row as usize) {
+                Some(0) | None => {}
+                Some(id) => ids.add(id as u32),
+            }
+        }
+        ids
+    }
+
+    pub(crate) fn values_of(&self, ids: Option<&Bitmap>) -> Vec<A::Item> {
+        match ids {
+            Some(ids) => ids
+                .iter()
+                .filter_map(|id| self.array.value(id as usize))
+                .map(ScalarRef::to_owned)
+                .collect(),
+            None => self
+                .array
+                .values()
+                .map(|(_, value)| ScalarRef::to_owned(value))
+                .collect(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn value_ids<F>(&self, mut predicate: F) -> Vec<usize>
+    where
+        F: FnMut(A::ItemRef<'_>) -> bool,
+    {
+        self.array
+            .values()
+            .filter_map(|(id, value)| (predicate)(value).then_some(id))
+            .collect()
+    }
+
     pub(crate) async fn lookup<'s>(
         &'s self,
         cx: &mut Context,
@@ -154,6 +234,18 @@ impl From<ArrayImpl> for LabelImpl {
 }
 
 impl LabelImpl {
+    #[inline]
+    pub fn new(r#type: &LabelType) -> Self {
+        match r#type {
+            Label::String(_) => Label::String(LabelColumn::<StringLabel>::new()),
+            Label::IPv4(_) => Label::IPv4(LabelColumn::<IPv4Label>::new()),
+            Label::IPv6(_) => Label::IPv6(LabelColumn::<IPv6Label>::new()),
+            Label::Int(_) => Label::Int(LabelColumn::<IntLabel>::new()),
+            Label::Bool(_) => Label::Bool(LabelColumn::<BoolLabel>::new()),
+        }
+        .into()
+    }
+
     pub fn push(&mut self, value: Option<LabelValue>) -> usize {
         macro_rules! push {
             ($($label_type:ident), *) => {
@@ -183,7 +275,7 @@ impl LabelImpl {
     }
 
     #[allow(clippy::missing_safety_doc)]
-    pub unsafe fn lookup_value_id_unchecked(&self, matcher: &Option<LabelValue>) -> Option<usize> {
+    pub unsafe fn lookup_value_id_unchecked(&self, matcher: Option<&LabelValue>) -> Option<usize> {
         macro_rules! lookup_value_id {
             ($($label_type:ident), * => $matcher:expr) => {
                 paste! {
@@ -191,7 +283,6 @@ impl LabelImpl {
                     $(
                     Label::$label_type(column) => {
                         $matcher
-                            .as_ref()
                             .map(|matcher| {
                                 let value = if let LabelValue::$label_type(value) = matcher {
                                     value
@@ -211,6 +302,44 @@ impl LabelImpl {
         lookup_value_id!(String, IPv4, IPv6, Int, Bool => matcher)
     }
 
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn lookup_value_ids_unchecked(&self, matcher: &MatcherOp) -> Option<Vec<usize>> {
+        match matcher {
+            MatcherOp::LiteralEqual(value) | MatcherOp::LiteralNotEqual(value) => Some(
+                self.lookup_value_id_unchecked(value.as_ref())
+                    .into_iter()
+                    .collect(),
+            ),
+            MatcherOp::In(values) | MatcherOp::NotIn(values) => Some(
+                values
+                    .iter()
+                    .filter_map(|value| self.lookup_value_id_unchecked(Some(value)))
+                    .collect(),
+            ),
+            MatcherOp::CidrMatch(cidr) | MatcherOp::CidrNotMatch(cidr) => match (&self.0, cidr) {
+                (Label::IPv4(column), Cidr::IPv4(prefix)) => {
+                    Some(column.value_ids(|value| prefix.contains(value)))
+                }
+                (Label::IPv6(column), Cidr::IPv6(prefix)) => {
+                    Some(column.value_ids(|value| prefix.contains(value)))
+                }
+                _ => None,
+            },
+            MatcherOp::LessThan(_)
+            | MatcherOp::LessEqual(_)
+            | MatcherOp::GreaterThan(_)
+            | MatcherOp::GreaterEqual(_)
+            | MatcherOp::Between(_, _) => match &self.0 {
+                Label::Int(column) => {
+                    let (start, end) = matcher.int_bounds()?;
+                    Some(column.value_ids(|value| (start, end).contains(value)))
+                }
+                _ => None,
+            },
+            MatcherOp::RegexMatch(_) | MatcherOp::RegexNotMatch(_) => None,
+        }
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         macro_rules! len {
@@ -231,6 +360,107 @@ impl LabelImpl {
         self.len() == 0
     }
 
+    #[inline]
+    pub fn get(&self, row: usize) -> Option<LabelValue> {
+        macro_rules! get {
+            ($($label_type:ident), *) => {
+                paste! {
+                match &self.0 {
+                    $(
+                    Label::$label_type(column) => column
+                        .array
+                        .get(row)
+                        .flatten()
+                        .map(|value| Label::$label_type(ScalarRef::to_owned(value))),
+                    )*
+                }
+                }
+            };
+        }
+
+        get!(String, IPv4, IPv6, Int, Bool)
+    }
+
+    // id 0 stands for null
+    #[inline]
+    pub fn value_id(&self, row: usize) -> Option<usize> {
+        macro_rules! value_id {
+            ($($label_type:ident), *) => {
+                paste! {
+                match &self.0 {
+                    $(Label::$label_type(column) => column.array.id(row),)*
+                }
+                }
+            };
+        }
+
+        value_id!(String, IPv4, IPv6, Int, Bool)
+    }
+
+    #[inline]
+    pub fn row_value_ids(&self, rows: &Bitmap) -> Bitmap {
+        macro_rules! row_value_ids {
+            ($($label_type:ident), *) => {
+                paste! {
+                match &self.0 {
+                    $(Label::$label_type(column) => column.row_value_ids(rows),)*
+                }
+                }
+            };
+        }
+
+        row_value_ids!(String, IPv4, IPv6, Int, Bool)
+    }
+
+    pub fn values(&self, ids: Option<&Bitmap>) -> Vec<LabelValue> {
+        macro_rules! values {
+            ($($label_type:ident), *) => {
+                paste! {
+                match &self.0 {
+                    $(
+                    Label::$label_type(column) => column
+                        .values_of(ids)
+                        .into_iter()
+                        .map(Label::$label_type)
+                        .collect(),
+                    )*
+                }
+                }
+            };
+        }
+
+        values!(String, IPv4, IPv6, Int, Bool)
+    }
+
+    #[inline]
+    pub fn cardinality(&self) -> usize {
+        macro_rules! cardinality {
+            ($($label_type:ident), *) => {
+                paste! {
+                match &self.0 {
+                    $(Label::$label_type(column) => column.cardinality(),)*
+                }
+                }
+            };
+        }
+
+        cardinality!(String, IPv4, IPv6, Int, Bool)
+    }
+
+    pub fn sketch(&self) -> Sketch {
+        macro_rules! sketch {
+            ($($label_type:ident), *) => {
+                paste! {
+                match &self.0 {
+                    $(Label::$label_type(column) => column.sketch(),)*
+                }
+                }
+            };
+        }
+
+        sketch!(String, IPv4, IPv6, Int, Bool)
+    }
+
     #[allow(clippy::missing_safety_doc)]
     #[inline]
     pub async unsafe fn filter(
@@ -264,10 +494,46 @@ impl LabelImpl {
             };
         }
 
+        macro_rules! label_in {
+            ($($label_type:ident), * => $op:expr, $values:expr) => {
+                paste! {
+                match &self.0 {
+                    $(
+                    Label::$label_type(column) => {
+                        let values = $values
+                            .iter()
+                            .map(|value| {
+                                let value = if let LabelValue::$label_type(value) = value {
+                                    value
+                                } else {
+                                    panic!("type of label value assertion failed")
+                                };
+                                Scalar::as_ref(value)
+                            })
+                            .collect::<Vec<_>>();
+                        column
+                            .predicate_match(
+                                cx,
+                                $op.positive(),
+                                |item| values.iter().any(|value| *value == item),
+                                superset,
+                            )
+                            .await;
+                        Ok(())
+                    }
+                    )*
+                }
+                }
+            };
+        }
+
         match matcher {
             op @ MatcherOp::LiteralEqual(matcher) | op @ MatcherOp::LiteralNotEqual(matcher) => {
                 label_lookup!(String, IPv4, IPv6, Int, Bool => op, matcher)
             }
+            op @ MatcherOp::In(values) | op @ MatcherOp::NotIn(values) => {
+                label_in!(String, IPv4, IPv6, Int, Bool => op, values)
+            }
             op @ MatcherOp::RegexMatch(matcher) | op @ MatcherOp::RegexNotMatch(matcher) => {
                 match &self.0 {
                     Label::String(column) => {
@@ -278,6 +544,46 @@ impl LabelImpl {
                     _ => Err(FilterError::RegexStringOnly),
                 }
             }
+            op @ MatcherOp::CidrMatch(cidr) | op @ MatcherOp::CidrNotMatch(cidr) => {
+                match (&self.0, cidr) {
+                    (Label::IPv4(column), Cidr::IPv4(prefix)) => {
+                        column
+                            .predicate_match(
+                                cx,
+                                op.positive(),
+                                |value| prefix.contains(value),
+                                superset,
+                            )
+                            .await;
+                        Ok(())
+                    }
+                    (Label::IPv6(column), Cidr::IPv6(prefix)) => {
+                        column
+                            .predicate_match(
+                                cx,
+                                op.positive(),
+                                |value| prefix.contains(value),
+                                superset,
+                            )
+                            .await;
+                        Ok(())
+                    }
+                    _ => Err(FilterError::CidrIpOnly),
+                }
+            }
+            op @ (MatcherOp::LessThan(_)
+            | MatcherOp::LessEqual(_)
+            | MatcherOp::GreaterThan(_)
+            | MatcherOp::GreaterEqual(_)
+            | MatcherOp::Between(_, _)) => match (&self.0, op.int_bounds()) {
+                (Label::Int(column), Some(bounds)) => {
+                    column
+                        .predicate_match(cx, true, |value| bounds.contains(value), superset)
+                        .await;
+                    Ok(())
+                }
+                _ => Err(FilterError::RangeIntOnly),
+            },
         }
     }
 
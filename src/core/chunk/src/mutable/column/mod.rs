@@ -1,15 +1,38 @@
 pub mod field;
 pub mod label;
 
+use common::{
+    column::field::FieldType,
+    time::{Instant, Range},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum FilterError {
     #[error("regex match only supports string label")]
     RegexStringOnly,
+    #[error("cidr match only supports ip label of the same family")]
+    CidrIpOnly,
+    #[error("range match only supports int label")]
+    RangeIntOnly,
     #[error("regex pattern error: {}", source)]
     PatternError {
         #[from]
         source: regex::Error,
     },
 }
+
+#[derive(Debug, Error)]
+pub enum WriteError {
+    #[error("write into a sealed chunk")]
+    Sealed,
+    #[error("{:?} is out of chunk range {:?}", at, range)]
+    OutOfRange { at: Instant, range: Range },
+    #[error("row {} does not exist", row)]
+    MissingRow { row: usize },
+    #[error("expect type: {}, found: {}", expected, found)]
+    TypeMismatch {
+        expected: FieldType,
+        found: FieldType,
+    },
+}
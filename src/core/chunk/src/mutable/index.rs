@@ -1,9 +1,19 @@
-use std::hash::Hash;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Bound,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use common::{index::Index as IndexType, query::MatcherOp};
+use common::{
+    index::{FilterKind, Index as IndexType, SparseOptions},
+    query::MatcherOp,
+};
 use croaring::Bitmap;
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use pdatastructs::filters::{bloomfilter::BloomFilter, Filter};
+use xorf::{BinaryFuse16, BinaryFuse8, Filter as _, Xor16, Xor8};
 
 pub trait Index {
     type Value;
@@ -58,20 +68,151 @@ where
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default)]
+pub struct SparseMetrics {
+    lookups: AtomicU64,
+    candidate_blocks: AtomicU64,
+    false_positive_blocks: AtomicU64,
+}
+
+impl SparseMetrics {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            lookups: AtomicU64::new(0),
+            candidate_blocks: AtomicU64::new(0),
+            false_positive_blocks: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn lookups(&self) -> u64 {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn candidate_blocks(&self) -> u64 {
+        self.candidate_blocks.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn false_positive_blocks(&self) -> u64 {
+        self.false_positive_blocks.load(Ordering::Relaxed)
+    }
+
+    // compares the rows of the blocks a sparse filter let through with the rows
+    // that really hold the value, before any other filter narrows them
+    pub(crate) fn record(&self, candidate: &Bitmap, matched: &Bitmap, block_size: u32) {
+        #[inline]
+        fn blocks(rows: &Bitmap, block_size: u32) -> Vec<u32> {
+            let mut blocks = rows.iter().map(|row| row / block_size).collect::<Vec<_>>();
+            blocks.dedup();
+            blocks
+        }
+
+        let candidate = blocks(candidate, block_size);
+        let matched = blocks(matched, block_size);
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        self.candidate_blocks
+            .fetch_add(candidate.len() as u64, Ordering::Relaxed);
+        self.false_positive_blocks
+            .fetch_add((candidate.len() - matched.len()) as u64, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+enum BlockFilter {
+    Bloom(BloomFilter<u64>),
+    Xor8(Xor8),
+    Xor16(Xor16),
+    BinaryFuse8(BinaryFuse8),
+    BinaryFuse16(BinaryFuse16),
+    Exact(HashSet<u64>),
+}
+
+impl BlockFilter {
+    fn build(options: &SparseOptions, keys: HashSet<u64>) -> Self {
+        // the fpp of xor and binary fuse filters is bounded by SparseOptions
+        let fingerprint8 = options.fpp() >= 1.0 / 256.0;
+        let filter = {
+            let keys = keys.iter().copied().collect::<Vec<_>>();
+            match (options.filter(), fingerprint8) {
+                (FilterKind::Bloom, _) => {
+                    let mut filter = BloomFilter::with_properties(keys.len().max(1), options.fpp());
+                    for key in &keys {
+                        filter.insert(key).unwrap();
+                    }
+                    Some(Self::Bloom(filter))
+                }
+                (FilterKind::Xor, true) => Some(Self::Xor8(Xor8::from(&keys))),
+                (FilterKind::Xor, false) => Some(Self::Xor16(Xor16::from(&keys))),
+                (FilterKind::BinaryFuse, true) => {
+                    BinaryFuse8::try_from(&keys).ok().map(Self::BinaryFuse8)
+                }
+                (FilterKind::BinaryFuse, false) => {
+                    BinaryFuse16::try_from(&keys).ok().map(Self::BinaryFuse16)
+                }
+            }
+        };
+        filter.unwrap_or(Self::Exact(keys))
+    }
+
+    #[inline]
+    fn contains(&self, key: u64) -> bool {
+        match self {
+            BlockFilter::Bloom(filter) => filter.query(&key),
+            BlockFilter::Xor8(filter) => filter.contains(&key),
+            BlockFilter::Xor16(filter) => filter.contains(&key),
+            BlockFilter::BinaryFuse8(filter) => filter.contains(&key),
+            BlockFilter::BinaryFuse16(filter) => filter.contains(&key),
+            BlockFilter::Exact(keys) => keys.contains(&key),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct SparseIndex<V: Hash> {
-    seens: Vec<BloomFilter<V>>,
-    block_size: u32,
+    sealed: Vec<BlockFilter>,
+    open: HashSet<u64>,
+    options: SparseOptions,
+    metrics: SparseMetrics,
+    _value: PhantomData<V>,
 }
 
 impl<T: Hash> SparseIndex<T> {
     #[inline]
-    pub(crate) fn new(block_size: u32) -> Self {
+    pub(crate) fn new(options: SparseOptions) -> Self {
         Self {
-            seens: Vec::new(),
-            block_size,
+            sealed: Vec::new(),
+            open: HashSet::new(),
+            options,
+            metrics: SparseMetrics::new(),
+            _value: PhantomData,
         }
     }
+
+    #[inline]
+    pub(crate) fn block_size(&self) -> u32 {
+        self.options.block_size
+    }
+
+    #[inline]
+    pub(crate) fn metrics(&self) -> &SparseMetrics {
+        &self.metrics
+    }
+
+    #[inline]
+    fn hash(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[inline]
+    fn seal(&mut self) {
+        let keys = std::mem::take(&mut self.open);
+        self.sealed.push(BlockFilter::build(&self.options, keys));
+    }
 }
 
 impl<V: Hash> Index for SparseIndex<V> {
@@ -79,26 +220,30 @@ impl<V: Hash> Index for SparseIndex<V> {
 
     #[inline]
     fn lookup<F: FnMut(&Bitmap)>(&self, value: &Self::Value, mut f: F) {
+        let key = Self::hash(value);
+        let block_size = self.options.block_size;
         let mut bitmap = Bitmap::create();
-        for (offset, block) in self.seens.iter().enumerate() {
-            if block.query(value) {
+        for (offset, block) in self.sealed.iter().enumerate() {
+            if block.contains(key) {
                 let offset = offset as u32;
-                bitmap.add_range((offset * self.block_size)..((offset + 1) * self.block_size));
+                bitmap.add_range((offset * block_size)..((offset + 1) * block_size));
             }
         }
+        if self.open.contains(&key) {
+            let offset = self.sealed.len() as u32;
+            bitmap.add_range((offset * block_size)..((offset + 1) * block_size));
+        }
 
         (f)(&bitmap);
     }
 
     #[inline]
     fn insert(&mut self, row: u32, value: Self::Value) {
-        let block = (row / self.block_size) as usize;
-        if self.seens.len() <= block {
-            self.seens.resize_with(block + 1, || {
-                BloomFilter::with_properties(self.block_size as usize, 1.0 / 100.0)
-            });
+        let block = (row / self.options.block_size) as usize;
+        while self.sealed.len() < block {
+            self.seal();
         }
-        self.seens[block].insert(&value).unwrap();
+        self.open.insert(Self::hash(&value));
     }
 
     #[inline]
@@ -107,17 +252,89 @@ impl<V: Hash> Index for SparseIndex<V> {
     }
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct OrderedIndex<K, V>
+where
+    V: Eq + Hash,
+{
+    order: BTreeMap<K, V>,
+    inverted: InvertedIndex<V>,
+}
+
+impl<K, V> OrderedIndex<K, V>
+where
+    K: Ord,
+    V: Eq + Hash + Clone,
+{
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            order: BTreeMap::new(),
+            inverted: InvertedIndex::new(),
+        }
+    }
+
+    #[inline]
+    pub fn insert_ordered(&mut self, row: u32, key: Option<K>, value: V) {
+        if let Some(key) = key {
+            self.order.entry(key).or_insert_with(|| value.clone());
+        }
+        self.inverted.insert(row, value);
+    }
+
+    pub fn range(&self, start: Bound<&K>, end: Bound<&K>) -> Vec<V> {
+        let empty = match (start, end) {
+            (Bound::Included(start) | Bound::Excluded(start), Bound::Included(end)) => start > end,
+            (Bound::Included(start), Bound::Excluded(end)) => start > end,
+            (Bound::Excluded(start), Bound::Excluded(end)) => start >= end,
+            _ => false,
+        };
+        if empty {
+            return Vec::new();
+        }
+        self.order
+            .range((start, end))
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+impl<K, V> Index for OrderedIndex<K, V>
+where
+    V: Eq + Hash,
+{
+    type Value = V;
+
+    #[inline]
+    fn lookup<F: FnMut(&Bitmap)>(&self, value: &Self::Value, f: F) {
+        self.inverted.lookup(value, f)
+    }
+
+    #[inline]
+    fn insert(&mut self, row: u32, value: Self::Value) {
+        self.inverted.insert(row, value)
+    }
+
+    #[inline]
+    fn exactly(&self) -> bool {
+        true
+    }
+}
+
 #[derive(Debug)]
-pub struct IndexImpl<V: Eq + Hash>(IndexType<InvertedIndex<V>, SparseIndex<V>>);
+pub struct IndexImpl<V: Eq + Hash>(
+    IndexType<InvertedIndex<V>, SparseIndex<V>, OrderedIndex<i64, V>>,
+);
 
 impl<V> IndexImpl<V>
 where
-    V: Eq + Hash,
+    V: Eq + Hash + Clone,
 {
-    pub fn new(r#type: &IndexType<(), u32>) -> Self {
+    pub fn new(r#type: &IndexType<(), SparseOptions, ()>) -> Self {
         Self(match r#type {
             IndexType::Inverted(_) => IndexType::Inverted(InvertedIndex::new()),
-            IndexType::Sparse(block_size) => IndexType::Sparse(SparseIndex::new(*block_size)),
+            IndexType::Sparse(options) => IndexType::Sparse(SparseIndex::new(options.clone())),
+            IndexType::Ordered(_) => IndexType::Ordered(OrderedIndex::new()),
         })
     }
 
@@ -126,6 +343,7 @@ where
         match &self.0 {
             IndexType::Inverted(index) => index.lookup(value, f),
             IndexType::Sparse(index) => index.lookup(value, f),
+            IndexType::Ordered(index) => index.lookup(value, f),
         }
     }
 
@@ -134,35 +352,75 @@ where
         match &self.0 {
             IndexType::Inverted(index) => index.exactly(),
             IndexType::Sparse(index) => index.exactly(),
+            IndexType::Ordered(index) => index.exactly(),
+        }
+    }
+
+    #[inline]
+    pub fn block_size(&self) -> Option<u32> {
+        match &self.0 {
+            IndexType::Sparse(index) => Some(index.block_size()),
+            _ => None,
         }
     }
 
+    // false positive counters of a sparse index, None for the exact ones
     #[inline]
-    pub fn insert(&mut self, id: usize, v: V) {
+    pub fn sparse_metrics(&self) -> Option<&SparseMetrics> {
+        match &self.0 {
+            IndexType::Sparse(index) => Some(index.metrics()),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn insert(&mut self, id: usize, v: V, key: Option<i64>) {
         match &mut self.0 {
             IndexType::Inverted(index) => index.insert(id as u32, v),
             IndexType::Sparse(index) => index.insert(id as u32, v),
+            IndexType::Ordered(index) => index.insert_ordered(id as u32, key, v),
         }
     }
 
     #[inline]
-    pub fn filter<OV>(&self, op: &MatcherOp<OV>, id: V, superset: &mut Bitmap) {
-        self.lookup(&id, |set| {
-            if op.positive() {
-                superset.and_inplace(set)
-            } else {
-                superset.andnot_inplace(set)
+    pub fn range_value_ids(&self, matcher: &MatcherOp) -> Option<Vec<V>> {
+        match &self.0 {
+            IndexType::Ordered(index) => {
+                let (start, end) = matcher.int_bounds()?;
+                Some(index.range(start, end))
             }
-        })
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn rows(&self, ids: &[V]) -> Bitmap {
+        let mut set = Bitmap::create();
+        for id in ids {
+            self.lookup(id, |rows| set.or_inplace(rows));
+        }
+        set
+    }
+
+    #[inline]
+    pub fn filter(&self, positive: bool, rows: &Bitmap, superset: &mut Bitmap) {
+        if positive {
+            superset.and_inplace(rows);
+        } else if self.exactly() {
+            superset.andnot_inplace(rows);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::ops::Bound;
+
+    use common::index::{FilterKind, SparseOptions, SparseOptionsError};
     use croaring::Bitmap;
     use pdatastructs::filters::bloomfilter::BloomFilter;
 
-    use super::{Index, SparseIndex};
+    use super::{Index, OrderedIndex, SparseIndex, SparseMetrics};
     use crate::mutable::index::InvertedIndex;
 
     #[test]
@@ -191,7 +449,7 @@ mod tests {
 
     #[test]
     fn test_sparse_index() {
-        let mut index = SparseIndex::<usize>::new(1000);
+        let mut index = SparseIndex::<usize>::new(SparseOptions::new(1000));
         index.insert(0, 1);
         index.insert(1001, 1);
         index.insert(2001, 2);
@@ -202,6 +460,67 @@ mod tests {
         assert_eq!(result, expect);
     }
 
+    #[test]
+    fn test_sparse_index_filters() {
+        for filter in [FilterKind::Bloom, FilterKind::Xor, FilterKind::BinaryFuse] {
+            for fpp in [1.0 / 100.0, 1.0 / 1000.0] {
+                let mut index = SparseIndex::<usize>::new(
+                    SparseOptions::new(100).with_filter(filter, fpp).unwrap(),
+                );
+                for row in 0..1000 {
+                    index.insert(row, row as usize % 250);
+                }
+                let mut result = Bitmap::from_range(0..1000);
+                index.lookup(&7, |set| result.and_inplace(set));
+                for block in [0, 2, 5, 7] {
+                    assert!(result.contains(block * 100), "{:?} {}", filter, fpp);
+                }
+
+                let mut result = Bitmap::from_range(0..1000);
+                index.lookup(&1000, |set| result.and_inplace(set));
+                assert!(!result.contains(900));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_options_fpp() {
+        for fpp in [0.0, 1.0, f64::NAN] {
+            assert!(matches!(
+                SparseOptions::new(100).with_filter(FilterKind::Bloom, fpp),
+                Err(SparseOptionsError::InvalidFpp(_))
+            ));
+        }
+        for filter in [FilterKind::Xor, FilterKind::BinaryFuse] {
+            assert_eq!(
+                SparseOptions::new(100).with_filter(filter, 1.0 / 100_000.0),
+                Err(SparseOptionsError::FppTooLow {
+                    filter,
+                    fpp: 1.0 / 100_000.0
+                })
+            );
+            let options = SparseOptions::new(100)
+                .with_filter(filter, SparseOptions::MIN_FINGERPRINT_FPP)
+                .unwrap();
+            assert_eq!(options.fpp(), SparseOptions::MIN_FINGERPRINT_FPP);
+        }
+        let options = SparseOptions::new(100)
+            .with_filter(FilterKind::Bloom, 1.0 / 100_000.0)
+            .unwrap();
+        assert_eq!(options.filter(), FilterKind::Bloom);
+    }
+
+    #[test]
+    fn test_sparse_metrics() {
+        let metrics = SparseMetrics::new();
+        let candidate = Bitmap::from_iter([0, 1, 10, 25, 31]);
+        let matched = Bitmap::from_iter([1, 31]);
+        metrics.record(&candidate, &matched, 10);
+        assert_eq!(metrics.lookups(), 1);
+        assert_eq!(metrics.candidate_blocks(), 4);
+        assert_eq!(metrics.false_positive_blocks(), 2);
+    }
+
     #[test]
     fn test_inverted_index() {
         let mut index = InvertedIndex::<usize>::new();
@@ -217,7 +536,7 @@ mod tests {
 
     #[test]
     fn test_fusion_index() {
-        let mut index_1 = SparseIndex::<usize>::new(1);
+        let mut index_1 = SparseIndex::<usize>::new(SparseOptions::new(1));
         let mut index_2 = InvertedIndex::<usize>::new();
         index_1.insert(0, 0);
         index_1.insert(1, 1);
@@ -230,4 +549,34 @@ mod tests {
         b.add(1);
         assert_eq!(result, b);
     }
+
+    #[test]
+    fn test_ordered_index() {
+        let mut index = OrderedIndex::<i64, usize>::new();
+        index.insert_ordered(0, Some(8080), 1);
+        index.insert_ordered(1, Some(443), 2);
+        index.insert_ordered(2, None, 0);
+        index.insert_ordered(3, Some(8080), 1);
+        index.insert_ordered(4, Some(9090), 3);
+        assert_eq!(
+            index.range(Bound::Unbounded, Bound::Excluded(&8080)),
+            vec![2]
+        );
+        assert_eq!(
+            index.range(Bound::Included(&443), Bound::Included(&8080)),
+            vec![2, 1]
+        );
+        assert_eq!(
+            index.range(Bound::Excluded(&9090), Bound::Unbounded),
+            vec![]
+        );
+        assert_eq!(
+            index.range(Bound::Included(&9090), Bound::Included(&443)),
+            vec![]
+        );
+
+        let mut result = Bitmap::from_range(0..5);
+        index.lookup(&1, |set| result.and_inplace(set));
+        assert_eq!(result, Bitmap::from_iter([0, 3]));
+    }
 }
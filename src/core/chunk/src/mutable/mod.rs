@@ -1,30 +1,32 @@
+use std::borrow::Cow;
+
 use common::{
     column::{
-        field::Field,
+        field::{Field, FieldValue},
         label::{Label, LabelValue},
     },
     context::Context,
     query::{MatcherOp, ProjectionRef},
     schema::Schema,
     time::{Duration, Instant, Range},
-    Set,
+    try_yield, Set,
 };
 use croaring::Bitmap;
 
 use self::{
-    column::{label::LabelImpl, FilterError},
+    column::{label::LabelImpl, FilterError, WriteError},
     index::IndexImpl,
+    stats::{Cardinality, Sketch},
 };
-use crate::mutable::column::{
-    field::{
-        BoolField, FieldImpl, Float32Field, Float64Field, Int16Field, Int32Field, Int64Field,
-        Int8Field, UInt16Field, UInt32Field, UInt64Field, UInt8Field,
-    },
-    label::{BoolLabel, IPv4Label, IPv6Label, IntLabel, LabelColumn, StringLabel},
+use crate::mutable::column::field::{
+    BoolField, FieldImpl, Float32Field, Float64Field, Int16Field, Int32Field, Int64Field,
+    Int8Field, UInt16Field, UInt32Field, UInt64Field, UInt8Field,
 };
 
 pub mod column;
 pub mod index;
+pub mod stats;
+pub mod zone;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Meta {
@@ -35,7 +37,7 @@ pub struct Meta {
 }
 
 impl Meta {
-    fn new(start_at: Instant, unit: Duration, length: u32, width: u32) -> Self {
+    pub fn new(start_at: Instant, unit: Duration, length: u32, width: u32) -> Self {
         Self {
             start_at,
             unit,
@@ -43,12 +45,40 @@ impl Meta {
             width,
         }
     }
+
+    #[inline]
+    pub fn start_at(&self) -> Instant {
+        self.start_at
+    }
+
+    #[inline]
+    pub fn unit(&self) -> Duration {
+        self.unit
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    #[inline]
+    pub fn timestamp(&self, slot: usize) -> Instant {
+        self.start_at + self.unit * slot as u32
+    }
+
+    // time right after the last slot
+    #[inline]
+    pub fn end_at(&self) -> Instant {
+        self.timestamp(self.width as usize)
+    }
 }
 
 #[derive(Debug)]
 pub struct Records {
     pub labels: Vec<LabelImpl>,
     pub fields: Vec<FieldImpl>,
+    // where the slots of field lists are, for records scanned out of a chunk
+    pub meta: Option<Meta>,
 }
 
 impl Records {
@@ -56,16 +86,7 @@ impl Records {
         let labels = schema
             .labels
             .iter()
-            .map(|label| {
-                match label.r#type {
-                    Label::String(_) => Label::String(LabelColumn::<StringLabel>::new()),
-                    Label::IPv4(_) => Label::IPv4(LabelColumn::<IPv4Label>::new()),
-                    Label::IPv6(_) => Label::IPv6(LabelColumn::<IPv6Label>::new()),
-                    Label::Int(_) => Label::Int(LabelColumn::<IntLabel>::new()),
-                    Label::Bool(_) => Label::Bool(LabelColumn::<BoolLabel>::new()),
-                }
-                .into()
-            })
+            .map(|label| LabelImpl::new(&label.r#type))
             .collect();
 
         let fields = schema
@@ -89,7 +110,11 @@ impl Records {
             })
             .collect();
 
-        Self { labels, fields }
+        Self {
+            labels,
+            fields,
+            meta: None,
+        }
     }
 
     #[inline]
@@ -101,6 +126,12 @@ impl Records {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    // None when the records were not scanned out of a chunk
+    #[inline]
+    pub fn timestamp(&self, slot: usize) -> Option<Instant> {
+        self.meta.as_ref().map(|meta| meta.timestamp(slot))
+    }
 }
 
 #[derive(Debug)]
@@ -108,8 +139,13 @@ pub struct MutableChunk {
     pub records: Records,
     pub index: Vec<IndexImpl<usize>>,
     pub meta: Meta,
+    sketches: Option<Vec<Sketch>>,
 }
 
+// a matcher the index could not answer exactly, with the block size and the
+// candidate rows of a sparse index when the matcher is positive
+type Residual<'m> = (usize, &'m MatcherOp, Option<(u32, Bitmap)>);
+
 impl MutableChunk {
     pub fn new(
         schema: &Schema,
@@ -131,75 +167,160 @@ impl MutableChunk {
             records: data,
             index,
             meta,
+            sketches: None,
+        }
+    }
+
+    pub fn seal(&mut self) {
+        if self.sketches.is_none() {
+            self.sketches = Some(self.records.labels.iter().map(LabelImpl::sketch).collect());
+        }
+    }
+
+    #[inline]
+    pub fn is_sealed(&self) -> bool {
+        self.sketches.is_some()
+    }
+
+    #[inline]
+    pub fn cardinality(&self, label: usize) -> Cardinality {
+        match &self.sketches {
+            Some(sketches) => Cardinality::Estimated(sketches[label].count()),
+            None => Cardinality::Exact(self.records.labels[label].cardinality()),
         }
     }
 
-    pub fn push(&mut self, labels: Vec<Option<LabelValue>>) {
-        for ((value, column), index) in labels
+    #[inline]
+    pub fn sketch(&self, label: usize) -> Cow<'_, Sketch> {
+        match &self.sketches {
+            Some(sketches) => Cow::Borrowed(&sketches[label]),
+            None => Cow::Owned(self.records.labels[label].sketch()),
+        }
+    }
+
+    pub fn push(&mut self, labels: Vec<Option<LabelValue>>) -> Result<(), WriteError> {
+        if self.is_sealed() {
+            return Err(WriteError::Sealed);
+        }
+        for (position, (value, column)) in labels
             .into_iter()
             .zip(self.records.labels.iter_mut())
-            .zip(self.index.iter_mut())
+            .enumerate()
         {
+            let key = match &value {
+                Some(Label::Int(key)) => Some(*key),
+                _ => None,
+            };
             let value_id: usize = column.push(value);
-            index.insert(column.len() - 1, value_id);
+            if let Some(index) = self.index.get_mut(position) {
+                index.insert(column.len() - 1, value_id, key);
+            }
         }
 
         for column in &mut self.records.fields {
             column.push_zero();
         }
+        Ok(())
+    }
+
+    pub fn set(
+        &mut self,
+        row: usize,
+        field: usize,
+        at: Instant,
+        value: Option<FieldValue>,
+    ) -> Result<(), WriteError> {
+        let step = (at - self.meta.start_at) / self.meta.unit;
+        if !(0..self.meta.width as i64).contains(&step) {
+            return Err(WriteError::OutOfRange {
+                at,
+                range: self.range(),
+            });
+        }
+        self.records.fields[field].set(row, step as usize, value)
     }
 
     #[inline]
-    unsafe fn filter_by_index(
+    fn prune(&self, predicate: &[Vec<MatcherOp<f64>>]) -> Bitmap {
+        let mut row_set = Bitmap::from_range(0..self.records.labels[0].len() as u32);
+        for (field, predicate) in self.records.fields.iter().zip(predicate.iter()) {
+            for op in predicate {
+                if row_set.is_empty() {
+                    return row_set;
+                }
+                field.zone().prune(op, &mut row_set);
+            }
+        }
+        row_set
+    }
+
+    #[inline]
+    unsafe fn filter_by_index<'m>(
         &self,
         row_set: &mut Bitmap,
-        matcher: &[Option<MatcherOp>],
-    ) -> Result<(), FilterError> {
-        for ((label, matcher), index) in self
-            .records
-            .labels
-            .iter()
-            .zip(matcher.iter())
-            .zip(self.index.iter())
+        matcher: &'m [Vec<MatcherOp>],
+    ) -> Result<Vec<Residual<'m>>, FilterError> {
+        let mut residual = Vec::new();
+        for (position, (label, matchers)) in
+            self.records.labels.iter().zip(matcher.iter()).enumerate()
         {
-            if let Some(matcher) = matcher {
-                match matcher {
-                    MatcherOp::LiteralEqual(op) | MatcherOp::LiteralNotEqual(op) => {
-                        let value_id = label.lookup_value_id_unchecked(op);
-                        match value_id {
-                            Some(value_id) => index.filter(matcher, value_id, row_set),
-                            None => {
-                                row_set.clear();
-                                return Ok(());
-                            }
+            let index = self.index.get(position);
+            for matcher in matchers {
+                let value_ids = index
+                    .and_then(|index| index.range_value_ids(matcher))
+                    .or_else(|| label.lookup_value_ids_unchecked(matcher));
+                match (index, value_ids) {
+                    (Some(index), Some(value_ids)) => {
+                        if value_ids.is_empty() && matcher.positive() {
+                            row_set.clear();
+                            return Ok(residual);
+                        }
+                        let rows = index.rows(&value_ids);
+                        index.filter(matcher.positive(), &rows, row_set);
+                        if !index.exactly() {
+                            let sparse = index
+                                .block_size()
+                                .filter(|_| matcher.positive())
+                                .map(|block_size| (block_size, rows));
+                            residual.push((position, matcher, sparse));
                         }
                     }
-                    _ => {}
+                    _ => residual.push((position, matcher, None)),
                 }
             }
         }
-        Ok(())
+        Ok(residual)
     }
 
-    async unsafe fn filter_rows(
+    #[allow(clippy::missing_safety_doc)]
+    pub async unsafe fn filter_rows(
         &self,
         cx: &mut Context,
-        matcher: &[Option<MatcherOp>],
+        matcher: &[Vec<MatcherOp>],
+        predicate: &[Vec<MatcherOp<f64>>],
     ) -> Result<Bitmap, FilterError> {
-        let mut row_set = Bitmap::from_range(0..self.records.labels[0].len() as u32);
-
-        self.filter_by_index(&mut row_set, matcher)?;
+        let mut row_set = self.prune(predicate);
         if row_set.is_empty() {
             return Ok(row_set);
         }
 
-        if self.exactly(matcher) {
-            return Ok(row_set);
-        }
-
-        for (label, matcher) in self.records.labels.iter().zip(matcher.iter()) {
-            if let Some(matcher) = matcher {
-                label.filter(cx, matcher, &mut row_set).await?;
+        let residual = self.filter_by_index(&mut row_set, matcher)?;
+        for (position, matcher, sparse) in residual {
+            if row_set.is_empty() {
+                break;
+            }
+            let label = &self.records.labels[position];
+            match sparse {
+                Some((block_size, mut candidate)) => {
+                    candidate.and_inplace(&Bitmap::from_range(0..label.len() as u32));
+                    let mut hits = candidate.clone();
+                    label.filter(cx, matcher, &mut hits).await?;
+                    if let Some(metrics) = self.index[position].sparse_metrics() {
+                        metrics.record(&candidate, &hits, block_size);
+                    }
+                    row_set.and_inplace(&hits);
+                }
+                None => label.filter(cx, matcher, &mut row_set).await?,
             }
         }
 
@@ -252,44 +373,121 @@ impl MutableChunk {
             }
         };
 
-        Records { labels, fields }
+        let meta = Meta::new(
+            self.meta.timestamp(range.start),
+            self.meta.unit,
+            set.cardinality() as u32,
+            range.len() as u32,
+        );
+        Records {
+            labels,
+            fields,
+            meta: Some(meta),
+        }
+    }
+
+    // first slot at or after `at`, bounded by the chunk
+    #[inline]
+    fn slot(&self, at: Instant) -> usize {
+        let offset = (at - self.meta.start_at).as_millis();
+        let unit = self.meta.unit.as_millis();
+        let slot = offset.div_euclid(unit) + (offset.rem_euclid(unit) > 0) as i64;
+        slot.clamp(0, self.meta.width as i64) as usize
     }
 
     #[inline]
     fn trim_range(&self, range: Range) -> std::ops::Range<usize> {
-        let start = match range.start {
-            Some(start) => ((start - self.meta.start_at) / self.meta.unit) as usize,
-            None => 0,
-        };
-        let end = match range.end {
-            Some(end) => ((end - self.end_at()) / self.meta.unit) as usize,
-            None => self.meta.width as usize,
-        };
-        start..end
+        let start = range.start.map(|start| self.slot(start)).unwrap_or(0);
+        let end = range
+            .end
+            .map(|end| self.slot(end))
+            .unwrap_or(self.meta.width as usize);
+        start..end.max(start)
+    }
+
+    #[inline]
+    fn has_matcher(matcher: &[Vec<MatcherOp>]) -> bool {
+        matcher.iter().any(|matcher| !matcher.is_empty())
+    }
+
+    #[allow(clippy::missing_safety_doc)]
+    pub async unsafe fn label_names(
+        &self,
+        cx: &mut Context,
+        matcher: &[Vec<MatcherOp>],
+    ) -> Result<Option<Vec<usize>>, FilterError> {
+        if !Self::has_matcher(matcher) {
+            if self.records.labels[0].is_empty() {
+                return Ok(None);
+            }
+            return Ok(Some(
+                (0..self.records.labels.len())
+                    .filter(|label| self.records.labels[*label].cardinality() > 0)
+                    .collect(),
+            ));
+        }
+
+        let set = self.filter_rows(cx, matcher, &[]).await?;
+        if set.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            (0..self.records.labels.len())
+                .filter(|label| !self.records.labels[*label].row_value_ids(&set).is_empty())
+                .collect(),
+        ))
+    }
+
+    #[allow(clippy::missing_safety_doc)]
+    pub async unsafe fn label_values(
+        &self,
+        cx: &mut Context,
+        matcher: &[Vec<MatcherOp>],
+        label: usize,
+    ) -> Result<Vec<LabelValue>, FilterError> {
+        let column = &self.records.labels[label];
+        if !Self::has_matcher(matcher) {
+            return Ok(column.values(None));
+        }
+
+        let set = self.filter_rows(cx, matcher, &[]).await?;
+        Ok(column.values(Some(&column.row_value_ids(&set))))
+    }
+
+    #[allow(clippy::missing_safety_doc)]
+    pub async unsafe fn series(
+        &self,
+        cx: &mut Context,
+        matcher: &[Vec<MatcherOp>],
+    ) -> Result<Vec<Vec<Option<LabelValue>>>, FilterError> {
+        let set = self.filter_rows(cx, matcher, &[]).await?;
+        let mut series = Vec::with_capacity(set.cardinality() as usize);
+        for row in set.iter() {
+            series.push(
+                self.records
+                    .labels
+                    .iter()
+                    .map(|label| label.get(row as usize))
+                    .collect(),
+            );
+            try_yield!(cx);
+        }
+        Ok(series)
     }
 
     #[allow(clippy::missing_safety_doc)]
     pub async unsafe fn filter(
         &self,
         cx: &mut Context,
-        matcher: &[Option<MatcherOp>],
+        matcher: &[Vec<MatcherOp>],
+        predicate: &[Vec<MatcherOp<f64>>],
         projection: ProjectionRef<'_>,
         range: Range,
     ) -> Result<Records, FilterError> {
-        let set = self.filter_rows(cx, matcher).await?;
+        let set = self.filter_rows(cx, matcher, predicate).await?;
         Ok(self.map(cx, projection, set, range).await)
     }
 
-    #[inline]
-    fn exactly<V>(&self, matcher: &[Option<MatcherOp<V>>]) -> bool {
-        for (index, matcher) in self.index.iter().zip(matcher.iter()) {
-            if matcher.is_some() && !index.exactly() {
-                return false;
-            }
-        }
-        true
-    }
-
     #[inline]
     pub fn end_at(&self) -> Instant {
         self.meta.start_at + self.meta.unit * self.meta.width
@@ -309,17 +507,26 @@ mod tests {
     use std::net::{Ipv4Addr, Ipv6Addr};
 
     use common::{
-        column::label::{Label, LabelValue},
+        column::{
+            field::Field,
+            label::{Label, LabelValue},
+        },
         context::Context,
-        index::Index,
-        query::MatcherOp,
+        index::{Index, SparseOptions},
+        query::{Cidr, MatcherOp},
         time::{Duration, Instant},
     };
     use croaring::Bitmap;
+    use regex::Regex;
 
     use super::{
-        column::label::{BoolLabel, IPv4Label, IPv6Label, IntLabel, LabelColumn, StringLabel},
+        column::{
+            field::Float64Field,
+            label::{BoolLabel, IPv4Label, IPv6Label, IntLabel, LabelColumn, StringLabel},
+            WriteError,
+        },
         index::IndexImpl,
+        stats::Cardinality,
         Meta, MutableChunk, Records,
     };
 
@@ -335,6 +542,7 @@ mod tests {
                     Label::Bool(LabelColumn::<BoolLabel>::new()).into(),
                 ],
                 fields: vec![],
+                meta: None,
             },
             index: vec![IndexImpl::new(&Index::Inverted(()))],
             meta: Meta {
@@ -343,6 +551,7 @@ mod tests {
                 length: 0,
                 width: 0,
             },
+            sketches: None,
         };
 
         let others = vec![
@@ -356,28 +565,34 @@ mod tests {
             Some(LabelValue::Bool(true)),
         ];
 
-        chunk.push(vec![None, None, None, None, None]);
-        chunk.push(
-            [
-                vec![Some(LabelValue::String(Vec::from("hello")))],
-                others.clone(),
-            ]
-            .concat(),
-        );
-        chunk.push(
-            [
-                vec![Some(LabelValue::String(Vec::from("world")))],
-                others.clone(),
-            ]
-            .concat(),
-        );
-        chunk.push(
-            [
-                vec![Some(LabelValue::String(Vec::from("hello")))],
-                others.clone(),
-            ]
-            .concat(),
-        );
+        chunk.push(vec![None, None, None, None, None]).unwrap();
+        chunk
+            .push(
+                [
+                    vec![Some(LabelValue::String(Vec::from("hello")))],
+                    others.clone(),
+                ]
+                .concat(),
+            )
+            .unwrap();
+        chunk
+            .push(
+                [
+                    vec![Some(LabelValue::String(Vec::from("world")))],
+                    others.clone(),
+                ]
+                .concat(),
+            )
+            .unwrap();
+        chunk
+            .push(
+                [
+                    vec![Some(LabelValue::String(Vec::from("hello")))],
+                    others.clone(),
+                ]
+                .concat(),
+            )
+            .unwrap();
 
         futures_lite::future::block_on(async move {
             unsafe {
@@ -385,7 +600,14 @@ mod tests {
                 let set = chunk
                     .filter_rows(
                         &mut cx,
-                        &[Some(MatcherOp::LiteralEqual(None)), None, None, None, None],
+                        &[
+                            vec![MatcherOp::LiteralEqual(None)],
+                            vec![],
+                            vec![],
+                            vec![],
+                            vec![],
+                        ],
+                        &[],
                     )
                     .await
                     .unwrap();
@@ -395,16 +617,17 @@ mod tests {
                     .filter_rows(
                         &mut cx,
                         &[
-                            Some(MatcherOp::LiteralEqual(Some(LabelValue::String(
+                            vec![MatcherOp::LiteralEqual(Some(LabelValue::String(
                                 Vec::from("hello"),
-                            )))),
-                            Some(MatcherOp::LiteralEqual(Some(LabelValue::IPv4(
+                            )))],
+                            vec![MatcherOp::LiteralEqual(Some(LabelValue::IPv4(
                                 "127.0.0.1".parse::<Ipv4Addr>().unwrap().octets(),
-                            )))),
-                            None,
-                            Some(MatcherOp::LiteralEqual(Some(LabelValue::Int(1)))),
-                            None,
+                            )))],
+                            vec![],
+                            vec![MatcherOp::LiteralEqual(Some(LabelValue::Int(1)))],
+                            vec![],
                         ],
+                        &[],
                     )
                     .await
                     .unwrap();
@@ -412,4 +635,511 @@ mod tests {
             }
         })
     }
+
+    #[test]
+    fn chunk_filter_sparse() {
+        let mut chunk = MutableChunk {
+            records: Records {
+                labels: vec![
+                    Label::String(LabelColumn::<StringLabel>::new()).into(),
+                    Label::Int(LabelColumn::<IntLabel>::new()).into(),
+                ],
+                fields: vec![],
+                meta: None,
+            },
+            index: vec![
+                IndexImpl::new(&Index::Sparse(SparseOptions::new(2))),
+                IndexImpl::new(&Index::Inverted(())),
+            ],
+            meta: Meta {
+                start_at: Instant::now(),
+                unit: Duration::from_secs(1),
+                length: 0,
+                width: 0,
+            },
+            sketches: None,
+        };
+        for (name, id) in [("a", 1), ("b", 1), ("b", 2), ("c", 2), ("a", 2), ("b", 2)] {
+            chunk
+                .push(vec![
+                    Some(LabelValue::String(Vec::from(name))),
+                    Some(LabelValue::Int(id)),
+                ])
+                .unwrap();
+        }
+
+        futures_lite::future::block_on(async move {
+            unsafe {
+                let mut cx = Context::new(256);
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![MatcherOp::LiteralEqual(Some(LabelValue::String(
+                                Vec::from("a"),
+                            )))],
+                            vec![MatcherOp::LiteralEqual(Some(LabelValue::Int(2)))],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([4]));
+
+                // the first block holds "a" although the other matcher leaves it out
+                let metrics = chunk.index[0].sparse_metrics().unwrap();
+                assert_eq!(metrics.lookups(), 1);
+                assert_eq!(
+                    metrics.candidate_blocks() - metrics.false_positive_blocks(),
+                    2
+                );
+                assert!(chunk.index[1].sparse_metrics().is_none());
+            }
+        })
+    }
+
+    #[test]
+    fn chunk_filter_cidr() {
+        let mut chunk = MutableChunk {
+            records: Records {
+                labels: vec![
+                    Label::IPv4(LabelColumn::<IPv4Label>::new()).into(),
+                    Label::IPv6(LabelColumn::<IPv6Label>::new()).into(),
+                ],
+                fields: vec![],
+                meta: None,
+            },
+            index: vec![IndexImpl::new(&Index::Inverted(()))],
+            meta: Meta {
+                start_at: Instant::now(),
+                unit: Duration::from_secs(1),
+                length: 0,
+                width: 0,
+            },
+            sketches: None,
+        };
+
+        for (ipv4, ipv6) in [
+            ("10.0.0.1", "fe80::1"),
+            ("10.1.0.1", "2001:db8::1"),
+            ("192.168.0.1", "fe80::2"),
+            ("10.0.0.1", "::1"),
+        ] {
+            chunk
+                .push(vec![
+                    Some(LabelValue::IPv4(ipv4.parse::<Ipv4Addr>().unwrap().octets())),
+                    Some(LabelValue::IPv6(ipv6.parse::<Ipv6Addr>().unwrap().octets())),
+                ])
+                .unwrap();
+        }
+        chunk.push(vec![None, None]).unwrap();
+
+        futures_lite::future::block_on(async move {
+            unsafe {
+                let mut cx = Context::new(256);
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![MatcherOp::CidrMatch("10.0.0.0/8".parse().unwrap())],
+                            vec![],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([0, 1, 3]));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![MatcherOp::CidrNotMatch("10.0.0.0/16".parse().unwrap())],
+                            vec![MatcherOp::CidrMatch("fe80::/10".parse().unwrap())],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([2]));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![MatcherOp::CidrMatch("172.16.0.0/12".parse().unwrap())],
+                            vec![],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert!(set.is_empty());
+
+                let cidr = "fe80::/10".parse::<Cidr>().unwrap();
+                assert!(chunk
+                    .filter_rows(&mut cx, &[vec![MatcherOp::CidrMatch(cidr)], vec![]], &[])
+                    .await
+                    .is_err());
+            }
+        })
+    }
+
+    #[test]
+    fn chunk_filter_range() {
+        let mut chunk = MutableChunk {
+            records: Records {
+                labels: vec![
+                    Label::Int(LabelColumn::<IntLabel>::new()).into(),
+                    Label::Int(LabelColumn::<IntLabel>::new()).into(),
+                ],
+                fields: vec![],
+                meta: None,
+            },
+            index: vec![
+                IndexImpl::new(&Index::Ordered(())),
+                IndexImpl::new(&Index::Inverted(())),
+            ],
+            meta: Meta {
+                start_at: Instant::now(),
+                unit: Duration::from_secs(1),
+                length: 0,
+                width: 0,
+            },
+            sketches: None,
+        };
+
+        for (shard, port) in [(Some(0), 80), (Some(3), 443), (Some(7), 8080), (None, 9090)] {
+            chunk
+                .push(vec![
+                    shard.map(LabelValue::Int),
+                    Some(LabelValue::Int(port)),
+                ])
+                .unwrap();
+        }
+
+        futures_lite::future::block_on(async move {
+            unsafe {
+                let mut cx = Context::new(256);
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[vec![MatcherOp::LessThan(LabelValue::Int(7))], vec![]],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([0, 1]));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![MatcherOp::GreaterEqual(LabelValue::Int(3))],
+                            vec![MatcherOp::Between(
+                                LabelValue::Int(100),
+                                LabelValue::Int(1000),
+                            )],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([1]));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[vec![], vec![MatcherOp::GreaterThan(LabelValue::Int(8080))]],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([3]));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![MatcherOp::Between(LabelValue::Int(8), LabelValue::Int(1))],
+                            vec![],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert!(set.is_empty());
+            }
+        })
+    }
+
+    #[test]
+    fn chunk_filter_in() {
+        let mut chunk = MutableChunk {
+            records: Records {
+                labels: vec![
+                    Label::String(LabelColumn::<StringLabel>::new()).into(),
+                    Label::String(LabelColumn::<StringLabel>::new()).into(),
+                ],
+                fields: vec![],
+                meta: None,
+            },
+            index: vec![IndexImpl::new(&Index::Inverted(()))],
+            meta: Meta {
+                start_at: Instant::now(),
+                unit: Duration::from_secs(1),
+                length: 0,
+                width: 0,
+            },
+            sketches: None,
+        };
+
+        for (host, env) in [
+            (Some("a"), "prod"),
+            (Some("b"), "prod"),
+            (Some("c"), "dev"),
+            (None, "test"),
+        ] {
+            chunk
+                .push(vec![
+                    host.map(|host| LabelValue::String(Vec::from(host))),
+                    Some(LabelValue::String(Vec::from(env))),
+                ])
+                .unwrap();
+        }
+
+        let values = |values: &[&str]| {
+            values
+                .iter()
+                .map(|value| LabelValue::String(Vec::from(*value)))
+                .collect::<Vec<_>>()
+        };
+
+        futures_lite::future::block_on(async move {
+            unsafe {
+                let mut cx = Context::new(256);
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[vec![MatcherOp::In(values(&["a", "c", "z"]))], vec![]],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([0, 2]));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![MatcherOp::NotIn(values(&["a"]))],
+                            vec![MatcherOp::In(values(&["prod", "test"]))],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([1, 3]));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[vec![], vec![MatcherOp::NotIn(values(&["prod"]))]],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([2, 3]));
+
+                let set = chunk
+                    .filter_rows(&mut cx, &[vec![MatcherOp::In(values(&["z"]))], vec![]], &[])
+                    .await
+                    .unwrap();
+                assert!(set.is_empty());
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[
+                            vec![
+                                MatcherOp::In(values(&["a", "b", "c"])),
+                                MatcherOp::LiteralNotEqual(Some(LabelValue::String(Vec::from(
+                                    "b",
+                                )))),
+                            ],
+                            vec![
+                                MatcherOp::RegexMatch(Regex::new("^(prod|dev)$").unwrap()),
+                                MatcherOp::NotIn(values(&["dev"])),
+                            ],
+                        ],
+                        &[],
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_iter([0]));
+            }
+        })
+    }
+
+    #[test]
+    fn chunk_prune_by_zone() {
+        let start_at = Instant::now();
+        let mut chunk = MutableChunk {
+            records: Records {
+                labels: vec![Label::String(LabelColumn::<StringLabel>::new()).into()],
+                fields: vec![Field::Float64(Float64Field::new(4)).into()],
+                meta: None,
+            },
+            index: vec![],
+            meta: Meta {
+                start_at,
+                unit: Duration::from_secs(1),
+                length: 0,
+                width: 4,
+            },
+            sketches: None,
+        };
+
+        for host in ["a", "b"] {
+            chunk
+                .push(vec![Some(LabelValue::String(Vec::from(host)))])
+                .unwrap();
+        }
+        chunk
+            .set(0, 0, start_at, Some(Field::Float64(1.0)))
+            .unwrap();
+        chunk
+            .set(
+                1,
+                0,
+                start_at + Duration::from_secs(3),
+                Some(Field::Float64(150.0)),
+            )
+            .unwrap();
+        chunk
+            .set(1, 0, start_at + Duration::from_secs(3), None)
+            .unwrap();
+        chunk
+            .set(
+                1,
+                0,
+                start_at + Duration::from_secs(2),
+                Some(Field::Float64(120.0)),
+            )
+            .unwrap();
+
+        let zone = chunk.records.fields[0].zone().chunk();
+        assert_eq!((zone.min, zone.max, zone.nulls), (1.0, 150.0, 6));
+
+        futures_lite::future::block_on(async move {
+            unsafe {
+                let mut cx = Context::new(256);
+                let set = chunk
+                    .filter_rows(&mut cx, &[vec![]], &[vec![MatcherOp::GreaterThan(100.0)]])
+                    .await
+                    .unwrap();
+                assert_eq!(set, Bitmap::from_range(0..2));
+
+                let set = chunk
+                    .filter_rows(
+                        &mut cx,
+                        &[vec![]],
+                        &[vec![
+                            MatcherOp::GreaterThan(100.0),
+                            MatcherOp::LessThan(0.0),
+                        ]],
+                    )
+                    .await
+                    .unwrap();
+                assert!(set.is_empty());
+            }
+        })
+    }
+
+    #[test]
+    fn chunk_write_errors() {
+        let start_at = Instant::now();
+        let mut chunk = MutableChunk {
+            records: Records {
+                labels: vec![Label::String(LabelColumn::<StringLabel>::new()).into()],
+                fields: vec![Field::Float64(Float64Field::new(2)).into()],
+                meta: None,
+            },
+            index: vec![],
+            meta: Meta {
+                start_at,
+                unit: Duration::from_secs(1),
+                length: 0,
+                width: 2,
+            },
+            sketches: None,
+        };
+
+        chunk
+            .push(vec![Some(LabelValue::String(Vec::from("a")))])
+            .unwrap();
+        assert!(matches!(
+            chunk.set(0, 0, start_at + Duration::from_secs(2), None),
+            Err(WriteError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            chunk.set(0, 0, start_at, Some(Field::Bool(true))),
+            Err(WriteError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            chunk.set(1, 0, start_at, Some(Field::Float64(1.0))),
+            Err(WriteError::MissingRow { row: 1 })
+        ));
+        chunk
+            .set(0, 0, start_at, Some(Field::Float64(1.0)))
+            .unwrap();
+
+        chunk.seal();
+        assert!(matches!(chunk.push(vec![None]), Err(WriteError::Sealed)));
+    }
+
+    #[test]
+    fn chunk_cardinality() {
+        let mut chunk = MutableChunk {
+            records: Records {
+                labels: vec![
+                    Label::String(LabelColumn::<StringLabel>::new()).into(),
+                    Label::Int(LabelColumn::<IntLabel>::new()).into(),
+                ],
+                fields: vec![],
+                meta: None,
+            },
+            index: vec![],
+            meta: Meta {
+                start_at: Instant::now(),
+                unit: Duration::from_secs(1),
+                length: 0,
+                width: 0,
+            },
+            sketches: None,
+        };
+
+        for id in 0..1000 {
+            chunk
+                .push(vec![
+                    Some(LabelValue::String(Vec::from(format!("host-{}", id % 100)))),
+                    Some(LabelValue::Int(id)),
+                ])
+                .unwrap();
+        }
+        chunk.push(vec![None, None]).unwrap();
+        assert_eq!(chunk.cardinality(0), Cardinality::Exact(100));
+        assert_eq!(chunk.cardinality(1), Cardinality::Exact(1000));
+
+        chunk.seal();
+        assert!(chunk.is_sealed());
+        for (label, expect) in [(0, 100.0), (1, 1000.0)] {
+            match chunk.cardinality(label) {
+                Cardinality::Estimated(value) => {
+                    assert!((value as f64 - expect).abs() / expect < 0.05, "{}", value)
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
 }
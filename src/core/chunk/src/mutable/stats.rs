@@ -0,0 +1,21 @@
+use pdatastructs::hyperloglog::HyperLogLog;
+
+// 2^12 registers, about 1.6% relative error
+pub const SKETCH_BITS: usize = 12;
+
+pub type Sketch = HyperLogLog<u64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cardinality {
+    Exact(usize),
+    Estimated(usize),
+}
+
+impl Cardinality {
+    #[inline]
+    pub fn value(&self) -> usize {
+        match self {
+            Cardinality::Exact(value) | Cardinality::Estimated(value) => *value,
+        }
+    }
+}
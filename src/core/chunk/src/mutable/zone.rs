@@ -0,0 +1,227 @@
+use std::ops::Bound;
+
+use common::query::MatcherOp;
+use croaring::Bitmap;
+
+pub const BLOCK_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoneMap {
+    pub min: f64,
+    pub max: f64,
+    pub nulls: u64,
+}
+
+impl Default for ZoneMap {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            nulls: 0,
+        }
+    }
+}
+
+impl ZoneMap {
+    // true if no value has been written, nulls may still exist
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    #[inline]
+    fn update(&mut self, value: f64) {
+        if value < self.min {
+            self.min = value;
+        }
+        if value > self.max {
+            self.max = value;
+        }
+    }
+
+    #[inline]
+    fn contains(&self, value: f64) -> bool {
+        self.min <= value && value <= self.max
+    }
+
+    pub fn may_match(&self, op: &MatcherOp<f64>) -> bool {
+        match op {
+            MatcherOp::LiteralEqual(Some(value)) => self.contains(*value),
+            MatcherOp::LiteralEqual(None) => self.nulls > 0,
+            MatcherOp::LiteralNotEqual(Some(value)) => {
+                !self.is_empty() && !(self.min == *value && self.max == *value)
+            }
+            MatcherOp::LiteralNotEqual(None) => !self.is_empty(),
+            MatcherOp::In(values) => values.iter().any(|value| self.contains(*value)),
+            MatcherOp::NotIn(values) => {
+                !self.is_empty() && !(self.min == self.max && values.contains(&self.min))
+            }
+            op => match op.bounds() {
+                Some((start, end)) => {
+                    let start = match start {
+                        Bound::Included(start) => *start <= self.max,
+                        Bound::Excluded(start) => *start < self.max,
+                        Bound::Unbounded => true,
+                    };
+                    let end = match end {
+                        Bound::Included(end) => self.min <= *end,
+                        Bound::Excluded(end) => self.min < *end,
+                        Bound::Unbounded => true,
+                    };
+                    !self.is_empty() && start && end
+                }
+                None => true,
+            },
+        }
+    }
+}
+
+// zone maps live in memory only, they are maintained on every write and
+// dropped together with the chunk, nothing is persisted
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneMaps {
+    chunk: ZoneMap,
+    blocks: Vec<ZoneMap>,
+    block_size: usize,
+}
+
+impl Default for ZoneMaps {
+    #[inline]
+    fn default() -> Self {
+        Self::new(BLOCK_SIZE)
+    }
+}
+
+impl ZoneMaps {
+    #[inline]
+    pub fn new(block_size: usize) -> Self {
+        Self {
+            chunk: ZoneMap::default(),
+            blocks: Vec::new(),
+            block_size,
+        }
+    }
+
+    #[inline]
+    pub fn chunk(&self) -> &ZoneMap {
+        &self.chunk
+    }
+
+    #[inline]
+    pub fn blocks(&self) -> &[ZoneMap] {
+        &self.blocks
+    }
+
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    #[inline]
+    fn block(&mut self, row: usize) -> usize {
+        let block = row / self.block_size;
+        if self.blocks.len() <= block {
+            self.blocks.resize_with(block + 1, ZoneMap::default);
+        }
+        block
+    }
+
+    #[inline]
+    pub(crate) fn push_nulls(&mut self, row: usize, count: usize) {
+        let block = self.block(row);
+        self.chunk.nulls += count as u64;
+        self.blocks[block].nulls += count as u64;
+    }
+
+    pub(crate) fn set(&mut self, row: usize, was_null: bool, value: Option<f64>) {
+        let nulls = match (was_null, value.is_some()) {
+            (true, true) => -1,
+            (false, false) => 1,
+            _ => 0,
+        };
+        let block = self.block(row);
+        for zone in [&mut self.chunk, &mut self.blocks[block]] {
+            zone.nulls = zone.nulls.wrapping_add_signed(nulls);
+            if let Some(value) = value {
+                zone.update(value);
+            }
+        }
+    }
+
+    pub fn prune(&self, op: &MatcherOp<f64>, row_set: &mut Bitmap) {
+        if !self.chunk.may_match(op) {
+            row_set.clear();
+            return;
+        }
+
+        let mut set = Bitmap::create();
+        for (offset, block) in self.blocks.iter().enumerate() {
+            if block.may_match(op) {
+                set.add_range(
+                    (offset * self.block_size) as u32..((offset + 1) * self.block_size) as u32,
+                );
+            }
+        }
+        row_set.and_inplace(&set);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::query::MatcherOp;
+    use croaring::Bitmap;
+
+    use super::{ZoneMap, ZoneMaps};
+
+    #[test]
+    fn zone_map_match() {
+        let zone = ZoneMap {
+            min: 10.0,
+            max: 20.0,
+            nulls: 0,
+        };
+        assert!(zone.may_match(&MatcherOp::LiteralEqual(Some(10.0))));
+        assert!(!zone.may_match(&MatcherOp::LiteralEqual(Some(21.0))));
+        assert!(!zone.may_match(&MatcherOp::LiteralEqual(None)));
+        assert!(zone.may_match(&MatcherOp::GreaterThan(15.0)));
+        assert!(!zone.may_match(&MatcherOp::GreaterThan(20.0)));
+        assert!(zone.may_match(&MatcherOp::GreaterEqual(20.0)));
+        assert!(!zone.may_match(&MatcherOp::LessThan(10.0)));
+        assert!(zone.may_match(&MatcherOp::Between(0.0, 10.0)));
+        assert!(!zone.may_match(&MatcherOp::In(vec![1.0, 30.0])));
+        assert!(!ZoneMap::default().may_match(&MatcherOp::LessThan(f64::INFINITY)));
+
+        let zone = ZoneMap {
+            min: 5.0,
+            max: 5.0,
+            nulls: 1,
+        };
+        assert!(!zone.may_match(&MatcherOp::LiteralNotEqual(Some(5.0))));
+        assert!(zone.may_match(&MatcherOp::LiteralEqual(None)));
+    }
+
+    #[test]
+    fn zone_maps_prune() {
+        let mut zones = ZoneMaps::new(2);
+        for row in 0..6 {
+            zones.push_nulls(row, 2);
+        }
+        zones.set(0, true, Some(1.0));
+        zones.set(1, true, Some(2.0));
+        zones.set(4, true, Some(100.0));
+        zones.set(4, false, Some(120.0));
+        zones.set(5, true, Some(3.0));
+        zones.set(5, false, None);
+        assert_eq!(zones.chunk().nulls, 9);
+        assert_eq!((zones.chunk().min, zones.chunk().max), (1.0, 120.0));
+
+        let mut row_set = Bitmap::from_range(0..6);
+        zones.prune(&MatcherOp::GreaterThan(50.0), &mut row_set);
+        assert_eq!(row_set, Bitmap::from_range(4..6));
+
+        let mut row_set = Bitmap::from_range(0..6);
+        zones.prune(&MatcherOp::GreaterThan(200.0), &mut row_set);
+        assert!(row_set.is_empty());
+    }
+}
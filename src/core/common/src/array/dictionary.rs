@@ -104,6 +104,19 @@ where
             Some(self.data.get_unchecked_mut(id - 1))
         }
     }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, A::ItemRef<'_>)> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(id, value)| (id + 1, value))
+    }
 }
 
 #[cfg(test)]
@@ -90,7 +90,7 @@ impl<P: Primitive> Array for FixedListArray<P> {
 
     #[inline]
     fn len(&self) -> usize {
-        self.data.len() / self.list_size()
+        self.data.len().checked_div(self.list_size()).unwrap_or(0)
     }
 }
 
@@ -214,7 +214,7 @@ impl<P: Primitive> Array for OptionalFixedListArray<P> {
 
     #[inline]
     fn get(&self, id: usize) -> Option<Self::ItemRef<'_>> {
-        if id * self.data.list_size() > self.data.data.len() {
+        if id * self.data.list_size() >= self.data.data.len() {
             None
         } else {
             Some(unsafe { self.get_unchecked(id) })
@@ -17,6 +17,26 @@ where
         self.values.lookup(value)
     }
 
+    #[inline]
+    pub fn cardinality(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn id(&self, row: usize) -> Option<usize> {
+        self.data.get(row).copied()
+    }
+
+    #[inline]
+    pub fn value(&self, id: usize) -> Option<A::ItemRef<'_>> {
+        self.values.get(id).flatten()
+    }
+
+    #[inline]
+    pub fn values(&self) -> impl Iterator<Item = (usize, A::ItemRef<'_>)> {
+        self.values.iter()
+    }
+
     pub fn push_and_get_id(&mut self, value: <Self as Array>::Item) -> usize {
         match value {
             Some(value) => {
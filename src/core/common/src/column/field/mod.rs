@@ -16,6 +16,27 @@ pub enum Field<U8, U16, U32, U64, I8, I16, I32, I64, F32, F64, B> {
     Bool(B),
 }
 
+impl<U8, U16, U32, U64, I8, I16, I32, I64, F32, F64, B>
+    Field<U8, U16, U32, U64, I8, I16, I32, I64, F32, F64, B>
+{
+    pub fn r#type(&self) -> FieldType {
+        match self {
+            Field::UInt8(_) => Field::UInt8(()),
+            Field::UInt16(_) => Field::UInt16(()),
+            Field::UInt32(_) => Field::UInt32(()),
+            Field::UInt64(_) => Field::UInt64(()),
+            Field::Int8(_) => Field::Int8(()),
+            Field::Int16(_) => Field::Int16(()),
+            Field::Int32(_) => Field::Int32(()),
+            Field::Int64(_) => Field::Int64(()),
+            Field::Float32(_) => Field::Float32(()),
+            Field::Float64(_) => Field::Float64(()),
+            Field::Bool(_) => Field::Bool(()),
+        }
+        .into()
+    }
+}
+
 type TypeInner = Field<(), (), (), (), (), (), (), (), (), (), ()>;
 
 #[derive(Clone)]
@@ -58,3 +79,5 @@ impl Display for FieldType {
         Debug::fmt(&self, f)
     }
 }
+
+pub type FieldValue = Field<u8, u16, u32, u64, i8, i16, i32, i64, f32, f64, bool>;
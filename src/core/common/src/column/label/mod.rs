@@ -1,6 +1,9 @@
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, Ipv6Addr},
+};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(C)]
 pub enum Label<S, IP4, IP6, I, B> {
     String(S),
@@ -37,3 +40,15 @@ impl Display for Label<(), (), (), (), ()> {
 }
 
 pub type LabelValue = Label<Vec<u8>, [u8; 4], [u8; 16], i64, bool>;
+
+impl Display for LabelValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Label::String(value) => f.write_str(&String::from_utf8_lossy(value)),
+            Label::IPv4(value) => write!(f, "{}", Ipv4Addr::from(*value)),
+            Label::IPv6(value) => write!(f, "{}", Ipv6Addr::from(*value)),
+            Label::Int(value) => write!(f, "{}", value),
+            Label::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
@@ -1,5 +1,68 @@
+use thiserror::Error;
+
 #[derive(Debug, PartialEq, Clone)]
-pub enum Index<Inverted, Sparse> {
+pub enum Index<Inverted, Sparse, Ordered> {
     Inverted(Inverted),
     Sparse(Sparse),
+    Ordered(Ordered),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FilterKind {
+    Bloom,
+    Xor,
+    BinaryFuse,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SparseOptionsError {
+    #[error("false positive probability {} is not in (0, 1)", .0)]
+    InvalidFpp(f64),
+    #[error("{:?} filters hold at most 16-bit fingerprints, fpp {} is below 1/65536", .filter, .fpp)]
+    FppTooLow { filter: FilterKind, fpp: f64 },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SparseOptions {
+    pub block_size: u32,
+    filter: FilterKind,
+    // xor and binary fuse filters use 8-bit fingerprints if fpp >= 1/256, 16-bit otherwise
+    fpp: f64,
+}
+
+impl SparseOptions {
+    pub const MIN_FINGERPRINT_FPP: f64 = 1.0 / 65536.0;
+
+    #[inline]
+    pub fn new(block_size: u32) -> Self {
+        Self {
+            block_size,
+            filter: FilterKind::Bloom,
+            fpp: 1.0 / 100.0,
+        }
+    }
+
+    pub fn with_filter(self, filter: FilterKind, fpp: f64) -> Result<Self, SparseOptionsError> {
+        if !(fpp > 0.0 && fpp < 1.0) {
+            return Err(SparseOptionsError::InvalidFpp(fpp));
+        }
+        if filter != FilterKind::Bloom && fpp < Self::MIN_FINGERPRINT_FPP {
+            return Err(SparseOptionsError::FppTooLow { filter, fpp });
+        }
+        Ok(Self {
+            filter,
+            fpp,
+            ..self
+        })
+    }
+
+    #[inline]
+    pub fn filter(&self) -> FilterKind {
+        self.filter
+    }
+
+    #[inline]
+    pub fn fpp(&self) -> f64 {
+        self.fpp
+    }
 }
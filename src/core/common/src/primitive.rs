@@ -1,12 +1,25 @@
-pub trait Primitive: 'static + Default + Clone + Copy + PartialEq {}
+pub trait Primitive: 'static + Default + Clone + Copy + PartialEq {
+    fn as_f64(self) -> f64;
+}
 
 macro_rules! native_type {
     ($type:ty) => {
-        impl Primitive for $type {}
+        impl Primitive for $type {
+            #[inline]
+            fn as_f64(self) -> f64 {
+                self as f64
+            }
+        }
     };
 }
 
-native_type!(bool);
+impl Primitive for bool {
+    #[inline]
+    fn as_f64(self) -> f64 {
+        self as u8 as f64
+    }
+}
+
 native_type!(u8);
 native_type!(u16);
 native_type!(u32);
@@ -1,7 +1,17 @@
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::Bound,
+    str::FromStr,
+};
+
 use regex::Regex;
+use thiserror::Error;
 
 use crate::{
-    column::{label::LabelValue, ColumnType},
+    column::{
+        label::{LabelType, LabelValue},
+        ColumnType,
+    },
     Set,
 };
 
@@ -65,6 +75,15 @@ pub enum MatcherOp<V = LabelValue> {
     LiteralNotEqual(Option<V>),
     RegexMatch(Regex),
     RegexNotMatch(Regex),
+    In(Vec<V>),
+    NotIn(Vec<V>),
+    CidrMatch(Cidr),
+    CidrNotMatch(Cidr),
+    LessThan(V),
+    LessEqual(V),
+    GreaterThan(V),
+    GreaterEqual(V),
+    Between(V, V),
 }
 
 impl<V: PartialEq> PartialEq for MatcherOp<V> {
@@ -74,6 +93,15 @@ impl<V: PartialEq> PartialEq for MatcherOp<V> {
             (Self::LiteralNotEqual(l0), Self::LiteralNotEqual(r0)) => l0 == r0,
             (Self::RegexMatch(l0), Self::RegexMatch(r0)) => l0.as_str() == r0.as_str(),
             (Self::RegexNotMatch(l0), Self::RegexNotMatch(r0)) => l0.as_str() == r0.as_str(),
+            (Self::In(l0), Self::In(r0)) => l0 == r0,
+            (Self::NotIn(l0), Self::NotIn(r0)) => l0 == r0,
+            (Self::CidrMatch(l0), Self::CidrMatch(r0)) => l0 == r0,
+            (Self::CidrNotMatch(l0), Self::CidrNotMatch(r0)) => l0 == r0,
+            (Self::LessThan(l0), Self::LessThan(r0)) => l0 == r0,
+            (Self::LessEqual(l0), Self::LessEqual(r0)) => l0 == r0,
+            (Self::GreaterThan(l0), Self::GreaterThan(r0)) => l0 == r0,
+            (Self::GreaterEqual(l0), Self::GreaterEqual(r0)) => l0 == r0,
+            (Self::Between(l0, l1), Self::Between(r0, r1)) => l0 == r0 && l1 == r1,
             _ => false,
         }
     }
@@ -86,6 +114,167 @@ impl<V> MatcherOp<V> {
             MatcherOp::LiteralNotEqual(_) => false,
             MatcherOp::RegexMatch(_) => true,
             MatcherOp::RegexNotMatch(_) => false,
+            MatcherOp::In(_) => true,
+            MatcherOp::NotIn(_) => false,
+            MatcherOp::CidrMatch(_) => true,
+            MatcherOp::CidrNotMatch(_) => false,
+            MatcherOp::LessThan(_)
+            | MatcherOp::LessEqual(_)
+            | MatcherOp::GreaterThan(_)
+            | MatcherOp::GreaterEqual(_)
+            | MatcherOp::Between(_, _) => true,
+        }
+    }
+
+    pub fn bounds(&self) -> Option<(Bound<&V>, Bound<&V>)> {
+        match self {
+            MatcherOp::LessThan(end) => Some((Bound::Unbounded, Bound::Excluded(end))),
+            MatcherOp::LessEqual(end) => Some((Bound::Unbounded, Bound::Included(end))),
+            MatcherOp::GreaterThan(start) => Some((Bound::Excluded(start), Bound::Unbounded)),
+            MatcherOp::GreaterEqual(start) => Some((Bound::Included(start), Bound::Unbounded)),
+            MatcherOp::Between(start, end) => Some((Bound::Included(start), Bound::Included(end))),
+            _ => None,
+        }
+    }
+}
+
+impl MatcherOp<LabelValue> {
+    pub fn int_bounds(&self) -> Option<(Bound<&i64>, Bound<&i64>)> {
+        #[inline]
+        fn int(bound: Bound<&LabelValue>) -> Option<Bound<&i64>> {
+            match bound {
+                Bound::Included(LabelValue::Int(value)) => Some(Bound::Included(value)),
+                Bound::Excluded(LabelValue::Int(value)) => Some(Bound::Excluded(value)),
+                Bound::Unbounded => Some(Bound::Unbounded),
+                _ => None,
+            }
+        }
+
+        let (start, end) = self.bounds()?;
+        Some((int(start)?, int(end)?))
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CidrError {
+    #[error("invalid cidr notation: {}", .0)]
+    InvalidNotation(String),
+    #[error("prefix length {} exceeds {} bits", .len, .max)]
+    PrefixTooLong { len: u8, max: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prefix<const SIZE: usize> {
+    network: [u8; SIZE],
+    len: u8,
+}
+
+impl<const SIZE: usize> Prefix<SIZE> {
+    pub fn new(mut network: [u8; SIZE], len: u8) -> Result<Self, CidrError> {
+        if len as usize > SIZE * 8 {
+            return Err(CidrError::PrefixTooLong {
+                len,
+                max: (SIZE * 8) as u8,
+            });
+        }
+        for (offset, byte) in network.iter_mut().enumerate() {
+            *byte &= Self::mask(len, offset);
+        }
+        Ok(Self { network, len })
+    }
+
+    #[inline]
+    fn mask(len: u8, offset: usize) -> u8 {
+        let bits = (len as usize).saturating_sub(offset * 8).min(8);
+        (!0_u16 << (8 - bits)) as u8
+    }
+
+    #[inline]
+    pub fn contains(&self, addr: &[u8; SIZE]) -> bool {
+        self.network
+            .iter()
+            .zip(addr.iter())
+            .enumerate()
+            .all(|(offset, (network, addr))| addr & Self::mask(self.len, offset) == *network)
+    }
+
+    #[inline]
+    pub fn network(&self) -> &[u8; SIZE] {
+        &self.network
+    }
+
+    #[inline]
+    pub fn len(&self) -> u8 {
+        self.len
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cidr {
+    IPv4(Prefix<4>),
+    IPv6(Prefix<16>),
+}
+
+impl Cidr {
+    #[inline]
+    pub fn r#type(&self) -> LabelType {
+        match self {
+            Cidr::IPv4(_) => LabelType::IPv4(()),
+            Cidr::IPv6(_) => LabelType::IPv6(()),
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = CidrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CidrError::InvalidNotation(s.to_owned());
+        let (addr, len) = s.split_once('/').ok_or_else(invalid)?;
+        let len = len.parse::<u8>().map_err(|_| invalid())?;
+        match addr.parse::<IpAddr>().map_err(|_| invalid())? {
+            IpAddr::V4(addr) => Ok(Cidr::IPv4(Prefix::new(addr.octets(), len)?)),
+            IpAddr::V6(addr) => Ok(Cidr::IPv6(Prefix::new(addr.octets(), len)?)),
+        }
+    }
+}
+
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cidr::IPv4(prefix) => write!(f, "{}/{}", Ipv4Addr::from(prefix.network), prefix.len),
+            Cidr::IPv6(prefix) => write!(f, "{}/{}", Ipv6Addr::from(prefix.network), prefix.len),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::{Cidr, CidrError};
+
+    #[test]
+    fn cidr_contains() {
+        let cidr = "10.1.2.3/8".parse::<Cidr>().unwrap();
+        assert_eq!(cidr.to_string(), "10.0.0.0/8");
+        let Cidr::IPv4(prefix) = cidr else {
+            unreachable!()
+        };
+        assert!(prefix.contains(&"10.255.0.1".parse::<Ipv4Addr>().unwrap().octets()));
+        assert!(!prefix.contains(&"11.0.0.1".parse::<Ipv4Addr>().unwrap().octets()));
+
+        let Cidr::IPv6(prefix) = "fe80::/10".parse::<Cidr>().unwrap() else {
+            unreachable!()
+        };
+        assert!(prefix.contains(&"fe80::1".parse::<Ipv6Addr>().unwrap().octets()));
+        assert!(prefix.contains(&"febf::1".parse::<Ipv6Addr>().unwrap().octets()));
+        assert!(!prefix.contains(&"fec0::1".parse::<Ipv6Addr>().unwrap().octets()));
+
+        assert_eq!(
+            "10.0.0.0/33".parse::<Cidr>(),
+            Err(CidrError::PrefixTooLong { len: 33, max: 32 })
+        );
+        assert!("10.0.0.0".parse::<Cidr>().is_err());
+    }
+}
@@ -1,4 +1,7 @@
-use std::ops::{Range, RangeFrom};
+use std::{
+    ops::{Range, RangeFrom},
+    simd::prelude::*,
+};
 
 use bitvec::prelude::*;
 
@@ -142,6 +145,93 @@ impl<'slice, P: Primitive> OptionalFixedSlice<'slice, P> {
     }
 }
 
+const LANES: usize = 8;
+
+// null slots are filled with a value the reduction ignores before it is vectorized
+impl OptionalFixedSlice<'_, f64> {
+    #[inline]
+    fn reduce(
+        &self,
+        fill: f64,
+        init: f64,
+        lanes: impl Fn(Simd<f64, LANES>, Simd<f64, LANES>) -> Simd<f64, LANES>,
+        scalar: impl Fn(f64, f64) -> f64,
+    ) -> (Simd<f64, LANES>, f64) {
+        let data = self.data.chunks_exact(LANES);
+        let validity = self.validity.chunks_exact(LANES);
+        let rest = data
+            .remainder()
+            .iter()
+            .zip(validity.remainder())
+            .filter(|(_, valid)| **valid)
+            .fold(init, |acc, (value, _)| scalar(acc, *value));
+        let acc = data
+            .zip(validity)
+            .fold(Simd::splat(init), |acc, (values, valid)| {
+                let mask = Mask::<i64, LANES>::from_array(std::array::from_fn(|lane| valid[lane]));
+                lanes(
+                    acc,
+                    mask.select(Simd::from_slice(values), Simd::splat(fill)),
+                )
+            });
+        (acc, rest)
+    }
+
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.validity.count_ones()
+    }
+
+    #[inline]
+    pub fn sum(&self) -> Option<f64> {
+        if self.count() == 0 {
+            return None;
+        }
+        let (acc, rest) = self.reduce(0.0, 0.0, |a, b| a + b, |a, b| a + b);
+        Some(acc.reduce_sum() + rest)
+    }
+
+    // NaN is only returned when every value is NaN
+    #[inline]
+    pub fn min(&self) -> Option<f64> {
+        if self.count() == 0 {
+            return None;
+        }
+        let (acc, rest) = self.reduce(f64::NAN, f64::NAN, SimdFloat::simd_min, f64::min);
+        Some(acc.reduce_min().min(rest))
+    }
+
+    #[inline]
+    pub fn max(&self) -> Option<f64> {
+        if self.count() == 0 {
+            return None;
+        }
+        let (acc, rest) = self.reduce(f64::NAN, f64::NAN, SimdFloat::simd_max, f64::max);
+        Some(acc.reduce_max().max(rest))
+    }
+
+    // sum of squared deviations from the mean
+    #[inline]
+    pub fn deviation(&self, mean: f64) -> Option<f64> {
+        if self.count() == 0 {
+            return None;
+        }
+        let mean_lanes = Simd::splat(mean);
+        let (acc, rest) = self.reduce(
+            mean,
+            0.0,
+            |acc, values| acc + (values - mean_lanes) * (values - mean_lanes),
+            |acc, value| acc + (value - mean) * (value - mean),
+        );
+        Some(acc.reduce_sum() + rest)
+    }
+
+    #[inline]
+    pub fn last(&self) -> Option<f64> {
+        self.validity.last_one().map(|slot| self.data[slot])
+    }
+}
+
 impl<'slice, P: Primitive> IntoIterator for OptionalFixedSlice<'slice, P> {
     type Item = Option<&'slice P>;
     type IntoIter = OptionalFixedSizeSliceIter<'slice, P>;
@@ -234,3 +324,41 @@ impl<'slice, P: Primitive> OptionalFixedSliceMut<'slice, P> {
         self.data.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::OptionalFixedList;
+    use crate::scalar::Scalar;
+
+    #[test]
+    fn test_reduce() {
+        let list = OptionalFixedList::from(
+            (0..20)
+                .map(|value| (value % 3 != 0).then_some(value as f64))
+                .collect::<Vec<_>>(),
+        );
+        let slice = list.as_ref();
+        let values = (0..20)
+            .filter(|value| value % 3 != 0)
+            .map(|value| value as f64);
+        assert_eq!(slice.count(), 13);
+        assert_eq!(slice.sum(), Some(values.clone().sum()));
+        assert_eq!(slice.min(), Some(1.0));
+        assert_eq!(slice.max(), Some(19.0));
+        assert_eq!(slice.last(), Some(19.0));
+        let mean = values.clone().sum::<f64>() / 13.0;
+        let deviation = values
+            .map(|value| (value - mean) * (value - mean))
+            .sum::<f64>();
+        assert!((slice.deviation(mean).unwrap() - deviation).abs() < 1e-9);
+
+        let slice = slice.slice(15..17);
+        assert_eq!(slice.count(), 1);
+        assert_eq!(slice.sum(), Some(16.0));
+        assert_eq!(slice.last(), Some(16.0));
+        assert_eq!(list.as_ref().slice(18..18).max(), None);
+
+        let list = OptionalFixedList::from(vec![Some(f64::NAN), None, Some(2.0)]);
+        assert_eq!(list.as_ref().min(), Some(2.0));
+    }
+}
@@ -1,9 +1,9 @@
 use crate::{
     column::{field::FieldType, label::LabelType},
-    index::Index,
+    index::{Index, SparseOptions},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Label {
     pub r#type: LabelType,
     pub name: String,
@@ -19,5 +19,5 @@ pub struct Field {
 pub struct Schema {
     pub labels: Vec<Label>,
     pub fields: Vec<Field>,
-    pub index: Vec<Index<(), u32>>,
+    pub index: Vec<Index<(), SparseOptions, ()>>,
 }
@@ -25,11 +25,16 @@ impl Duration {
     }
 
     #[inline]
-    pub fn from_secs(secs: i64) -> Self {
+    pub const fn from_secs(secs: i64) -> Self {
         Self {
             millis: secs * 1000,
         }
     }
+
+    #[inline]
+    pub const fn from_millis(millis: i64) -> Self {
+        Self { millis }
+    }
 }
 
 impl Mul<Duration> for isize {
@@ -111,6 +116,64 @@ impl Instant {
     }
 }
 
+// calendar fields of an instant in UTC
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Civil {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    // days since Sunday
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl Civil {
+    #[inline]
+    pub fn days_in_month(&self) -> u32 {
+        match self.month {
+            2 if self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+}
+
+impl Instant {
+    // days to the proleptic Gregorian date, counted in 400 year eras of 146097 days
+    pub fn civil(&self) -> Civil {
+        let secs = self.millis.div_euclid(MILLIS_PER_SEC);
+        let days = secs.div_euclid(86_400);
+        let secs = secs.rem_euclid(86_400);
+
+        let shifted = days + 719_468;
+        let era = shifted.div_euclid(146_097);
+        let day_of_era = shifted.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Civil {
+            year,
+            month: month as u32,
+            day: day as u32,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+            hour: (secs / 3600) as u32,
+            minute: (secs % 3600 / 60) as u32,
+        }
+    }
+}
+
 impl fmt::Display for Instant {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let duration = std::time::Duration::from_millis(self.millis as u64);
@@ -171,10 +234,10 @@ impl Range {
     pub fn is_empty(&self) -> bool {
         if let Some(start) = self.start {
             if let Some(end) = self.end {
-                return end - start > Duration::from_secs(0);
+                return end <= start;
             }
         }
-        true
+        false
     }
 }
 
@@ -200,3 +263,41 @@ impl std::ops::BitAnd for Range {
         Self { start, end }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Civil, Instant};
+
+    #[test]
+    fn test_civil() {
+        assert_eq!(
+            Instant::from_millis(0).civil(),
+            Civil {
+                year: 1970,
+                month: 1,
+                day: 1,
+                weekday: 4,
+                hour: 0,
+                minute: 0,
+            }
+        );
+        // 2024-02-29T23:59:59Z, a Thursday
+        let civil = Instant::from_millis(1_709_251_199_000).civil();
+        assert_eq!((civil.year, civil.month, civil.day), (2024, 2, 29));
+        assert_eq!((civil.weekday, civil.hour, civil.minute), (4, 23, 59));
+        assert_eq!(civil.days_in_month(), 29);
+        // 1969-12-31T23:00:00Z
+        let civil = Instant::from_millis(-3_600_000).civil();
+        assert_eq!(
+            (civil.year, civil.month, civil.day, civil.weekday),
+            (1969, 12, 31, 3)
+        );
+        // 2100 is not a leap year
+        assert_eq!(
+            Instant::from_millis(4_105_123_200_000)
+                .civil()
+                .days_in_month(),
+            28
+        );
+    }
+}
@@ -1,18 +1,31 @@
 mod normalize;
+mod registry;
 mod rules;
 
 use std::sync::{Arc, RwLock};
 
+use common::{
+    column::{field::FieldType, label::LabelType},
+    query::MatcherOp,
+    schema::Label,
+};
 use normalize::{Normalize, NormalizeError};
+use registry::{Argument, Arguments, Kind};
 use resource::{db::DB, table::Table};
 use thiserror::Error;
 
 use self::rules::{Rule, TypeMismatch};
 use crate::{
-    execute::function::rate,
+    execute::{
+        aggregate::{AggregateOp, Param},
+        binary::arithmetic,
+        function::Function,
+    },
     plan::{
-        logical::Logical,
-        physical::{Call, Physical},
+        logical::{
+            self, AggregateAction, BinaryOp, GroupSide, Logical, MatchingAction, VectorMatching,
+        },
+        physical::{Aggregate, Binary, Call, Physical},
     },
     Layer, Pass,
 };
@@ -25,6 +38,40 @@ pub enum Error {
     TypeError(#[from] TypeMismatch),
     #[error(transparent)]
     UpStream(#[from] Box<dyn std::error::Error>),
+    #[error("unknown function {}", .0)]
+    UnknownFunction(String),
+    #[error("{} expression is not supported yet", .0)]
+    NoSupport(&'static str),
+    #[error("operator {:?} is only allowed between instant vectors", .0)]
+    SetOperatorScalar(BinaryOp),
+    #[error("comparisons between scalars must use bool modifier")]
+    ComparisonScalar,
+    #[error("no grouping allowed for operator {:?}", .0)]
+    SetOperatorGroup(BinaryOp),
+    #[error("label {} has different types on both sides", .0)]
+    LabelTypeConflict(String),
+    #[error("expected instant vector in aggregation {}", .0)]
+    AggregateScalar(String),
+    #[error("invalid parameter of aggregation {}", .0)]
+    AggregateParam(String),
+    #[error("expected range vector in call to function {}", .0)]
+    ExpectedRange(String),
+    #[error("invalid parameter of function {}", .0)]
+    FunctionParam(String),
+    #[error("function {} expects {} arguments, found {}", .0, .1, .2)]
+    FunctionArity(String, String, usize),
+    #[error("expected {} in call to function {}", .1, .0)]
+    ExpectedKind(String, Kind),
+    #[error("expected instant vector in call to function {}", .0)]
+    ExpectedVector(String),
+    #[error("function {} needs a string or int label le", .0)]
+    BucketLabel(String),
+    #[error("invalid destination label name {}", .0)]
+    InvalidLabel(String),
+    #[error("function {} does not support {} fields", .0, .1)]
+    UnsupportedField(String, FieldType),
+    #[error(transparent)]
+    InvalidRegex(#[from] regex::Error),
 }
 
 pub struct Env<'db> {
@@ -63,25 +110,274 @@ fn check(env: &mut Env, expression: Logical) -> Result<Physical, Error> {
         Logical::Call(call) => {
             let mut args = Vec::with_capacity(call.args.len());
             for arg in call.args {
-                args.push(check(env, arg)?);
+                match arg {
+                    // the trailing literal names the field the function applies to
+                    Logical::Literal(_) => continue,
+                    Logical::String(string) => args.push(Argument::String(string)),
+                    arg => args.push(Argument::Expr(check(env, arg)?)),
+                }
             }
-            let func = match call.name.as_str() {
-                "rate" => rate,
-                _ => unimplemented!(),
-            };
-            Ok(Physical::Call(Call {
-                args,
-                name: call.name,
-                function: func,
-            }))
+            check_call(call.name, args)
         }
         Logical::Scan(scan) => {
             let scan = scan.normalize(env)?;
             scan.check(env)?;
             Ok(Physical::Scan(scan))
         }
-        _ => unimplemented!(),
+        Logical::Aggregate(aggregate) => check_aggregate(env, aggregate),
+        Logical::Binary(binary) => {
+            let lhs = check(env, *binary.lhs)?;
+            let rhs = check(env, *binary.rhs)?;
+            check_binary(binary.op, binary.return_bool, binary.matching, lhs, rhs)
+        }
+        Logical::Negation(expr) => match check(env, *expr)? {
+            Physical::Number(value) => Ok(Physical::Number(-value)),
+            expr => check_binary(BinaryOp::Mul, false, None, expr, Physical::Number(-1.0)),
+        },
+        Logical::Number(value) => Ok(Physical::Number(value)),
+        Logical::Literal(_) | Logical::String(_) => Err(Error::NoSupport("literal")),
+    }
+}
+
+fn check_call(name: String, args: Vec<Argument>) -> Result<Physical, Error> {
+    let Some(definition) = registry::lookup(&name) else {
+        return Err(Error::UnknownFunction(name));
+    };
+    definition.check(&args)?;
+
+    let mut arguments = Arguments {
+        numbers: Vec::new(),
+        strings: Vec::new(),
+        vector: None,
+    };
+    let mut vector = None;
+    for arg in args {
+        match arg {
+            Argument::Expr(Physical::Number(number)) => arguments.numbers.push(number),
+            Argument::Expr(expr) => vector = Some(expr),
+            Argument::String(string) => arguments.strings.push(string),
+        }
+    }
+    arguments.vector = vector.as_ref();
+    let function = (definition.build)(&name, &arguments)?;
+
+    let mut args = vector.into_iter().collect::<Vec<_>>();
+    let labels = match &function {
+        Function::HistogramQuantile { le, .. } => {
+            let mut labels = args[0].labels();
+            labels.remove(*le);
+            labels
+        }
+        Function::Relabel(relabel) => relabel.labels(args[0].labels()),
+        Function::Absent(_) => {
+            let labels = match args.as_slice() {
+                [Physical::Scan(scan)] => registry::equal_labels(scan).0,
+                _ => vec![],
+            };
+            if name == "absent_over_time" {
+                let count = args.into_iter().map(Argument::Expr).collect();
+                args = vec![check_call("count_over_time".to_owned(), count)?];
+            }
+            labels
+        }
+        _ => args.first().map(Physical::labels).unwrap_or_default(),
+    };
+    Ok(Physical::Call(Call {
+        args,
+        name,
+        function,
+        labels,
+    }))
+}
+
+fn check_aggregate(env: &mut Env, aggregate: logical::Aggregate) -> Result<Physical, Error> {
+    let Some(op) = AggregateOp::from_name(&aggregate.name) else {
+        return Err(Error::NoSupport("aggregation"));
+    };
+    // the vector is the last argument before the trailing field literal
+    let mut args = aggregate
+        .args
+        .into_iter()
+        .filter(|arg| !matches!(arg, Logical::Literal(_)))
+        .collect::<Vec<_>>();
+    let input = match args.pop() {
+        Some(input) => check(env, input)?,
+        None => return Err(Error::AggregateScalar(aggregate.name)),
+    };
+    if input.is_scalar() {
+        return Err(Error::AggregateScalar(aggregate.name));
+    }
+    let param = match (op, args.pop()) {
+        (AggregateOp::CountValues, Some(Logical::String(label))) => Param::Label(label),
+        (AggregateOp::Quantile | AggregateOp::TopK | AggregateOp::BottomK, Some(arg)) => {
+            match check(env, arg)? {
+                Physical::Number(number) => Param::Number(number),
+                _ => return Err(Error::AggregateParam(aggregate.name)),
+            }
+        }
+        (
+            AggregateOp::CountValues
+            | AggregateOp::Quantile
+            | AggregateOp::TopK
+            | AggregateOp::BottomK,
+            _,
+        ) => return Err(Error::AggregateParam(aggregate.name)),
+        (_, None) => Param::None,
+        (_, Some(_)) => return Err(Error::AggregateParam(aggregate.name)),
+    };
+    if !args.is_empty() {
+        return Err(Error::AggregateParam(aggregate.name));
+    }
+
+    let input_labels = input.labels();
+    let (by, mut labels): (Vec<_>, Vec<_>) = input_labels
+        .iter()
+        .cloned()
+        .enumerate()
+        .filter(|(_, label)| {
+            if matches!(&param, Param::Label(name) if *name == label.name) {
+                return false;
+            }
+            let listed = aggregate.by.contains(&label.name);
+            match aggregate.action {
+                AggregateAction::With => listed,
+                AggregateAction::Without => !listed,
+            }
+        })
+        .unzip();
+    if op.keeps_series() {
+        labels = input_labels;
+    }
+    if let Param::Label(name) = &param {
+        labels.push(Label {
+            r#type: LabelType::String(()),
+            name: name.clone(),
+        });
+    }
+    Ok(Physical::Aggregate(Aggregate {
+        op,
+        param,
+        by,
+        input: Box::new(input),
+        labels,
+    }))
+}
+
+fn check_binary(
+    op: BinaryOp,
+    return_bool: bool,
+    matching: Option<VectorMatching>,
+    mut lhs: Physical,
+    mut rhs: Physical,
+) -> Result<Physical, Error> {
+    let labels = match (&lhs, &rhs) {
+        _ if op.is_set() && (lhs.is_scalar() || rhs.is_scalar()) => {
+            return Err(Error::SetOperatorScalar(op))
+        }
+        _ if lhs.is_scalar() && rhs.is_scalar() && op.is_comparison() && !return_bool => {
+            return Err(Error::ComparisonScalar);
+        }
+        (Physical::Number(l), Physical::Number(r)) => {
+            return Ok(Physical::Number(arithmetic(op, *l, *r)));
+        }
+        (scalar, vector) | (vector, scalar) if scalar.is_scalar() => vector.labels(),
+        (lhs, rhs) => binary_labels(op, matching.as_ref(), lhs.labels(), rhs.labels())?,
+    };
+    if op.is_comparison() && !return_bool {
+        lower_predicate(op, &mut lhs, &mut rhs);
+    }
+    Ok(Physical::Binary(Binary {
+        op,
+        return_bool,
+        matching,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+        labels,
+    }))
+}
+
+// a selector filtered by a number lets chunks skip blocks of rows without a
+// matching sample, the comparison still filters the samples themselves
+fn lower_predicate(op: BinaryOp, lhs: &mut Physical, rhs: &mut Physical) {
+    let (scan, value, flipped) = match (lhs, rhs) {
+        (Physical::Scan(scan), Physical::Number(value)) => (scan, *value, false),
+        (Physical::Number(value), Physical::Scan(scan)) => (scan, *value, true),
+        _ => return,
+    };
+    // NaN samples are kept by != but never reach a zone map, so it is not lowered
+    let op = match (op, flipped) {
+        (BinaryOp::Eq, _) => MatcherOp::LiteralEqual(Some(value)),
+        (BinaryOp::Gt, false) | (BinaryOp::Lt, true) => MatcherOp::GreaterThan(value),
+        (BinaryOp::Ge, false) | (BinaryOp::Le, true) => MatcherOp::GreaterEqual(value),
+        (BinaryOp::Lt, false) | (BinaryOp::Gt, true) => MatcherOp::LessThan(value),
+        (BinaryOp::Le, false) | (BinaryOp::Ge, true) => MatcherOp::LessEqual(value),
+        _ => return,
+    };
+    let field = scan.field_id();
+    if scan.predicate.len() <= field {
+        scan.predicate.resize_with(field + 1, Vec::new);
+    }
+    scan.predicate[field].push(op);
+}
+
+fn binary_labels(
+    op: BinaryOp,
+    matching: Option<&VectorMatching>,
+    lhs: Vec<Label>,
+    rhs: Vec<Label>,
+) -> Result<Vec<Label>, Error> {
+    let group = matching.and_then(|matching| matching.group.as_ref());
+    if op.is_set() {
+        if group.is_some() {
+            return Err(Error::SetOperatorGroup(op));
+        }
+        return match op {
+            BinaryOp::Or => merge_labels(lhs, rhs, None),
+            _ => Ok(lhs),
+        };
+    }
+
+    match (matching, group) {
+        (_, Some(group)) => {
+            let (many, one) = match group.side {
+                GroupSide::Left => (lhs, rhs),
+                GroupSide::Right => (rhs, lhs),
+            };
+            merge_labels(many, one, Some(&group.labels))
+        }
+        (Some(matching), None) => Ok(lhs
+            .into_iter()
+            .filter(|label| {
+                let listed = matching.labels.contains(&label.name);
+                match matching.action {
+                    MatchingAction::On => listed,
+                    MatchingAction::Ignoring => !listed,
+                }
+            })
+            .collect()),
+        (None, None) => Ok(lhs),
+    }
+}
+
+// appends labels of `other` missing in `labels`, restricted to `include` if given
+fn merge_labels(
+    mut labels: Vec<Label>,
+    other: Vec<Label>,
+    include: Option<&[String]>,
+) -> Result<Vec<Label>, Error> {
+    for label in other {
+        match labels.iter().find(|l| l.name == label.name) {
+            Some(exist) if exist.r#type != label.r#type => {
+                return Err(Error::LabelTypeConflict(label.name))
+            }
+            Some(_) => {}
+            None if include.is_none_or(|include| include.contains(&label.name)) => {
+                labels.push(label)
+            }
+            None => {}
+        }
     }
+    Ok(labels)
 }
 
 pub struct Checker {
@@ -108,10 +404,26 @@ impl<Input, Inner: Pass<Input, Output = Logical>> Layer<Input, Inner> for Checke
 #[cfg(test)]
 mod tests {
 
+    use chunk::mutable::MutableChunk;
+    use common::{
+        column::{field::Field, label::LabelValue},
+        context::Context,
+        query::{MatcherOp, Projection},
+        time::{Duration, Instant, Range},
+        Set,
+    };
+    use regex::Regex;
     use resource::db::tests::test_db;
 
-    use super::Checker;
-    use crate::{parse::Parser, Layer, Pass};
+    use super::{normalize::NormalizeError, registry::Kind, Checker, Error};
+    use crate::{
+        parse::Parser,
+        plan::{
+            logical::{Logical, Matcher, Scan},
+            physical::Physical,
+        },
+        Layer, Pass,
+    };
 
     #[test]
     fn check_scan() {
@@ -129,4 +441,352 @@ mod tests {
                 println!("{:?}", mir);
             });
     }
+
+    fn scan(name: &str, op: MatcherOp) -> Logical {
+        scan_all(vec![(name, op)])
+    }
+
+    fn scan_all(matcher: Vec<(&str, MatcherOp)>) -> Logical {
+        Logical::Scan(Scan {
+            resource: "foo.bar.something_used".into(),
+            matcher: matcher
+                .into_iter()
+                .map(|(name, op)| Matcher {
+                    name: name.into(),
+                    op,
+                })
+                .collect(),
+            predicate: vec![],
+            range: Range {
+                start: None,
+                end: None,
+            },
+            offset: None,
+            at: None,
+            projection: Projection {
+                labels: Set::Universe,
+                fields: Set::Universe,
+            },
+        })
+    }
+
+    #[test]
+    fn check_cidr_matcher() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let checker = Checker::new(test_db()).layer(());
+                let cidr = |cidr: &str| MatcherOp::CidrMatch(cidr.parse().unwrap());
+
+                assert!(checker.apply(scan("ip", cidr("10.0.0.0/8"))).is_ok());
+                assert!(matches!(
+                    checker.apply(scan("env", cidr("10.0.0.0/8"))),
+                    Err(Error::NormalizeError(NormalizeError::NoSupportCidr { .. }))
+                ));
+                assert!(matches!(
+                    checker.apply(scan("ip", cidr("fe80::/10"))),
+                    Err(Error::TypeError(_))
+                ));
+            });
+    }
+
+    #[test]
+    fn check_range_matcher() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let checker = Checker::new(test_db()).layer(());
+
+                assert!(checker
+                    .apply(scan(
+                        "shard",
+                        MatcherOp::Between(LabelValue::Int(1), LabelValue::Int(4))
+                    ))
+                    .is_ok());
+                assert!(matches!(
+                    checker.apply(scan("env", MatcherOp::LessThan(LabelValue::Int(1)))),
+                    Err(Error::NormalizeError(NormalizeError::NoSupportRange { .. }))
+                ));
+                assert!(matches!(
+                    checker.apply(scan(
+                        "shard",
+                        MatcherOp::GreaterEqual(LabelValue::String("1".into()))
+                    )),
+                    Err(Error::TypeError(_))
+                ));
+            });
+    }
+
+    #[test]
+    fn check_multiple_matcher() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let parser = Checker::new(test_db()).layer(Parser::new().layer(()));
+                let plan = parser
+                    .apply(r#"foo.bar.something_used{env=~"api.*", env!="api-canary"}"#)
+                    .unwrap();
+                match plan {
+                    Physical::Scan(scan) => {
+                        assert_eq!(scan.matcher[0].len(), 2);
+                        assert!(scan.matcher[1..].iter().all(Vec::is_empty));
+                    }
+                    _ => unreachable!(),
+                }
+
+                let checker = Checker::new(test_db()).layer(());
+                assert!(matches!(
+                    checker.apply(scan_all(vec![
+                        ("env", MatcherOp::RegexMatch(Regex::new("api.*").unwrap())),
+                        ("env", MatcherOp::LiteralNotEqual(Some(LabelValue::Int(1)))),
+                    ])),
+                    Err(Error::TypeError(_))
+                ));
+            });
+    }
+
+    #[test]
+    fn check_value_predicate() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let checker = Checker::new(test_db()).layer(());
+                let predicate = |op: MatcherOp<f64>| match scan_all(vec![]) {
+                    Logical::Scan(mut scan) => {
+                        scan.predicate.push(Matcher {
+                            name: "value".into(),
+                            op,
+                        });
+                        Logical::Scan(scan)
+                    }
+                    _ => unreachable!(),
+                };
+
+                match checker.apply(predicate(MatcherOp::GreaterThan(100.0))) {
+                    Ok(Physical::Scan(scan)) => {
+                        assert_eq!(scan.predicate, vec![vec![MatcherOp::GreaterThan(100.0)]])
+                    }
+                    _ => unreachable!(),
+                }
+                assert!(matches!(
+                    checker.apply(predicate(MatcherOp::RegexMatch(Regex::new("1.*").unwrap()))),
+                    Err(Error::NormalizeError(
+                        NormalizeError::NoSupportPredicate { .. }
+                    ))
+                ));
+            });
+    }
+
+    #[test]
+    fn check_lowered_predicate() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let parser = Checker::new(test_db()).layer(Parser::new().layer(()));
+                let scan = match parser.apply("foo.bar.something_used > 5") {
+                    Ok(Physical::Binary(binary)) => match *binary.lhs {
+                        Physical::Scan(scan) => scan,
+                        _ => unreachable!(),
+                    },
+                    _ => unreachable!(),
+                };
+                assert_eq!(scan.predicate, vec![vec![MatcherOp::GreaterThan(5.0)]]);
+
+                match parser.apply("5 >= foo.bar.something_used") {
+                    Ok(Physical::Binary(binary)) => match *binary.rhs {
+                        Physical::Scan(scan) => {
+                            assert_eq!(scan.predicate, vec![vec![MatcherOp::LessEqual(5.0)]])
+                        }
+                        _ => unreachable!(),
+                    },
+                    _ => unreachable!(),
+                }
+                match parser.apply("foo.bar.something_used > bool 5") {
+                    Ok(Physical::Binary(binary)) => match *binary.lhs {
+                        Physical::Scan(scan) => assert!(scan.predicate.concat().is_empty()),
+                        _ => unreachable!(),
+                    },
+                    _ => unreachable!(),
+                }
+
+                // only the second block holds samples above 5
+                let start_at = Instant::from_millis(1_000_000);
+                let mut chunk = MutableChunk::new(
+                    &scan.resource.meta.schema,
+                    start_at,
+                    Duration::SECOND,
+                    2048,
+                    1,
+                );
+                for row in 0..2048 {
+                    let env = LabelValue::String(Vec::from(row.to_string()));
+                    chunk.push(vec![Some(env), None, None, None]).unwrap();
+                    let value = if row < 1024 { 1.0 } else { 10.0 };
+                    chunk
+                        .set(row, 0, start_at, Some(Field::Float64(value)))
+                        .unwrap();
+                }
+                let mut cx = Context::new(256);
+                let rows = unsafe { chunk.filter_rows(&mut cx, &scan.matcher, &scan.predicate) }
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    rows.iter().collect::<Vec<_>>(),
+                    (1024..2048).collect::<Vec<_>>()
+                );
+            });
+    }
+
+    #[test]
+    fn check_unsupported() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let parser = Checker::new(test_db()).layer(Parser::new().layer(()));
+
+                assert!(matches!(
+                    parser.apply("foo.bar.something_used and 2"),
+                    Err(Error::SetOperatorScalar(_))
+                ));
+                assert!(matches!(
+                    parser.apply("1 > 2"),
+                    Err(Error::ComparisonScalar)
+                ));
+                assert!(matches!(
+                    parser.apply("2 ^ 3 - -1"),
+                    Ok(Physical::Number(value)) if value == 9.0
+                ));
+                assert!(matches!(
+                    parser.apply("unknown(foo.bar.something_used)"),
+                    Err(Error::UnknownFunction(name)) if name == "unknown"
+                ));
+                assert!(matches!(
+                    parser.apply("rate(foo.bar.something_used[5m])"),
+                    Ok(Physical::Call(_))
+                ));
+                assert!(matches!(
+                    parser.apply("irate(foo.bar.something_used)"),
+                    Err(Error::ExpectedRange(name)) if name == "irate"
+                ));
+                assert!(matches!(
+                    parser.apply("quantile_over_time(0.9, foo.bar.something_used[5m])"),
+                    Ok(Physical::Call(call)) if call.args.len() == 1
+                ));
+                assert!(matches!(
+                    parser.apply("quantile_over_time(foo.bar.something_used[5m])"),
+                    Err(Error::FunctionArity(_, expected, 1)) if expected == "2"
+                ));
+                assert!(matches!(
+                    parser.apply("predict_linear(foo.bar.something_used[5m], 3600)"),
+                    Ok(Physical::Call(call)) if call.args.len() == 1
+                ));
+                assert!(matches!(
+                    parser.apply("holt_winters(foo.bar.something_used[5m], 0.5, 1)"),
+                    Err(Error::FunctionParam(_))
+                ));
+                assert!(matches!(
+                    parser.apply("clamp(foo.bar.something_used, 1, 2)"),
+                    Ok(Physical::Call(call)) if call.args.len() == 1
+                ));
+                assert!(matches!(
+                    parser.apply("clamp(foo.bar.something_used, 1)"),
+                    Err(Error::FunctionArity(..))
+                ));
+                assert!(matches!(
+                    parser.apply("abs(1)"),
+                    Err(Error::ExpectedVector(_))
+                ));
+                for query in [
+                    "abs(foo.bar.healthy)",
+                    r#"abs(label_replace(foo.bar.healthy, "team", "$1", "env", "(.*)"))"#,
+                    "abs(last_over_time(foo.bar.healthy[5m]))",
+                    "abs(topk(1, foo.bar.healthy))",
+                    "abs(foo.bar.healthy > 0)",
+                    "abs(foo.bar.something_used or foo.bar.healthy)",
+                ] {
+                    assert!(matches!(
+                        parser.apply(query),
+                        Err(Error::UnsupportedField(..))
+                    ));
+                }
+                for query in [
+                    "abs(foo.bar.healthy * 1)",
+                    "abs(foo.bar.healthy > bool 0)",
+                    "abs(count_over_time(foo.bar.healthy[5m]))",
+                    "abs(sum(foo.bar.healthy))",
+                ] {
+                    assert!(matches!(parser.apply(query), Ok(Physical::Call(_))));
+                }
+                assert!(matches!(
+                    parser.apply(r#"absent(foo.bar.something_used{env="a", env="b"})"#),
+                    Ok(Physical::Call(call)) if call.labels.is_empty()
+                ));
+                for query in [
+                    r#"absent(foo.bar.something_used{env="a", env=~"a|b"})"#,
+                    r#"absent(foo.bar.something_used{env=~"a.*", env="a", env="a"})"#,
+                ] {
+                    assert!(matches!(
+                        parser.apply(query),
+                        Ok(Physical::Call(call))
+                            if call.labels.iter().map(|label| &label.name).eq(["env"].iter())
+                    ));
+                }
+                assert!(matches!(
+                    parser.apply("absent_over_time(foo.bar.something_used)"),
+                    Err(Error::ExpectedRange(_))
+                ));
+                assert!(matches!(
+                    parser.apply("round(foo.bar.something_used, 1, 2)"),
+                    Err(Error::FunctionArity(_, expected, 3)) if expected == "1 to 2"
+                ));
+                assert!(matches!(
+                    parser.apply(r#"label_join(foo.bar.something_used, "dst")"#),
+                    Err(Error::FunctionArity(_, expected, 2)) if expected == "at least 3"
+                ));
+                assert!(matches!(
+                    parser.apply(r#"label_join(foo.bar.something_used, "dst", 1, "env")"#),
+                    Err(Error::ExpectedKind(_, Kind::String))
+                ));
+                assert!(matches!(
+                    parser.apply("predict_linear(foo.bar.something_used[5m], time())"),
+                    Err(Error::ExpectedKind(_, Kind::Number))
+                ));
+                assert!(matches!(
+                    parser.apply("deriv(foo.bar.healthy[5m])"),
+                    Err(Error::UnsupportedField(..))
+                ));
+                assert!(matches!(
+                    parser.apply("count_over_time(foo.bar.healthy[5m])"),
+                    Ok(Physical::Call(_))
+                ));
+                assert!(matches!(
+                    parser.apply("time() > 1"),
+                    Err(Error::ComparisonScalar)
+                ));
+                assert!(matches!(
+                    parser.apply("sum(time())"),
+                    Err(Error::AggregateScalar(_))
+                ));
+                assert!(matches!(
+                    parser.apply("hour()"),
+                    Ok(Physical::Call(call)) if call.labels.is_empty()
+                ));
+                assert!(matches!(
+                    parser.apply("time() + foo.bar.something_used"),
+                    Ok(Physical::Binary(binary)) if !binary.labels.is_empty()
+                ));
+            });
+    }
 }
@@ -24,6 +24,24 @@ pub enum NormalizeError {
         name: String,
         table: String,
     },
+    #[error(
+        "{label} type of label column {name} in table: {table} does not support cidr matching"
+    )]
+    NoSupportCidr {
+        label: LabelType,
+        name: String,
+        table: String,
+    },
+    #[error(
+        "{label} type of label column {name} in table: {table} does not support range matching"
+    )]
+    NoSupportRange {
+        label: LabelType,
+        name: String,
+        table: String,
+    },
+    #[error("field column {name} in table: {table} only supports value comparison")]
+    NoSupportPredicate { name: String, table: String },
     #[error("resource: {name} not exists")]
     ResourceNotExists { name: String },
 }
@@ -49,7 +67,10 @@ impl Normalize for logical::Scan {
         Ok(physical::Scan {
             resource,
             matcher: self.matcher.normalize(env)?,
+            predicate: self.predicate.normalize(env)?,
             range: self.range,
+            offset: self.offset,
+            at: self.at,
             projection: self.projection.normalize(env)?,
         })
     }
@@ -95,11 +116,11 @@ impl Normalize for Projection<String> {
 }
 
 impl Normalize for Vec<Matcher> {
-    type Output = Vec<Option<MatcherOp>>;
+    type Output = Vec<Vec<MatcherOp>>;
 
     fn normalize(self, env: &mut Env<'_>) -> Result<Self::Output, NormalizeError> {
         let table = env.table.as_ref().unwrap();
-        let mut op = vec![None; table.meta.schema.labels.len()];
+        let mut op = vec![Vec::new(); table.meta.schema.labels.len()];
         for m in self {
             let (id, meta) = table
                 .meta
@@ -125,10 +146,72 @@ impl Normalize for Vec<Matcher> {
                         });
                     }
                 },
+                MatcherOp::CidrMatch(_) | MatcherOp::CidrNotMatch(_) => match &meta.r#type {
+                    LabelType::IPv4(_) | LabelType::IPv6(_) => {}
+                    other => {
+                        return Err(NormalizeError::NoSupportCidr {
+                            label: other.clone(),
+                            name: meta.name.clone(),
+                            table: table.name.to_string(),
+                        });
+                    }
+                },
+                MatcherOp::LessThan(_)
+                | MatcherOp::LessEqual(_)
+                | MatcherOp::GreaterThan(_)
+                | MatcherOp::GreaterEqual(_)
+                | MatcherOp::Between(_, _) => match &meta.r#type {
+                    LabelType::Int(_) => {}
+                    other => {
+                        return Err(NormalizeError::NoSupportRange {
+                            label: other.clone(),
+                            name: meta.name.clone(),
+                            table: table.name.to_string(),
+                        });
+                    }
+                },
+                _ => {}
+            }
+
+            op[id].push(m.op);
+        }
+        Ok(op)
+    }
+}
+
+impl Normalize for Vec<Matcher<String, f64>> {
+    type Output = Vec<Vec<MatcherOp<f64>>>;
+
+    fn normalize(self, env: &mut Env<'_>) -> Result<Self::Output, NormalizeError> {
+        let table = env.table.as_ref().unwrap();
+        let mut op = vec![Vec::new(); table.meta.schema.fields.len()];
+        for m in self {
+            let id = table
+                .meta
+                .schema
+                .fields
+                .iter()
+                .position(|field| field.name == m.name)
+                .ok_or_else(|| NormalizeError::NoColumn {
+                    op: "filter",
+                    table: table.name.to_string(),
+                    name: m.name.clone(),
+                })?;
+
+            match m.op {
+                MatcherOp::RegexMatch(_)
+                | MatcherOp::RegexNotMatch(_)
+                | MatcherOp::CidrMatch(_)
+                | MatcherOp::CidrNotMatch(_) => {
+                    return Err(NormalizeError::NoSupportPredicate {
+                        name: m.name,
+                        table: table.name.to_string(),
+                    });
+                }
                 _ => {}
             }
 
-            op[id] = Some(m.op.clone());
+            op[id].push(m.op);
         }
         Ok(op)
     }
@@ -0,0 +1,422 @@
+use std::fmt;
+
+use common::{
+    column::{
+        field::Field,
+        label::{LabelType, LabelValue},
+    },
+    query::MatcherOp,
+    schema::Label,
+};
+use regex::Regex;
+
+use super::Error;
+use crate::{
+    execute::function::{
+        delta, idelta, increase, irate, rate, Function, Math, OverTime, Relabel, Rewrite, Trend,
+    },
+    plan::physical::{Physical, Scan},
+};
+
+// what a function accepts at an argument position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Number,
+    String,
+    Instant,
+    Range,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Number => write!(f, "number"),
+            Kind::String => write!(f, "string"),
+            Kind::Instant => write!(f, "instant vector"),
+            Kind::Range => write!(f, "range vector"),
+        }
+    }
+}
+
+// field types the vector argument may be scanned from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Fields {
+    Any,
+    Numeric,
+}
+
+#[derive(Debug)]
+pub(super) enum Argument {
+    Expr(Physical),
+    String(String),
+}
+
+// arguments of a call split by kind, once they matched the signature
+pub(super) struct Arguments<'a> {
+    pub(super) numbers: Vec<f64>,
+    pub(super) strings: Vec<String>,
+    pub(super) vector: Option<&'a Physical>,
+}
+
+impl Arguments<'_> {
+    fn labels(&self) -> Vec<Label> {
+        self.vector.map(Physical::labels).unwrap_or_default()
+    }
+}
+
+type Build = fn(&str, &Arguments<'_>) -> Result<Function, Error>;
+
+pub(super) struct Definition {
+    pub(super) name: &'static str,
+    kinds: &'static [Kind],
+    // arguments past this count may be left out
+    required: usize,
+    // the last kind repeats any number of times
+    variadic: bool,
+    fields: Fields,
+    pub(super) build: Build,
+}
+
+impl Definition {
+    const fn new(name: &'static str, kinds: &'static [Kind], fields: Fields, build: Build) -> Self {
+        Self {
+            name,
+            kinds,
+            required: kinds.len(),
+            variadic: false,
+            fields,
+            build,
+        }
+    }
+
+    const fn optional(mut self, required: usize) -> Self {
+        self.required = required;
+        self
+    }
+
+    const fn variadic(mut self) -> Self {
+        self.variadic = true;
+        self
+    }
+
+    pub(super) fn check(&self, args: &[Argument]) -> Result<(), Error> {
+        let max = (!self.variadic).then_some(self.kinds.len());
+        if args.len() < self.required || max.is_some_and(|max| args.len() > max) {
+            let expected = match max {
+                Some(max) if max == self.required => max.to_string(),
+                Some(max) => format!("{} to {}", self.required, max),
+                None => format!("at least {}", self.required),
+            };
+            return Err(Error::FunctionArity(
+                self.name.to_owned(),
+                expected,
+                args.len(),
+            ));
+        }
+
+        let repeated = self.kinds.last().filter(|_| self.variadic);
+        for (kind, arg) in self
+            .kinds
+            .iter()
+            .chain(repeated.into_iter().cycle())
+            .zip(args)
+        {
+            match (kind, arg) {
+                (Kind::Number, Argument::Expr(Physical::Number(_)))
+                | (Kind::String, Argument::String(_)) => {}
+                (Kind::Range, Argument::Expr(expr @ Physical::Scan(scan)))
+                    if scan.range.start.is_some() =>
+                {
+                    self.check_field(expr)?
+                }
+                (Kind::Instant, Argument::Expr(expr))
+                    if !expr.is_scalar()
+                        && !matches!(expr, Physical::Scan(scan) if scan.range.start.is_some()) =>
+                {
+                    self.check_field(expr)?
+                }
+                (Kind::Range, _) => return Err(Error::ExpectedRange(self.name.to_owned())),
+                (Kind::Instant, _) => return Err(Error::ExpectedVector(self.name.to_owned())),
+                (kind, _) => return Err(Error::ExpectedKind(self.name.to_owned(), *kind)),
+            }
+        }
+        Ok(())
+    }
+
+    // a field reaches a function through whatever passes its samples along
+    fn check_field(&self, expr: &Physical) -> Result<(), Error> {
+        let Some(r#type) = expr.field_type() else {
+            return Ok(());
+        };
+        match (self.fields, r#type.as_ref()) {
+            (Fields::Numeric, Field::Bool(_)) => Err(Error::UnsupportedField(
+                self.name.to_owned(),
+                r#type.clone(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub(super) fn lookup(name: &str) -> Option<&'static Definition> {
+    FUNCTIONS.iter().find(|definition| definition.name == name)
+}
+
+const FUNCTIONS: &[Definition] = &[
+    Definition::new("rate", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::Range(rate))
+    }),
+    Definition::new("increase", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::Range(increase))
+    }),
+    Definition::new("delta", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::Range(delta))
+    }),
+    Definition::new("irate", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::Range(irate))
+    }),
+    Definition::new("idelta", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::Range(idelta))
+    }),
+    Definition::new("avg_over_time", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::OverTime(OverTime::Avg))
+    }),
+    Definition::new("min_over_time", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::OverTime(OverTime::Min))
+    }),
+    Definition::new("max_over_time", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::OverTime(OverTime::Max))
+    }),
+    Definition::new("sum_over_time", &[Kind::Range], Fields::Numeric, |_, _| {
+        Ok(Function::OverTime(OverTime::Sum))
+    }),
+    Definition::new("count_over_time", &[Kind::Range], Fields::Any, |_, _| {
+        Ok(Function::OverTime(OverTime::Count))
+    }),
+    Definition::new("last_over_time", &[Kind::Range], Fields::Any, |_, _| {
+        Ok(Function::OverTime(OverTime::Last))
+    }),
+    Definition::new(
+        "stddev_over_time",
+        &[Kind::Range],
+        Fields::Numeric,
+        |_, _| Ok(Function::OverTime(OverTime::Stddev)),
+    ),
+    Definition::new("present_over_time", &[Kind::Range], Fields::Any, |_, _| {
+        Ok(Function::OverTime(OverTime::Present))
+    }),
+    Definition::new(
+        "quantile_over_time",
+        &[Kind::Number, Kind::Range],
+        Fields::Numeric,
+        |_, args| Ok(Function::OverTime(OverTime::Quantile(args.numbers[0]))),
+    ),
+    Definition::new("deriv", &[Kind::Range], Fields::Numeric, trend),
+    Definition::new(
+        "predict_linear",
+        &[Kind::Range, Kind::Number],
+        Fields::Numeric,
+        trend,
+    ),
+    Definition::new(
+        "holt_winters",
+        &[Kind::Range, Kind::Number, Kind::Number],
+        Fields::Numeric,
+        trend,
+    ),
+    Definition::new(
+        "histogram_quantile",
+        &[Kind::Number, Kind::Instant],
+        Fields::Numeric,
+        histogram_quantile,
+    ),
+    Definition::new(
+        "label_replace",
+        &[
+            Kind::Instant,
+            Kind::String,
+            Kind::String,
+            Kind::String,
+            Kind::String,
+        ],
+        Fields::Any,
+        label_replace,
+    ),
+    Definition::new(
+        "label_join",
+        &[Kind::Instant, Kind::String, Kind::String, Kind::String],
+        Fields::Any,
+        label_join,
+    )
+    .optional(3)
+    .variadic(),
+    Definition::new("absent", &[Kind::Instant], Fields::Any, absent),
+    Definition::new("absent_over_time", &[Kind::Range], Fields::Any, absent),
+    Definition::new("time", &[], Fields::Any, |_, _| Ok(Function::Time)),
+    Definition::new("timestamp", &[Kind::Instant], Fields::Any, |_, _| {
+        Ok(Function::Timestamp)
+    }),
+    Definition::new("abs", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("ceil", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("floor", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new(
+        "round",
+        &[Kind::Instant, Kind::Number],
+        Fields::Numeric,
+        math,
+    )
+    .optional(1),
+    Definition::new("sqrt", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("exp", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("ln", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("log2", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("log10", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("sgn", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("sin", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("cos", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("tan", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("asin", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("acos", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("atan", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("sinh", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("cosh", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("tanh", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("asinh", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("acosh", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("atanh", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("deg", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new("rad", &[Kind::Instant], Fields::Numeric, math),
+    Definition::new(
+        "clamp",
+        &[Kind::Instant, Kind::Number, Kind::Number],
+        Fields::Numeric,
+        math,
+    ),
+    Definition::new(
+        "clamp_min",
+        &[Kind::Instant, Kind::Number],
+        Fields::Numeric,
+        math,
+    ),
+    Definition::new(
+        "clamp_max",
+        &[Kind::Instant, Kind::Number],
+        Fields::Numeric,
+        math,
+    ),
+    // date functions read the evaluation time when called without a vector
+    Definition::new("minute", &[Kind::Instant], Fields::Numeric, math).optional(0),
+    Definition::new("hour", &[Kind::Instant], Fields::Numeric, math).optional(0),
+    Definition::new("day_of_week", &[Kind::Instant], Fields::Numeric, math).optional(0),
+    Definition::new("day_of_month", &[Kind::Instant], Fields::Numeric, math).optional(0),
+    Definition::new("days_in_month", &[Kind::Instant], Fields::Numeric, math).optional(0),
+    Definition::new("month", &[Kind::Instant], Fields::Numeric, math).optional(0),
+    Definition::new("year", &[Kind::Instant], Fields::Numeric, math).optional(0),
+];
+
+fn math(name: &str, args: &Arguments<'_>) -> Result<Function, Error> {
+    Math::from_name(name, &args.numbers)
+        .map(Function::Math)
+        .ok_or_else(|| Error::FunctionParam(name.to_owned()))
+}
+
+fn trend(name: &str, args: &Arguments<'_>) -> Result<Function, Error> {
+    Trend::from_name(name, &args.numbers)
+        .map(Function::Trend)
+        .ok_or_else(|| Error::FunctionParam(name.to_owned()))
+}
+
+fn histogram_quantile(name: &str, args: &Arguments<'_>) -> Result<Function, Error> {
+    let labels = args.labels();
+    let le = labels
+        .iter()
+        .position(|label| label.name == "le")
+        .ok_or_else(|| Error::BucketLabel(name.to_owned()))?;
+    if !matches!(labels[le].r#type, LabelType::String(_) | LabelType::Int(_)) {
+        return Err(Error::BucketLabel(name.to_owned()));
+    }
+    Ok(Function::HistogramQuantile {
+        quantile: args.numbers[0],
+        le,
+    })
+}
+
+fn label_replace(_: &str, args: &Arguments<'_>) -> Result<Function, Error> {
+    let labels = args.labels();
+    let [destination, replacement, source, regex] = args.strings.as_slice() else {
+        unreachable!()
+    };
+    Ok(Function::Relabel(relabel(
+        &labels,
+        destination,
+        Rewrite::Replace {
+            source: labels.iter().position(|label| &label.name == source),
+            regex: Regex::new(&format!("^(?:{regex})$"))?,
+            replacement: replacement.clone(),
+        },
+    )?))
+}
+
+fn label_join(_: &str, args: &Arguments<'_>) -> Result<Function, Error> {
+    let labels = args.labels();
+    let [destination, separator, sources @ ..] = args.strings.as_slice() else {
+        unreachable!()
+    };
+    Ok(Function::Relabel(relabel(
+        &labels,
+        destination,
+        Rewrite::Join {
+            sources: sources
+                .iter()
+                .map(|source| labels.iter().position(|label| &label.name == source))
+                .collect(),
+            separator: separator.clone(),
+        },
+    )?))
+}
+
+fn relabel(labels: &[Label], destination: &str, rewrite: Rewrite) -> Result<Relabel, Error> {
+    let mut chars = destination.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_');
+    if !valid {
+        return Err(Error::InvalidLabel(destination.to_owned()));
+    }
+    Ok(Relabel {
+        name: destination.to_owned(),
+        destination: labels
+            .iter()
+            .position(|label| label.name == destination)
+            .unwrap_or(labels.len()),
+        rewrite,
+    })
+}
+
+fn absent(_: &str, args: &Arguments<'_>) -> Result<Function, Error> {
+    let values = match args.vector {
+        Some(Physical::Scan(scan)) => equal_labels(scan).1,
+        _ => vec![],
+    };
+    Ok(Function::Absent(values))
+}
+
+// a selector passes the labels of its equality matchers on to the absent series,
+// a label only drops out when its equality matchers ask for different values
+pub(super) fn equal_labels(scan: &Scan) -> (Vec<Label>, Vec<Option<LabelValue>>) {
+    scan.matcher
+        .iter()
+        .zip(&scan.resource.meta.schema.labels)
+        .filter_map(|(ops, label)| {
+            let mut values = ops.iter().filter_map(|op| match op {
+                MatcherOp::LiteralEqual(Some(value)) => Some(value),
+                _ => None,
+            });
+            let value = values.next()?;
+            values
+                .all(|other| other == value)
+                .then(|| (label.clone(), Some(value.clone())))
+        })
+        .unzip()
+}
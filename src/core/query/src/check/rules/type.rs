@@ -1,3 +1,5 @@
+use std::ops::Bound;
+
 use common::{column::ColumnType, query::MatcherOp};
 
 use super::{Rule, TypeMismatch};
@@ -7,7 +9,7 @@ impl Rule for Scan {
     fn check(&self, env: &Env) -> Result<(), TypeMismatch> {
         let columns = &env.table.as_ref().unwrap().meta.schema.labels;
         for (matcher, column) in self.matcher.iter().zip(columns.iter()) {
-            if let Some(op) = matcher {
+            for op in matcher {
                 match op {
                     MatcherOp::LiteralEqual(Some(matcher)) => {
                         let expect = column.r#type.r#type();
@@ -31,6 +33,50 @@ impl Rule for Scan {
                             });
                         }
                     }
+                    MatcherOp::In(matchers) | MatcherOp::NotIn(matchers) => {
+                        let expect = column.r#type.r#type();
+                        for matcher in matchers {
+                            let found = matcher.r#type();
+                            if expect != found {
+                                return Err(TypeMismatch {
+                                    place: column.name.clone(),
+                                    expect: ColumnType::Label(expect),
+                                    found: ColumnType::Label(found),
+                                });
+                            }
+                        }
+                    }
+                    MatcherOp::CidrMatch(cidr) | MatcherOp::CidrNotMatch(cidr) => {
+                        let expect = column.r#type.r#type();
+                        let found = cidr.r#type();
+                        if expect != found {
+                            return Err(TypeMismatch {
+                                place: column.name.clone(),
+                                expect: ColumnType::Label(expect),
+                                found: ColumnType::Label(found),
+                            });
+                        }
+                    }
+                    MatcherOp::LessThan(_)
+                    | MatcherOp::LessEqual(_)
+                    | MatcherOp::GreaterThan(_)
+                    | MatcherOp::GreaterEqual(_)
+                    | MatcherOp::Between(_, _) => {
+                        let (start, end) = op.bounds().unwrap();
+                        for bound in [start, end] {
+                            if let Bound::Included(matcher) | Bound::Excluded(matcher) = bound {
+                                let expect = column.r#type.r#type();
+                                let found = matcher.r#type();
+                                if expect != found {
+                                    return Err(TypeMismatch {
+                                        place: column.name.clone(),
+                                        expect: ColumnType::Label(expect),
+                                        found: ColumnType::Label(found),
+                                    });
+                                }
+                            }
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -0,0 +1,1214 @@
+use std::sync::{Arc, RwLock};
+
+use chunk::mutable::Meta;
+use common::{
+    column::label::LabelValue,
+    context::Context,
+    schema::Label,
+    time::{Duration, Instant, Range},
+    DynError,
+};
+use resource::db::DB;
+use thiserror::Error;
+
+use crate::{
+    check::{self, Checker},
+    execute::{
+        binary::{self, Side},
+        plan::{plan, scalar, Context as PlanContext},
+    },
+    parse::{Evaluation, Parser},
+    plan::physical::{Physical, Scan},
+    Layer, Pass,
+};
+
+pub const LOOKBACK: Duration = Duration::from_secs(5 * 60);
+
+// points a range query may return per series
+pub const MAX_POINTS: usize = 11_000;
+
+// written by Prometheus when a series disappears
+pub const STALE_NAN: u64 = 0x7ff0_0000_0000_0002;
+
+#[inline]
+pub fn is_stale(value: f64) -> bool {
+    value.to_bits() == STALE_NAN
+}
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Check(#[from] check::Error),
+    #[error(transparent)]
+    Plan(#[from] Box<dyn std::error::Error>),
+    #[error(transparent)]
+    Execute(#[from] DynError),
+    #[error("zero or negative query resolution step widths are not accepted")]
+    InvalidStep,
+    #[error("end timestamp must not be before start time")]
+    InvalidRange,
+    #[error(
+        "exceeded maximum resolution of {} points per timeseries, try decreasing the query \
+         resolution (?step=XX)",
+        MAX_POINTS
+    )]
+    TooManyPoints,
+    #[error("range vector selector can not be evaluated at a step")]
+    RangeVector,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Steps {
+    pub start: Instant,
+    pub end: Instant,
+    pub step: Duration,
+    pub lookback: Duration,
+}
+
+impl Steps {
+    #[inline]
+    pub fn new(start: Instant, end: Instant, step: Duration) -> Result<Self, Error> {
+        if step.as_millis() <= 0 {
+            return Err(Error::InvalidStep);
+        }
+        if end < start {
+            return Err(Error::InvalidRange);
+        }
+        let steps = Self {
+            start,
+            end,
+            step,
+            lookback: LOOKBACK,
+        };
+        if steps.len() > MAX_POINTS {
+            return Err(Error::TooManyPoints);
+        }
+        Ok(steps)
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        ((self.end - self.start) / self.step) as usize + 1
+    }
+
+    #[inline]
+    pub fn times(&self) -> impl Iterator<Item = Instant> + '_ {
+        (0..self.len()).map(|step| self.start + self.step * step as i64)
+    }
+
+    // a slot per step, for samples read at the steps
+    #[inline]
+    pub(crate) fn meta(&self) -> Meta {
+        Meta::new(self.start, self.step, 0, self.len() as u32)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub labels: Vec<Option<LabelValue>>,
+    pub points: Vec<(Instant, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    pub labels: Vec<Label>,
+    pub series: Vec<Series>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub labels: Vec<Option<LabelValue>>,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector {
+    pub labels: Vec<Label>,
+    pub time: Instant,
+    pub samples: Vec<Sample>,
+}
+
+pub async fn query(
+    db: Arc<RwLock<DB>>,
+    literal: &str,
+    time: Instant,
+    lookback: Duration,
+) -> Result<Vector, Error> {
+    let expr = Checker::new(db)
+        .layer(Parser::with_evaluation(Evaluation::instant(time)).layer(()))
+        .apply(literal)?;
+    evaluate_instant(expr, time, lookback).await
+}
+
+// an instant query is a range query of a single step
+pub async fn evaluate_instant(
+    expr: Physical,
+    time: Instant,
+    lookback: Duration,
+) -> Result<Vector, Error> {
+    let steps = Steps {
+        start: time,
+        end: time,
+        step: Duration::SECOND,
+        lookback,
+    };
+    let matrix = evaluate(expr, &steps).await?;
+    Ok(Vector {
+        labels: matrix.labels,
+        time,
+        samples: matrix
+            .series
+            .into_iter()
+            .filter_map(|series| {
+                Some(Sample {
+                    value: series.points.first()?.1,
+                    labels: series.labels,
+                })
+            })
+            .collect(),
+    })
+}
+
+pub async fn query_range(
+    db: Arc<RwLock<DB>>,
+    literal: &str,
+    start: Instant,
+    end: Instant,
+    step: Duration,
+) -> Result<Matrix, Error> {
+    let steps = Steps::new(start, end, step)?;
+    let expr = Checker::new(db)
+        .layer(Parser::with_evaluation(Evaluation::range(start, end)).layer(()))
+        .apply(literal)?;
+    evaluate(expr, &steps).await
+}
+
+// the plan reads samples at every step, so every value of a vector holds one slot per step
+pub async fn evaluate(expr: Physical, steps: &Steps) -> Result<Matrix, Error> {
+    if let Physical::Scan(Scan {
+        range: Range { start: Some(_), .. },
+        ..
+    }) = expr
+    {
+        return Err(Error::RangeVector);
+    }
+    let times = steps.times().collect::<Vec<_>>();
+    let mut cx = PlanContext::with_steps(*steps);
+    if expr.is_scalar() {
+        let values = scalar(&cx, expr)?;
+        return Ok(Matrix {
+            labels: vec![],
+            series: vec![Series {
+                labels: vec![],
+                points: times
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, time)| (*time, binary::scalar_at(&values, slot)))
+                    .collect(),
+            }],
+        });
+    }
+
+    let (labels, field) = (expr.labels(), expr.field());
+    let execution = plan(&mut cx, expr)?;
+    let mut side = Side::collect(&mut Context::new(256), execution, labels, field).await?;
+    side.rebase(&steps.meta());
+    Ok(Matrix {
+        labels: side.labels,
+        series: side
+            .series
+            .into_iter()
+            .map(|series| Series {
+                labels: series.labels,
+                points: times
+                    .iter()
+                    .zip(series.values)
+                    .filter_map(|(time, value)| Some((*time, value?)))
+                    .collect(),
+            })
+            .filter(|series| !series.points.is_empty())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use chunk::mutable::MutableChunk;
+    use common::{
+        column::{
+            field::Field,
+            label::{Label, LabelValue},
+        },
+        time::{Duration, Instant},
+    };
+    use resource::db::{tests::test_db, DB};
+
+    use super::{query, query_range, Error, LOOKBACK, STALE_NAN};
+    use crate::{check, execute::binary};
+
+    type Rows = Vec<(Vec<Option<LabelValue>>, Vec<Option<f64>>)>;
+
+    fn string(value: &str) -> Option<LabelValue> {
+        Some(Label::String(Vec::from(value)))
+    }
+
+    // pushes one chunk into the table, a row per series and a slot per value
+    fn load(db: &Arc<RwLock<DB>>, table: &str, start_at: Instant, unit: Duration, rows: Rows) {
+        let table = db.read().unwrap().get(table).unwrap().clone();
+        let width = rows.iter().map(|(_, values)| values.len()).max();
+        let mut chunk = MutableChunk::new(
+            &table.meta.schema,
+            start_at,
+            unit,
+            rows.len() as u32,
+            width.unwrap_or(0) as u32,
+        );
+        for (row, (labels, values)) in rows.into_iter().enumerate() {
+            chunk.push(labels).unwrap();
+            for (slot, value) in values.into_iter().enumerate() {
+                chunk
+                    .set(
+                        row,
+                        0,
+                        start_at + unit * slot as u32,
+                        value.map(Field::Float64),
+                    )
+                    .unwrap();
+            }
+        }
+        table.shards.get().borrow_mut().mutable.push(chunk);
+    }
+
+    #[test]
+    fn range_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                for (chunk_start, values) in [
+                    (start_at, vec![Some(1.0), None, Some(3.0)]),
+                    (start_at + unit * 3u32, vec![None, Some(5.0), None]),
+                ] {
+                    let scaled = values.iter().map(|value| value.map(|v| v * 10.0)).collect();
+                    load(
+                        &db,
+                        "foo.bar.something_used",
+                        chunk_start,
+                        unit,
+                        vec![
+                            (vec![string("prod"), string("200"), None, None], values),
+                            (vec![string("dev"), string("200"), None, None], scaled),
+                        ],
+                    );
+                }
+
+                let end = start_at + unit * 5u32;
+                let matrix = query_range(
+                    db.clone(),
+                    r#"foo.bar.something_used{env="prod"}"#,
+                    start_at,
+                    end,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(matrix.series.len(), 1);
+                assert_eq!(
+                    matrix.series[0].labels,
+                    vec![string("prod"), string("200"), None, None]
+                );
+                assert_eq!(
+                    matrix.series[0]
+                        .points
+                        .iter()
+                        .map(|(_, value)| *value)
+                        .collect::<Vec<_>>(),
+                    vec![1.0, 1.0, 3.0, 3.0, 5.0, 5.0]
+                );
+                assert_eq!(matrix.series[0].points[5].0, end);
+
+                // the second chunk has nothing below 4, but its samples still hide older ones
+                let matrix = query_range(
+                    db.clone(),
+                    r#"foo.bar.something_used{env="prod"} < 4"#,
+                    start_at,
+                    end,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    matrix.series[0]
+                        .points
+                        .iter()
+                        .map(|(_, value)| *value)
+                        .collect::<Vec<_>>(),
+                    vec![1.0, 1.0, 3.0, 3.0]
+                );
+
+                let matrix = query_range(
+                    db.clone(),
+                    r#"foo.bar.something_used{env="dev"} / on (status) foo.bar.something_used{env="prod"}"#,
+                    start_at,
+                    end,
+                    unit * 2u32,
+                )
+                .await
+                .unwrap();
+                assert_eq!(matrix.series.len(), 1);
+                assert_eq!(
+                    matrix.series[0].points,
+                    vec![
+                        (start_at, 10.0),
+                        (start_at + unit * 2u32, 10.0),
+                        (start_at + unit * 4u32, 10.0)
+                    ]
+                );
+
+                let matrix = query_range(
+                    db.clone(),
+                    "sum by (status) (foo.bar.something_used)",
+                    start_at,
+                    end,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(matrix.labels.len(), 1);
+                assert_eq!(
+                    matrix.series[0]
+                        .points
+                        .iter()
+                        .map(|(_, value)| *value)
+                        .collect::<Vec<_>>(),
+                    vec![11.0, 11.0, 33.0, 33.0, 55.0, 55.0]
+                );
+
+                let matrix = query_range(
+                    db.clone(),
+                    r#"foo.bar.something_used{env="prod"} offset 20s"#,
+                    start_at,
+                    start_at + unit * 2u32,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(matrix.series[0].points, vec![(start_at + unit * 2u32, 1.0)]);
+
+                assert!(matches!(
+                    query_range(db.clone(), "foo.bar.something_used[1m]", start_at, end, unit)
+                        .await,
+                    Err(Error::RangeVector)
+                ));
+                assert!(matches!(
+                    query_range(db.clone(), "foo.bar.something_used", end, start_at, unit).await,
+                    Err(Error::InvalidRange)
+                ));
+                assert!(matches!(
+                    query_range(
+                        db,
+                        "foo.bar.something_used",
+                        start_at,
+                        start_at + Duration::from_secs(11_000),
+                        Duration::SECOND
+                    )
+                    .await,
+                    Err(Error::TooManyPoints)
+                ));
+            });
+    }
+
+    #[test]
+    fn binary_range_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                // wider than the lookback, so every step sees only its own slot
+                let unit = Duration::from_secs(10 * 60);
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![
+                        (
+                            vec![string("dev"), string("200"), None, None],
+                            vec![Some(1.0), Some(2.0), None, None],
+                        ),
+                        (
+                            vec![string("dev"), string("500"), None, None],
+                            vec![None, None, Some(3.0), Some(4.0)],
+                        ),
+                        (
+                            vec![string("prod"), string("200"), None, None],
+                            vec![Some(10.0); 4],
+                        ),
+                    ],
+                );
+
+                let end = start_at + unit * 3u32;
+                let values = |literal: &'static str| {
+                    let db = db.clone();
+                    async move {
+                        let matrix = query_range(db, literal, start_at, end, unit).await?;
+                        Ok::<_, Error>(
+                            matrix
+                                .series
+                                .into_iter()
+                                .map(|series| {
+                                    series.points.into_iter().map(|(_, value)| value).collect()
+                                })
+                                .collect::<Vec<Vec<_>>>(),
+                        )
+                    }
+                };
+
+                // the dev series hand the empty signature over to each other
+                assert_eq!(
+                    values(
+                        r#"foo.bar.something_used{env="prod"} - on () foo.bar.something_used{env="dev"}"#
+                    )
+                    .await
+                    .unwrap(),
+                    vec![vec![9.0, 8.0, 7.0, 6.0]]
+                );
+                assert_eq!(
+                    values(
+                        r#"foo.bar.something_used{env="dev"} * on () group_right foo.bar.something_used{env="prod"}"#
+                    )
+                    .await
+                    .unwrap(),
+                    vec![vec![10.0, 20.0, 30.0, 40.0]]
+                );
+
+                // prod and dev overlap at every step
+                match values(r#"foo.bar.something_used - on () foo.bar.something_used{env="dev"}"#)
+                    .await
+                {
+                    Err(Error::Execute(err)) => assert!(matches!(
+                        err.downcast_ref::<binary::Error>(),
+                        Some(binary::Error::ManyToOne)
+                    )),
+                    others => panic!("{:?}", others.map(|_| ())),
+                }
+            });
+    }
+
+    #[test]
+    fn instant_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                let stale = Some(f64::from_bits(STALE_NAN));
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![
+                        (
+                            vec![string("prod"), string("200"), None, None],
+                            vec![Some(1.0), None, Some(3.0), None, None, None],
+                        ),
+                        (
+                            vec![string("dev"), string("200"), None, None],
+                            vec![Some(10.0), None, None, stale, None, None],
+                        ),
+                    ],
+                );
+
+                let values = |vector: super::Vector| {
+                    vector
+                        .samples
+                        .into_iter()
+                        .map(|sample| (sample.labels[0].clone(), sample.value))
+                        .collect::<Vec<_>>()
+                };
+
+                let vector = query(
+                    db.clone(),
+                    "foo.bar.something_used",
+                    start_at + unit * 5u32,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(vector.time, start_at + unit * 5u32);
+                assert_eq!(values(vector), vec![(string("prod"), 3.0)]);
+
+                let vector = query(
+                    db.clone(),
+                    "foo.bar.something_used",
+                    start_at + unit * 5u32,
+                    Duration::from_secs(20),
+                )
+                .await
+                .unwrap();
+                assert!(vector.samples.is_empty());
+
+                let vector = query(
+                    db.clone(),
+                    "clamp_max(sqrt(foo.bar.something_used * 8), 5)",
+                    start_at + Duration::from_secs(25),
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    values(vector),
+                    vec![(string("dev"), 5.0), (string("prod"), 24f64.sqrt())]
+                );
+
+                let vector = query(
+                    db.clone(),
+                    "foo.bar.something_used * 2",
+                    start_at + Duration::from_secs(25),
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    values(vector),
+                    vec![(string("dev"), 20.0), (string("prod"), 6.0)]
+                );
+
+                let matrix = query_range(
+                    db,
+                    r#"foo.bar.something_used{env="dev"}"#,
+                    start_at,
+                    start_at + unit * 5u32,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(matrix.series[0].points.len(), 3);
+            });
+    }
+
+    #[test]
+    fn time_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![
+                        (
+                            vec![string("prod"), string("200"), None, None],
+                            vec![Some(1.0), None, Some(3.0), None, None, None],
+                        ),
+                        (
+                            vec![string("dev"), string("200"), None, None],
+                            vec![Some(10.0), None, None, None, None, None],
+                        ),
+                    ],
+                );
+
+                let values = |vector: super::Vector| {
+                    vector
+                        .samples
+                        .into_iter()
+                        .map(|sample| (sample.labels.first().cloned().flatten(), sample.value))
+                        .collect::<Vec<_>>()
+                };
+
+                let vector = query(db.clone(), "time() - 1", start_at + unit * 5u32, LOOKBACK)
+                    .await
+                    .unwrap();
+                assert_eq!(values(vector), vec![(None, 1049.0)]);
+
+                let vector = query(
+                    db.clone(),
+                    "timestamp(foo.bar.something_used)",
+                    start_at + Duration::from_secs(25),
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    values(vector),
+                    vec![(string("dev"), 1000.0), (string("prod"), 1020.0)]
+                );
+
+                let vector = query(
+                    db.clone(),
+                    "minute(timestamp(foo.bar.something_used))",
+                    start_at + Duration::from_secs(25),
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    values(vector),
+                    vec![(string("dev"), 16.0), (string("prod"), 17.0)]
+                );
+
+                let vector = query(db.clone(), "year()", start_at, LOOKBACK)
+                    .await
+                    .unwrap();
+                assert_eq!(values(vector), vec![(None, 1970.0)]);
+
+                let matrix = query_range(
+                    db.clone(),
+                    "time() * 2",
+                    start_at,
+                    start_at + unit * 2u32,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    matrix.series[0].points,
+                    vec![
+                        (start_at, 2000.0),
+                        (start_at + unit, 2020.0),
+                        (start_at + unit * 2u32, 2040.0)
+                    ]
+                );
+
+                let matrix = query_range(
+                    db,
+                    r#"timestamp(foo.bar.something_used{env="prod"})"#,
+                    start_at,
+                    start_at + unit * 3u32,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    matrix.series[0]
+                        .points
+                        .iter()
+                        .map(|(_, value)| *value)
+                        .collect::<Vec<_>>(),
+                    vec![1000.0, 1000.0, 1020.0, 1020.0]
+                );
+            });
+    }
+
+    #[test]
+    fn rate_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                // the counter resets between 40 and 5
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![(
+                        vec![string("prod"), string("200"), None, None],
+                        vec![
+                            Some(10.0),
+                            Some(20.0),
+                            Some(40.0),
+                            Some(5.0),
+                            Some(15.0),
+                            None,
+                        ],
+                    )],
+                );
+
+                let vector = query(
+                    db.clone(),
+                    "increase(foo.bar.something_used[1m])",
+                    start_at + unit * 5u32,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(vector.samples.len(), 1);
+                assert!((vector.samples[0].value - 66.25).abs() < 1e-9);
+
+                let vector = query(
+                    db.clone(),
+                    "irate(foo.bar.something_used[1m])",
+                    start_at + unit * 5u32,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(vector.samples[0].value, 1.0);
+
+                let matrix = query_range(
+                    db,
+                    "rate(foo.bar.something_used[1m])",
+                    start_at + unit * 4u32,
+                    start_at + unit * 5u32,
+                    unit,
+                )
+                .await
+                .unwrap();
+                let points = &matrix.series[0].points;
+                assert_eq!(points.len(), 2);
+                assert!((points[0].1 - 55.0 / 60.0).abs() < 1e-9);
+                assert!((points[1].1 - 66.25 / 60.0).abs() < 1e-9);
+            });
+    }
+
+    // the series and expected values come from the functions tests of Prometheus
+    #[test]
+    fn trend_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let minutes = |minutes: u32| Instant::from_millis(0) + Duration::from_secs(60) * minutes;
+                // testcounter_reset_middle 0+10x4 0+10x5 and http_requests 0+80x10 every 5m
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    minutes(0),
+                    Duration::from_secs(300),
+                    vec![
+                        (
+                            vec![string("reset"), None, None, None],
+                            [0, 10, 20, 30, 40, 0, 10, 20, 30, 40, 50]
+                                .into_iter()
+                                .map(|value| Some(value as f64))
+                                .collect(),
+                        ),
+                        (
+                            vec![string("canary"), None, None, None],
+                            (0..=10).map(|i| Some(i as f64 * 80.0)).collect(),
+                        ),
+                    ],
+                );
+                let value = |literal: &'static str, time: Instant| {
+                    let db = db.clone();
+                    async move {
+                        let vector = query(db, literal, time, LOOKBACK).await.unwrap();
+                        assert_eq!(vector.samples.len(), 1, "{}", literal);
+                        vector.samples[0].value
+                    }
+                };
+
+                for (literal, expected) in [
+                    (
+                        r#"deriv(foo.bar.something_used{env="canary"}[50m])"#,
+                        0.26666666666666666,
+                    ),
+                    (
+                        r#"deriv(foo.bar.something_used{env="reset"}[100m])"#,
+                        0.010606060606060607,
+                    ),
+                    (
+                        r#"predict_linear(foo.bar.something_used{env="reset"}[100m], 3600)"#,
+                        76.81818181818181,
+                    ),
+                    (
+                        r#"predict_linear(foo.bar.something_used{env="reset"}[50m], 3600)"#,
+                        70.0,
+                    ),
+                ] {
+                    let value = value(literal, minutes(50)).await;
+                    assert!((value - expected).abs() < 1e-9, "{}: {}", literal, value);
+                }
+                let emulated = value(
+                    r#"predict_linear(foo.bar.something_used{env="canary"}[50m], 3600) - (foo.bar.something_used{env="canary"} + deriv(foo.bar.something_used{env="canary"}[50m]) * 3600)"#,
+                    minutes(10),
+                )
+                .await;
+                assert!(emulated.abs() < 1e-9);
+
+                // the prediction starts at the evaluation time, not where the offset moved it
+                let shifted = value(
+                    r#"predict_linear(foo.bar.something_used{env="reset"}[100m] offset 10m, 3600)"#,
+                    minutes(60),
+                )
+                .await;
+                let expected = value(
+                    r#"predict_linear(foo.bar.something_used{env="reset"}[100m], 4200)"#,
+                    minutes(50),
+                )
+                .await;
+                assert!((shifted - expected).abs() < 1e-9);
+
+                // http_requests 0+10x1000 and 0+20x1000 every 10s, read at 8000s
+                let db = test_db();
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    minutes(0),
+                    Duration::from_secs(10),
+                    [("0", 10.0), ("1", 20.0)]
+                        .into_iter()
+                        .map(|(instance, step)| {
+                            (
+                                vec![string(instance), None, None, None],
+                                (0..=800).map(|i| Some(i as f64 * step)).collect(),
+                            )
+                        })
+                        .collect(),
+                );
+                let vector = query(
+                    db,
+                    "holt_winters(foo.bar.something_used[1m], 0.01, 0.1)",
+                    Instant::from_millis(8_000_000),
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                let values = vector
+                    .samples
+                    .iter()
+                    .map(|sample| (sample.labels[0].clone(), sample.value))
+                    .collect::<Vec<_>>();
+                assert_eq!(values.len(), 2);
+                for ((instance, value), expected) in values.into_iter().zip([8000.0, 16000.0]) {
+                    assert!((value - expected).abs() < 1e-9, "{:?}: {}", instance, value);
+                }
+            });
+    }
+
+    #[test]
+    fn over_time_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![(
+                        vec![string("prod"), string("200"), None, None],
+                        vec![Some(1.0), None, Some(3.0), Some(4.0), Some(2.0), None],
+                    )],
+                );
+
+                for (function, expected) in [
+                    ("avg_over_time", 2.5),
+                    ("min_over_time", 1.0),
+                    ("max_over_time", 4.0),
+                    ("sum_over_time", 10.0),
+                    ("count_over_time", 4.0),
+                    ("last_over_time", 2.0),
+                    ("quantile_over_time", 2.5),
+                    ("stddev_over_time", 1.25f64.sqrt()),
+                    ("present_over_time", 1.0),
+                ] {
+                    let param = if function == "quantile_over_time" {
+                        "0.5, "
+                    } else {
+                        ""
+                    };
+                    let vector = query(
+                        db.clone(),
+                        &format!("{function}({param}foo.bar.something_used[1m])"),
+                        start_at + unit * 5u32,
+                        LOOKBACK,
+                    )
+                    .await
+                    .unwrap();
+                    assert!(
+                        (vector.samples[0].value - expected).abs() < 1e-9,
+                        "{function}"
+                    );
+                }
+
+                let matrix = query_range(
+                    db,
+                    "max_over_time(foo.bar.something_used[20s])",
+                    start_at,
+                    start_at + unit * 4u32,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(
+                    matrix.series[0]
+                        .points
+                        .iter()
+                        .map(|(_, value)| *value)
+                        .collect::<Vec<_>>(),
+                    vec![1.0, 1.0, 3.0, 4.0, 4.0]
+                );
+            });
+    }
+
+    #[test]
+    fn histogram_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                load(
+                    &db,
+                    "foo.bar.latency_bucket",
+                    start_at,
+                    unit,
+                    [
+                        ("prod", "0.1", 10.0),
+                        ("prod", "0.5", 30.0),
+                        ("prod", "+Inf", 40.0),
+                        ("dev", "0.1", 0.0),
+                        ("dev", "0.5", 10.0),
+                        ("dev", "+Inf", 20.0),
+                    ]
+                    .into_iter()
+                    .map(|(env, le, count)| (vec![string(env), string(le)], vec![Some(count)]))
+                    .collect(),
+                );
+
+                let vector = query(
+                    db.clone(),
+                    "histogram_quantile(0.5, foo.bar.latency_bucket)",
+                    start_at,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(vector.labels.len(), 1);
+                assert_eq!(
+                    vector
+                        .samples
+                        .iter()
+                        .map(|sample| (sample.labels[0].clone(), sample.value))
+                        .collect::<Vec<_>>(),
+                    vec![(string("dev"), 0.5), (string("prod"), 0.1 + 0.4 * 0.5)]
+                );
+
+                let matrix = query_range(
+                    db.clone(),
+                    "histogram_quantile(0.5, sum by (le) (foo.bar.latency_bucket))",
+                    start_at,
+                    start_at,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert!(matrix.labels.is_empty());
+                assert!((matrix.series[0].points[0].1 - (0.1 + 0.4 * 20.0 / 30.0)).abs() < 1e-9);
+
+                assert!(matches!(
+                    query(
+                        db,
+                        "histogram_quantile(0.5, foo.bar.something_used)",
+                        start_at,
+                        LOOKBACK
+                    )
+                    .await,
+                    Err(Error::Check(check::Error::BucketLabel(_)))
+                ));
+            });
+    }
+
+    #[test]
+    fn relabel_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![
+                        (
+                            vec![string("prod"), string("200"), None, Some(Label::Int(3))],
+                            vec![Some(1.0)],
+                        ),
+                        (
+                            vec![string("dev"), string("500"), None, None],
+                            vec![Some(2.0)],
+                        ),
+                    ],
+                );
+
+                let vector = query(
+                    db.clone(),
+                    r#"label_replace(foo.bar.something_used, "team", "$1-team", "env", "(p.*)")"#,
+                    start_at,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(vector.labels.len(), 5);
+                assert_eq!(vector.labels[4].name, "team");
+                assert_eq!(
+                    vector
+                        .samples
+                        .iter()
+                        .map(|sample| sample.labels[4].clone())
+                        .collect::<Vec<_>>(),
+                    vec![None, string("prod-team")]
+                );
+
+                let matrix = query_range(
+                    db.clone(),
+                    r#"label_join(foo.bar.something_used, "shard", "/", "env", "shard")"#,
+                    start_at,
+                    start_at,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(matrix.labels.len(), 4);
+                assert_eq!(
+                    matrix
+                        .series
+                        .iter()
+                        .map(|series| series.labels[3].clone())
+                        .collect::<Vec<_>>(),
+                    vec![string("dev/"), string("prod/3")]
+                );
+
+                assert!(matches!(
+                    query(
+                        db.clone(),
+                        r#"label_join(foo.bar.something_used, "1st", ",", "env")"#,
+                        start_at,
+                        LOOKBACK
+                    )
+                    .await,
+                    Err(Error::Check(check::Error::InvalidLabel(_)))
+                ));
+
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![(
+                        vec![string("test"), string("200"), None, Some(Label::Int(3))],
+                        vec![Some(3.0)],
+                    )],
+                );
+                assert!(matches!(
+                    query(
+                        db,
+                        r#"label_replace(foo.bar.something_used, "env", "prod", "env", "test")"#,
+                        start_at,
+                        LOOKBACK
+                    )
+                    .await,
+                    Err(Error::Execute(e))
+                        if e.to_string() == "vector cannot contain metrics with the same labelset"
+                ));
+            });
+    }
+
+    #[test]
+    fn absent_query() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let start_at = Instant::from_millis(1_000_000);
+                let unit = Duration::from_secs(10);
+                load(
+                    &db,
+                    "foo.bar.something_used",
+                    start_at,
+                    unit,
+                    vec![(
+                        vec![string("prod"), string("200"), None, None],
+                        vec![Some(1.0), None, None, None, None, None],
+                    )],
+                );
+
+                let vector = query(
+                    db.clone(),
+                    r#"absent(foo.bar.something_used{env="prod"})"#,
+                    start_at,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert!(vector.samples.is_empty());
+
+                let vector = query(
+                    db.clone(),
+                    r#"absent(foo.bar.something_used{env="test", status=~"5.."})"#,
+                    start_at,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert_eq!(vector.labels.len(), 1);
+                assert_eq!(vector.labels[0].name, "env");
+                assert_eq!(vector.samples.len(), 1);
+                assert_eq!(vector.samples[0].labels, vec![string("test")]);
+                assert_eq!(vector.samples[0].value, 1.0);
+
+                let vector = query(
+                    db.clone(),
+                    r#"absent(sum(foo.bar.something_used{env="test"}))"#,
+                    start_at,
+                    LOOKBACK,
+                )
+                .await
+                .unwrap();
+                assert!(vector.labels.is_empty());
+                assert_eq!(vector.samples.len(), 1);
+
+                let matrix = query_range(
+                    db,
+                    r#"absent_over_time(foo.bar.something_used{env="prod"}[15s])"#,
+                    start_at,
+                    start_at + unit * 4u32,
+                    unit,
+                )
+                .await
+                .unwrap();
+                assert_eq!(matrix.series.len(), 1);
+                assert_eq!(matrix.series[0].labels, vec![string("prod")]);
+                assert_eq!(
+                    matrix.series[0].points,
+                    vec![
+                        (start_at + unit * 2u32, 1.0),
+                        (start_at + unit * 3u32, 1.0),
+                        (start_at + unit * 4u32, 1.0)
+                    ]
+                );
+            });
+    }
+}
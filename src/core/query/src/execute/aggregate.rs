@@ -0,0 +1,1204 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    iter,
+};
+
+use chunk::mutable::{Meta, Records};
+use common::{
+    column::label::{Label as LabelValueType, LabelValue},
+    context::Context,
+    schema::Label,
+    try_yield, DynError,
+};
+use executor::Task;
+
+use super::{
+    binary::{cover, records, slot, Error, Series},
+    scan::ScanPlanner,
+    step::SelectPlanner,
+    Execution, ExecutionImpl, Planner,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateOp {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Count,
+    Stddev,
+    Stdvar,
+    Quantile,
+    TopK,
+    BottomK,
+    CountValues,
+}
+
+impl AggregateOp {
+    #[inline]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sum" => Some(Self::Sum),
+            "avg" => Some(Self::Avg),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "count" => Some(Self::Count),
+            "stddev" => Some(Self::Stddev),
+            "stdvar" => Some(Self::Stdvar),
+            "quantile" => Some(Self::Quantile),
+            "topk" => Some(Self::TopK),
+            "bottomk" => Some(Self::BottomK),
+            "count_values" => Some(Self::CountValues),
+            _ => None,
+        }
+    }
+
+    // topk and bottomk give back the input series instead of one series per group
+    #[inline]
+    pub fn keeps_series(&self) -> bool {
+        matches!(self, Self::TopK | Self::BottomK)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Param {
+    None,
+    Number(f64),
+    Label(String),
+}
+
+impl Param {
+    #[inline]
+    fn number(&self) -> f64 {
+        match self {
+            Param::Number(number) => *number,
+            _ => f64::NAN,
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn quantile(q: f64, values: &mut [f64]) -> f64 {
+    if values.is_empty() || q.is_nan() {
+        return f64::NAN;
+    }
+    if q < 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if q > 1.0 {
+        return f64::INFINITY;
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = q * (values.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = (lower + 1).min(values.len() - 1);
+    let weight = rank - rank.floor();
+    values[lower] * (1.0 - weight) + values[upper] * weight
+}
+
+// NaN always loses, whichever end is kept
+#[inline]
+// label values look like Prometheus formats them, infinities as `+Inf` and `-Inf`
+fn format_value(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".into()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".into()
+    } else {
+        value.to_string()
+    }
+}
+
+fn rank(op: AggregateOp, a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) if op == AggregateOp::TopK => b.total_cmp(&a),
+        (false, false) => a.total_cmp(&b),
+    }
+}
+
+type Candidates = Vec<(Vec<Option<LabelValue>>, Vec<Option<f64>>)>;
+
+// per slot state of a group, slots without any sample stay empty
+#[derive(Debug, Clone)]
+pub(crate) enum State {
+    Fold {
+        values: Vec<Option<f64>>,
+        counts: Vec<u64>,
+    },
+    Moments {
+        counts: Vec<u64>,
+        means: Vec<f64>,
+        deviations: Vec<f64>,
+    },
+    Samples(Vec<Vec<f64>>),
+    Series(Candidates),
+    Values(BTreeMap<String, Vec<u64>>),
+}
+
+impl State {
+    fn new(op: AggregateOp) -> Self {
+        match op {
+            AggregateOp::Sum
+            | AggregateOp::Avg
+            | AggregateOp::Min
+            | AggregateOp::Max
+            | AggregateOp::Count => State::Fold {
+                values: vec![],
+                counts: vec![],
+            },
+            AggregateOp::Stddev | AggregateOp::Stdvar => State::Moments {
+                counts: vec![],
+                means: vec![],
+                deviations: vec![],
+            },
+            AggregateOp::Quantile => State::Samples(vec![]),
+            AggregateOp::TopK | AggregateOp::BottomK => State::Series(vec![]),
+            AggregateOp::CountValues => State::Values(BTreeMap::new()),
+        }
+    }
+
+    #[inline]
+    fn accumulate(op: AggregateOp, value: &mut Option<f64>, other: f64) {
+        *value = Some(match (op, *value) {
+            (_, None) => other,
+            (AggregateOp::Min, Some(v)) if v.is_nan() || other < v => other,
+            (AggregateOp::Max, Some(v)) if v.is_nan() || other > v => other,
+            (AggregateOp::Min | AggregateOp::Max, Some(v)) => v,
+            (_, Some(v)) => v + other,
+        });
+    }
+
+    pub(crate) fn update<F>(&mut self, op: AggregateOp, series: F, values: &[Option<f64>])
+    where
+        F: FnOnce() -> Vec<Option<LabelValue>>,
+    {
+        let width = values.len();
+        match self {
+            State::Fold {
+                values: folded,
+                counts,
+            } => {
+                if folded.len() < width {
+                    folded.resize(width, None);
+                    counts.resize(width, 0);
+                }
+                for (slot, value) in values.iter().enumerate() {
+                    if let Some(value) = value {
+                        Self::accumulate(op, &mut folded[slot], *value);
+                        counts[slot] += 1;
+                    }
+                }
+            }
+            State::Moments {
+                counts,
+                means,
+                deviations,
+            } => {
+                if counts.len() < width {
+                    counts.resize(width, 0);
+                    means.resize(width, 0.0);
+                    deviations.resize(width, 0.0);
+                }
+                for (slot, value) in values.iter().enumerate() {
+                    if let Some(value) = value {
+                        counts[slot] += 1;
+                        let delta = value - means[slot];
+                        means[slot] += delta / counts[slot] as f64;
+                        deviations[slot] += delta * (value - means[slot]);
+                    }
+                }
+            }
+            State::Samples(samples) => {
+                if samples.len() < width {
+                    samples.resize(width, vec![]);
+                }
+                for (slot, value) in values.iter().enumerate() {
+                    if let Some(value) = value {
+                        samples[slot].push(*value);
+                    }
+                }
+            }
+            State::Series(candidates) => candidates.push((series(), values.to_vec())),
+            State::Values(counts) => {
+                for (slot, value) in values.iter().enumerate() {
+                    if let Some(value) = value {
+                        let counts = counts.entry(format_value(*value)).or_default();
+                        if counts.len() < width {
+                            counts.resize(width, 0);
+                        }
+                        counts[slot] += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // empty slots in front, for samples older than the first slot so far
+    fn shift(&mut self, slots: usize) {
+        fn prepend<T: Clone>(values: &mut Vec<T>, slots: usize, empty: T) {
+            values.splice(0..0, iter::repeat_n(empty, slots));
+        }
+        match self {
+            State::Fold { values, counts } => {
+                prepend(values, slots, None);
+                prepend(counts, slots, 0);
+            }
+            State::Moments {
+                counts,
+                means,
+                deviations,
+            } => {
+                prepend(counts, slots, 0);
+                prepend(means, slots, 0.0);
+                prepend(deviations, slots, 0.0);
+            }
+            State::Samples(samples) => prepend(samples, slots, vec![]),
+            State::Series(candidates) => {
+                for (_, values) in candidates.iter_mut() {
+                    prepend(values, slots, None);
+                }
+            }
+            State::Values(counts) => {
+                for counts in counts.values_mut() {
+                    prepend(counts, slots, 0);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn merge(&mut self, op: AggregateOp, other: State) {
+        match (self, other) {
+            (
+                State::Fold { values, counts },
+                State::Fold {
+                    values: other,
+                    counts: other_counts,
+                },
+            ) => {
+                if values.len() < other.len() {
+                    values.resize(other.len(), None);
+                    counts.resize(other.len(), 0);
+                }
+                for (slot, (value, count)) in other.into_iter().zip(other_counts).enumerate() {
+                    if let Some(value) = value {
+                        Self::accumulate(op, &mut values[slot], value);
+                        counts[slot] += count;
+                    }
+                }
+            }
+            (
+                State::Moments {
+                    counts,
+                    means,
+                    deviations,
+                },
+                State::Moments {
+                    counts: other_counts,
+                    means: other_means,
+                    deviations: other_deviations,
+                },
+            ) => {
+                if counts.len() < other_counts.len() {
+                    counts.resize(other_counts.len(), 0);
+                    means.resize(other_counts.len(), 0.0);
+                    deviations.resize(other_counts.len(), 0.0);
+                }
+                for (slot, count) in other_counts.into_iter().enumerate() {
+                    if count == 0 {
+                        continue;
+                    }
+                    let total = (counts[slot] + count) as f64;
+                    let delta = other_means[slot] - means[slot];
+                    deviations[slot] += other_deviations[slot]
+                        + delta * delta * counts[slot] as f64 * count as f64 / total;
+                    means[slot] += delta * count as f64 / total;
+                    counts[slot] += count;
+                }
+            }
+            (State::Samples(samples), State::Samples(other)) => {
+                if samples.len() < other.len() {
+                    samples.resize(other.len(), vec![]);
+                }
+                for (slot, other) in other.into_iter().enumerate() {
+                    samples[slot].extend(other);
+                }
+            }
+            (State::Series(candidates), State::Series(other)) => candidates.extend(other),
+            (State::Values(counts), State::Values(other)) => {
+                for (value, other) in other {
+                    let counts = counts.entry(value).or_default();
+                    if counts.len() < other.len() {
+                        counts.resize(other.len(), 0);
+                    }
+                    for (slot, count) in other.into_iter().enumerate() {
+                        counts[slot] += count;
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // for every slot, marks the k best candidates having a sample there
+    fn select(op: AggregateOp, k: f64, candidates: &Candidates) -> Vec<Vec<bool>> {
+        let width = candidates
+            .iter()
+            .map(|(_, values)| values.len())
+            .max()
+            .unwrap_or(0);
+        let mut kept = vec![vec![false; candidates.len()]; width];
+        if k.is_nan() || k < 1.0 {
+            return kept;
+        }
+        for (slot, kept) in kept.iter_mut().enumerate() {
+            let mut present = candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(position, (_, values))| Some((position, (*values.get(slot)?)?)))
+                .collect::<Vec<_>>();
+            present.sort_by(|a, b| rank(op, a.1, b.1));
+            for (position, _) in present.into_iter().take(k as usize) {
+                kept[position] = true;
+            }
+        }
+        kept
+    }
+
+    // drops candidates that can never be selected, before they cross threads
+    fn prune(&mut self, op: AggregateOp, param: &Param) {
+        if let State::Series(candidates) = self {
+            let kept = Self::select(op, param.number(), candidates);
+            let mut position = 0;
+            candidates.retain(|_| {
+                position += 1;
+                kept.iter().any(|kept| kept[position - 1])
+            });
+        }
+    }
+
+    fn finish(self, op: AggregateOp, param: &Param, key: Vec<Option<LabelValue>>) -> Vec<Series> {
+        let values = match self {
+            State::Fold { values, counts } => values
+                .into_iter()
+                .zip(counts)
+                .map(|(value, count)| match op {
+                    AggregateOp::Avg => value.map(|sum| sum / count as f64),
+                    AggregateOp::Count => value.map(|_| count as f64),
+                    _ => value,
+                })
+                .collect(),
+            State::Moments {
+                counts, deviations, ..
+            } => counts
+                .into_iter()
+                .zip(deviations)
+                .map(|(count, deviation)| {
+                    let variance = deviation / count as f64;
+                    (count > 0).then(|| match op {
+                        AggregateOp::Stddev => variance.sqrt(),
+                        _ => variance,
+                    })
+                })
+                .collect(),
+            State::Samples(samples) => samples
+                .into_iter()
+                .map(|mut samples| {
+                    (!samples.is_empty()).then(|| quantile(param.number(), &mut samples))
+                })
+                .collect(),
+            // a series spread over chunks is one candidate per chunk
+            State::Series(candidates) => {
+                let kept = Self::select(op, param.number(), &candidates);
+                let mut series = BTreeMap::<_, Vec<Option<f64>>>::new();
+                for (position, (labels, values)) in candidates.into_iter().enumerate() {
+                    let merged = series.entry(labels).or_default();
+                    if merged.len() < values.len() {
+                        merged.resize(values.len(), None);
+                    }
+                    for ((merged, value), kept) in merged.iter_mut().zip(values).zip(&kept) {
+                        if kept[position] && value.is_some() {
+                            *merged = value;
+                        }
+                    }
+                }
+                return series
+                    .into_iter()
+                    .map(|(labels, values)| Series { labels, values })
+                    .collect();
+            }
+            // the counted value becomes the last label
+            State::Values(counts) => {
+                return counts
+                    .into_iter()
+                    .map(|(value, counts)| {
+                        let mut labels = key.clone();
+                        labels.push(Some(LabelValueType::String(value.into_bytes())));
+                        Series {
+                            labels,
+                            values: counts
+                                .into_iter()
+                                .map(|count| (count > 0).then_some(count as f64))
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            }
+        };
+        vec![Series {
+            labels: key,
+            values,
+        }]
+    }
+}
+
+// groups keyed by the values of the grouping labels
+#[derive(Debug)]
+pub(crate) struct Groups {
+    op: AggregateOp,
+    param: Param,
+    groups: HashMap<Vec<Option<LabelValue>>, State>,
+    // where the slots of every state are, covering every batch so far
+    pub(crate) meta: Option<Meta>,
+}
+
+impl Groups {
+    #[inline]
+    pub(crate) fn new(op: AggregateOp, param: Param) -> Self {
+        Self {
+            op,
+            param,
+            groups: HashMap::new(),
+            meta: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn merge(&mut self, key: Vec<Option<LabelValue>>, state: State) {
+        let op = self.op;
+        self.groups
+            .entry(key)
+            .or_insert_with(|| State::new(op))
+            .merge(op, state);
+    }
+
+    // grows the slots to cover meta, and gives the slot its first one lands on
+    fn align(&mut self, meta: &Meta) -> Result<usize, Error> {
+        let base = cover(self.meta.as_ref(), meta)?;
+        if let Some(own) = &self.meta {
+            let shift = slot(&base, own.start_at());
+            if shift > 0 {
+                for state in self.groups.values_mut() {
+                    state.shift(shift);
+                }
+            }
+        }
+        let offset = slot(&base, meta.start_at());
+        self.meta = Some(base);
+        Ok(offset)
+    }
+
+    pub(crate) fn absorb(&mut self, other: Groups) -> Result<(), Error> {
+        let offset = match &other.meta {
+            Some(meta) => self.align(meta)?,
+            None => 0,
+        };
+        for (key, mut state) in other.groups {
+            if offset > 0 {
+                state.shift(offset);
+            }
+            self.merge(key, state);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn prune(&mut self) {
+        for state in self.groups.values_mut() {
+            state.prune(self.op, &self.param);
+        }
+    }
+
+    // rows of a batch are grouped by label value ids first, so label values are
+    // only materialized once per group, and their slots are aligned by sample time
+    pub(crate) async fn update_records(
+        &mut self,
+        cx: &mut Context,
+        records: &Records,
+        by: &[usize],
+        field: usize,
+    ) -> Result<(), Error> {
+        let op = self.op;
+        let Some(meta) = &records.meta else {
+            if records.is_empty() {
+                return Ok(());
+            }
+            return Err(Error::Unaligned);
+        };
+        let offset = self.align(meta)?;
+        let mut batch = HashMap::<Vec<Option<usize>>, (usize, State)>::new();
+        for row in 0..records.len() {
+            let Some(values) = records.fields[field].get_f64(row) else {
+                continue;
+            };
+            let values = iter::repeat_n(None, offset)
+                .chain(values)
+                .collect::<Vec<_>>();
+            let ids = by
+                .iter()
+                .map(|label| records.labels[*label].value_id(row))
+                .collect();
+            let series = || records.labels.iter().map(|label| label.get(row)).collect();
+            batch
+                .entry(ids)
+                .or_insert_with(|| (row, State::new(op)))
+                .1
+                .update(op, series, &values);
+            try_yield!(cx);
+        }
+        for (row, state) in batch.into_values() {
+            let key = by
+                .iter()
+                .map(|label| records.labels[*label].get(row))
+                .collect();
+            self.merge(key, state);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Vec<Series> {
+        let (op, param) = (self.op, self.param);
+        self.groups
+            .into_iter()
+            .flat_map(|(key, state)| state.finish(op, &param, key))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct AggregatePlanner {
+    pub(crate) op: AggregateOp,
+    pub(crate) param: Param,
+    pub(crate) by: Vec<usize>,
+    pub(crate) labels: Vec<Label>,
+    pub(crate) field: usize,
+}
+
+impl Planner for AggregatePlanner {
+    type Execution = Aggregate;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, inner: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Aggregate {
+            planner: Some(self),
+            input: Input::Execution(inner),
+        })
+    }
+}
+
+impl AggregatePlanner {
+    // every worker aggregates the chunks of its own shard, so only partial
+    // groups cross threads instead of every matching series
+    #[allow(clippy::await_holding_refcell_ref)]
+    pub(crate) fn plan_partial(self, scan: ScanPlanner) -> Aggregate {
+        let partials = (0..executor::worker_num())
+            .map(|id| {
+                let scan = scan.clone();
+                let (op, param) = (self.op, self.param.clone());
+                let (by, field) = (self.by.clone(), self.field);
+                executor::spawn_to(id, move || async move {
+                    let resource = scan.resource.clone();
+                    let shards = resource.shards.get().borrow();
+                    let mut worker = scan.worker(&shards.mutable);
+                    let mut cx = Context::new(256);
+                    let mut groups = Groups::new(op, param);
+                    while let Some(records) = worker.next(&mut cx).await {
+                        groups
+                            .update_records(&mut cx, &records?, &by, field)
+                            .await?;
+                    }
+                    groups.prune();
+                    Ok::<_, DynError>(groups)
+                })
+            })
+            .collect();
+        Aggregate {
+            planner: Some(self),
+            input: Input::Partial(partials),
+        }
+    }
+
+    // the same at steps, every worker selects the samples of its own shard and
+    // aggregates them, unless a series turns out to be spread over shards
+    pub(crate) fn plan_selected(self, select: SelectPlanner) -> Aggregate {
+        let partials = (0..executor::worker_num())
+            .map(|id| {
+                let select = select.clone();
+                let (op, param) = (self.op, self.param.clone());
+                let (by, field) = (self.by.clone(), self.field);
+                executor::spawn_to(id, move || async move {
+                    let (records, hashes) = select.select_local().await?;
+                    let mut cx = Context::new(256);
+                    let mut groups = Groups::new(op, param);
+                    groups.update_records(&mut cx, &records, &by, field).await?;
+                    groups.prune();
+                    Ok::<_, DynError>((groups, hashes))
+                })
+            })
+            .collect();
+        Aggregate {
+            planner: Some(self),
+            input: Input::Selected { partials, select },
+        }
+    }
+}
+
+// partial groups with the hashes of the series a worker selected
+type Selected = Task<Result<(Groups, Vec<u64>), DynError>>;
+
+#[derive(Debug)]
+enum Input {
+    Execution(ExecutionImpl),
+    Partial(Vec<Task<Result<Groups, DynError>>>),
+    Selected {
+        partials: Vec<Selected>,
+        select: SelectPlanner,
+    },
+}
+
+#[derive(Debug)]
+pub struct Aggregate {
+    planner: Option<AggregatePlanner>,
+    input: Input,
+}
+
+impl Execution for Aggregate {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        let mut groups = Groups::new(planner.op, planner.param.clone());
+        match &mut self.input {
+            Input::Execution(input) => {
+                while let Some(records) = Box::pin(input.next(cx)).await {
+                    match records {
+                        Ok(records) => {
+                            if let Err(e) = groups
+                                .update_records(cx, &records, &planner.by, planner.field)
+                                .await
+                            {
+                                return Some(Err(e.into()));
+                            }
+                        }
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            }
+            Input::Partial(partials) => {
+                for partial in partials.drain(..) {
+                    let absorbed = partial
+                        .await
+                        .and_then(|partial| Ok(groups.absorb(partial)?));
+                    if let Err(e) = absorbed {
+                        return Some(Err(e));
+                    }
+                }
+            }
+            Input::Selected { partials, select } => {
+                let mut hashes = HashSet::new();
+                let mut spread = false;
+                for partial in partials.drain(..) {
+                    let absorbed = partial.await.and_then(|(partial, series)| {
+                        for hash in series {
+                            spread |= !hashes.insert(hash);
+                        }
+                        Ok(groups.absorb(partial)?)
+                    });
+                    if let Err(e) = absorbed {
+                        return Some(Err(e));
+                    }
+                }
+                // the latest sample of a series read by several workers is only
+                // known once all of them were read, so the steps are selected again
+                if spread {
+                    groups = Groups::new(planner.op, planner.param.clone());
+                    let selected = match select.clone().select().await {
+                        Ok(records) => groups
+                            .update_records(cx, &records, &planner.by, planner.field)
+                            .await
+                            .map_err(Into::into),
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = selected {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+        let meta = groups.meta.clone();
+        Some(Ok(records(&planner.labels, groups.finish(), meta)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chunk::mutable::MutableChunk;
+    use common::{
+        column::{
+            field::Field,
+            label::{Label, LabelValue},
+        },
+        context::Context,
+        time::{Duration, Instant},
+    };
+    use resource::db::tests::test_db;
+
+    use super::Input;
+    use crate::{
+        check::Checker,
+        evaluate::Steps,
+        execute::{
+            plan::{plan, Context as PlanContext},
+            Execution, ExecutionImpl,
+        },
+        parse::Parser,
+        Layer, Pass,
+    };
+
+    type Rows = Vec<(Vec<Option<LabelValue>>, Vec<Option<f64>>)>;
+
+    fn string(value: &str) -> Option<LabelValue> {
+        Some(Label::String(Vec::from(value)))
+    }
+
+    #[test]
+    fn aggregate() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+
+                let start_at = Instant::now() - Duration::from_secs(10);
+                let samples = [
+                    ("prod", "200", [Some(1.0), Some(2.0)]),
+                    ("prod", "500", [Some(3.0), None]),
+                    ("dev", "200", [Some(10.0), None]),
+                    ("dev", "500", [None, None]),
+                ];
+                // the same label values land in two chunks with their own value ids
+                for samples in samples.chunks(2) {
+                    let mut chunk =
+                        MutableChunk::new(&table.meta.schema, start_at, Duration::SECOND, 2, 2);
+                    for (row, (env, status, values)) in samples.iter().enumerate() {
+                        chunk
+                            .push(vec![string(env), string(status), None, None])
+                            .unwrap();
+                        for (step, value) in values.iter().enumerate() {
+                            chunk
+                                .set(
+                                    row,
+                                    0,
+                                    start_at + Duration::SECOND * step as u32,
+                                    value.map(Field::Float64),
+                                )
+                                .unwrap();
+                        }
+                    }
+                    table.shards.get().borrow_mut().mutable.push(chunk);
+                }
+
+                let parser = Checker::new(db.clone()).layer(Parser::new().layer(()));
+                let query = |query: &'static str| {
+                    let parser = &parser;
+                    async move {
+                        let expr = parser.apply(query).unwrap();
+                        let mut execution = plan(&mut PlanContext::default(), expr).unwrap();
+                        let mut cx = Context::new(256);
+                        let mut rows = Rows::new();
+                        while let Some(records) = execution.next(&mut cx).await {
+                            let records = records.unwrap();
+                            for row in 0..records.len() {
+                                rows.push((
+                                    records.labels.iter().map(|label| label.get(row)).collect(),
+                                    records.fields[0].get_f64(row).unwrap(),
+                                ));
+                            }
+                        }
+                        rows.sort_by(|a, b| a.0.cmp(&b.0));
+                        rows
+                    }
+                };
+
+                assert_eq!(
+                    query("sum(foo.bar.something_used)").await,
+                    vec![(vec![], vec![Some(14.0), Some(2.0)])]
+                );
+                assert_eq!(
+                    query("sum by (env) (foo.bar.something_used)").await,
+                    vec![
+                        (vec![string("dev")], vec![Some(10.0), None]),
+                        (vec![string("prod")], vec![Some(4.0), Some(2.0)]),
+                    ]
+                );
+                assert_eq!(
+                    query("count without (status, ip, shard) (foo.bar.something_used)").await,
+                    vec![
+                        (vec![string("dev")], vec![Some(1.0), None]),
+                        (vec![string("prod")], vec![Some(2.0), Some(1.0)]),
+                    ]
+                );
+                assert_eq!(
+                    query("avg by (status) (foo.bar.something_used)").await,
+                    vec![
+                        (vec![string("200")], vec![Some(5.5), Some(2.0)]),
+                        (vec![string("500")], vec![Some(3.0), None]),
+                    ]
+                );
+                assert_eq!(
+                    query("max by (env) (foo.bar.something_used)").await,
+                    vec![
+                        (vec![string("dev")], vec![Some(10.0), None]),
+                        (vec![string("prod")], vec![Some(3.0), Some(2.0)]),
+                    ]
+                );
+                assert_eq!(
+                    query("min(foo.bar.something_used) * 2").await,
+                    vec![(vec![], vec![Some(2.0), Some(4.0)])]
+                );
+                assert_eq!(
+                    query("topk(1, foo.bar.something_used)").await,
+                    vec![
+                        (
+                            vec![string("dev"), string("200"), None, None],
+                            vec![Some(10.0), None]
+                        ),
+                        (
+                            vec![string("prod"), string("200"), None, None],
+                            vec![None, Some(2.0)]
+                        ),
+                    ]
+                );
+                assert_eq!(
+                    query("bottomk by (env) (1, foo.bar.something_used)").await,
+                    vec![
+                        (
+                            vec![string("dev"), string("200"), None, None],
+                            vec![Some(10.0), None]
+                        ),
+                        (
+                            vec![string("prod"), string("200"), None, None],
+                            vec![Some(1.0), Some(2.0)]
+                        ),
+                    ]
+                );
+                assert_eq!(
+                    query("quantile(0.5, foo.bar.something_used)").await,
+                    vec![(vec![], vec![Some(3.0), Some(2.0)])]
+                );
+                assert_eq!(
+                    query("stddev by (env) (foo.bar.something_used)").await,
+                    vec![
+                        (vec![string("dev")], vec![Some(0.0), None]),
+                        (vec![string("prod")], vec![Some(1.0), Some(0.0)]),
+                    ]
+                );
+                assert_eq!(
+                    query(r#"count_values("value", foo.bar.something_used)"#).await,
+                    vec![
+                        (vec![string("1")], vec![Some(1.0), None]),
+                        (vec![string("10")], vec![Some(1.0), None]),
+                        (vec![string("2")], vec![None, Some(1.0)]),
+                        (vec![string("3")], vec![Some(1.0), None]),
+                    ]
+                );
+                assert_eq!(
+                    query(r#"count_values("value", foo.bar.something_used / 0)"#).await,
+                    vec![(vec![string("+Inf")], vec![Some(3.0), Some(1.0)])]
+                );
+                assert_eq!(
+                    query(r#"count_values("value", -foo.bar.something_used / 0)"#).await,
+                    vec![(vec![string("-Inf")], vec![Some(3.0), Some(1.0)])]
+                );
+                assert_eq!(
+                    query(r#"count_values("value", foo.bar.something_used * NaN)"#).await,
+                    vec![(vec![string("NaN")], vec![Some(3.0), Some(1.0)])]
+                );
+            });
+    }
+
+    #[test]
+    fn partial_aggregate() {
+        executor::ExecutorBuilder::new()
+            .worker_num(2)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+
+                let start_at = Instant::now() - Duration::from_secs(10);
+                for id in 0..executor::worker_num() {
+                    let table = table.clone();
+                    executor::spawn_to(id, move || async move {
+                        let mut chunk =
+                            MutableChunk::new(&table.meta.schema, start_at, Duration::SECOND, 2, 1);
+                        for (row, env) in ["prod", "dev"].into_iter().enumerate() {
+                            chunk
+                                .push(vec![string(env), string(&id.to_string()), None, None])
+                                .unwrap();
+                            chunk
+                                .set(
+                                    row,
+                                    0,
+                                    start_at,
+                                    Some(Field::Float64((row + 1) as f64 * (id + 1) as f64)),
+                                )
+                                .unwrap();
+                        }
+                        table.shards.get().borrow_mut().mutable.push(chunk);
+                    })
+                    .await;
+                }
+
+                let expr = Checker::new(db.clone())
+                    .layer(Parser::new().layer(()))
+                    .apply("sum by (env) (foo.bar.something_used)")
+                    .unwrap();
+                let mut execution = plan(&mut PlanContext::default(), expr).unwrap();
+                let mut cx = Context::new(256);
+                let records = execution.next(&mut cx).await.unwrap().unwrap();
+                assert!(execution.next(&mut cx).await.is_none());
+
+                let mut rows = (0..records.len())
+                    .map(|row| {
+                        (
+                            records.labels[0].get(row),
+                            records.fields[0].get_f64(row).unwrap(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                rows.sort_by(|a, b| a.0.cmp(&b.0));
+                assert_eq!(
+                    rows,
+                    vec![
+                        (string("dev"), vec![Some(6.0)]),
+                        (string("prod"), vec![Some(3.0)])
+                    ]
+                );
+
+                let expr = Checker::new(db.clone())
+                    .layer(Parser::new().layer(()))
+                    .apply("topk(1, foo.bar.something_used)")
+                    .unwrap();
+                let mut execution = plan(&mut PlanContext::default(), expr).unwrap();
+                let records = execution.next(&mut cx).await.unwrap().unwrap();
+                assert_eq!(records.len(), 1);
+                assert_eq!(
+                    (records.labels[1].get(0), records.fields[0].get_f64(0)),
+                    (string("1"), Some(vec![Some(4.0)]))
+                );
+            });
+    }
+
+    #[test]
+    fn stepped_partial_aggregate() {
+        executor::ExecutorBuilder::new()
+            .worker_num(2)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+
+                let start_at = Instant::now() - Duration::from_secs(10);
+                let load =
+                    |id: usize,
+                     status: &'static str,
+                     rows: Vec<(&'static str, Vec<Option<f64>>)>| {
+                        let table = table.clone();
+                        executor::spawn_to(id, move || async move {
+                            let mut chunk = MutableChunk::new(
+                                &table.meta.schema,
+                                start_at,
+                                Duration::SECOND,
+                                2,
+                                3,
+                            );
+                            for (row, (env, values)) in rows.into_iter().enumerate() {
+                                chunk
+                                    .push(vec![string(env), string(status), None, None])
+                                    .unwrap();
+                                for (slot, value) in values.into_iter().enumerate() {
+                                    chunk
+                                        .set(
+                                            row,
+                                            0,
+                                            start_at + Duration::SECOND * slot as u32,
+                                            value.map(Field::Float64),
+                                        )
+                                        .unwrap();
+                                }
+                            }
+                            table.shards.get().borrow_mut().mutable.push(chunk);
+                        })
+                    };
+                for id in 0..executor::worker_num() {
+                    let scale = (id + 1) as f64;
+                    load(
+                        id,
+                        ["0", "1"][id],
+                        vec![
+                            (
+                                "prod",
+                                vec![Some(scale), Some(2.0 * scale), Some(3.0 * scale)],
+                            ),
+                            ("dev", vec![Some(10.0 * scale), None, Some(30.0 * scale)]),
+                        ],
+                    )
+                    .await;
+                }
+
+                let parser = Checker::new(db.clone()).layer(Parser::new().layer(()));
+                let steps = Steps::new(
+                    start_at,
+                    start_at + Duration::from_secs(2),
+                    Duration::SECOND,
+                )
+                .unwrap();
+                let query = |literal: &'static str| {
+                    let parser = &parser;
+                    async move {
+                        let expr = parser.apply(literal).unwrap();
+                        let mut execution =
+                            plan(&mut PlanContext::with_steps(steps), expr).unwrap();
+                        let ExecutionImpl::Aggregate(aggregate) = &execution else {
+                            unreachable!()
+                        };
+                        assert!(matches!(aggregate.input, Input::Selected { .. }));
+
+                        let mut cx = Context::new(256);
+                        let records = execution.next(&mut cx).await.unwrap().unwrap();
+                        assert!(execution.next(&mut cx).await.is_none());
+                        let mut rows = (0..records.len())
+                            .map(|row| {
+                                (
+                                    records.labels[0].get(row),
+                                    records.fields[0].get_f64(row).unwrap(),
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        rows.sort_by(|a, b| a.0.cmp(&b.0));
+                        rows
+                    }
+                };
+
+                // the missing dev sample is looked back to
+                assert_eq!(
+                    query("sum by (env) (foo.bar.something_used)").await,
+                    vec![
+                        (string("dev"), vec![Some(30.0), Some(30.0), Some(90.0)]),
+                        (string("prod"), vec![Some(3.0), Some(6.0), Some(9.0)])
+                    ]
+                );
+
+                // both workers hold the test series, its newer sample hides the older one
+                load(0, "2", vec![("test", vec![Some(1.0), None, None])]).await;
+                load(1, "2", vec![("test", vec![None, Some(5.0), None])]).await;
+                let rows = query("sum by (env) (foo.bar.something_used)").await;
+                assert_eq!(rows.len(), 3);
+                assert_eq!(
+                    rows[2],
+                    (string("test"), vec![Some(1.0), Some(5.0), Some(5.0)])
+                );
+            });
+    }
+
+    #[test]
+    fn aggregate_across_chunks() {
+        executor::ExecutorBuilder::new()
+            .worker_num(2)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+
+                // the series goes on in the chunk of the second worker, two slots later
+                let start_at = Instant::now() - Duration::from_secs(10);
+                for (id, values) in [[1.0, 2.0], [10.0, 20.0]].into_iter().enumerate() {
+                    let table = table.clone();
+                    executor::spawn_to(id, move || async move {
+                        let chunk_start = start_at + Duration::SECOND * (id * 2) as u32;
+                        let mut chunk = MutableChunk::new(
+                            &table.meta.schema,
+                            chunk_start,
+                            Duration::SECOND,
+                            1,
+                            2,
+                        );
+                        chunk
+                            .push(vec![string("prod"), string("200"), None, None])
+                            .unwrap();
+                        for (slot, value) in values.into_iter().enumerate() {
+                            chunk
+                                .set(
+                                    0,
+                                    0,
+                                    chunk_start + Duration::SECOND * slot as u32,
+                                    Some(Field::Float64(value)),
+                                )
+                                .unwrap();
+                        }
+                        table.shards.get().borrow_mut().mutable.push(chunk);
+                    })
+                    .await;
+                }
+
+                let parser = Checker::new(db.clone()).layer(Parser::new().layer(()));
+                let mut cx = Context::new(256);
+                // partial groups of every worker, then groups over a computed input
+                for query in [
+                    "sum by (env) (foo.bar.something_used)",
+                    "sum by (env) (foo.bar.something_used * 1)",
+                    "topk(1, foo.bar.something_used)",
+                ] {
+                    let expr = parser.apply(query).unwrap();
+                    let mut execution = plan(&mut PlanContext::default(), expr).unwrap();
+                    let records = execution.next(&mut cx).await.unwrap().unwrap();
+                    assert_eq!(records.len(), 1, "{query}");
+                    assert_eq!(records.timestamp(0), Some(start_at), "{query}");
+                    assert_eq!(
+                        records.fields[0].get_f64(0),
+                        Some(vec![Some(1.0), Some(2.0), Some(10.0), Some(20.0)]),
+                        "{query}"
+                    );
+                }
+            });
+    }
+}
@@ -0,0 +1,831 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    convert::Infallible,
+    iter,
+};
+
+use chunk::mutable::{
+    column::{field::Float64Field, label::LabelImpl},
+    Meta, Records,
+};
+use common::{
+    array::Array,
+    column::{field::Field, label::LabelValue},
+    context::Context,
+    scalar::list::OptionalFixedList,
+    schema::Label,
+    time::Instant,
+    DynError,
+};
+use thiserror::Error;
+
+use super::{Execution, ExecutionImpl, Planner};
+use crate::plan::logical::{BinaryOp, GroupSide, MatchingAction, VectorMatching};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("many-to-many matching not allowed: matching labels must be unique on one side")]
+    ManyToMany,
+    #[error(
+        "multiple matches for labels: many-to-one matching must be explicit \
+         (group_left/group_right)"
+    )]
+    ManyToOne,
+    #[error("multiple matches for labels: grouping labels must ensure unique matches")]
+    DuplicateSeries,
+    #[error("samples without their time can not be aligned")]
+    Unaligned,
+    #[error("samples of different resolutions can not be aligned")]
+    Resolution,
+}
+
+// slots covering both, at the resolution they share
+pub(crate) fn cover(base: Option<&Meta>, meta: &Meta) -> Result<Meta, Error> {
+    let Some(base) = base else {
+        return Ok(meta.clone());
+    };
+    if base.unit() != meta.unit() {
+        return Err(Error::Resolution);
+    }
+    let start_at = base.start_at().min(meta.start_at());
+    let end_at = base.end_at().max(meta.end_at());
+    let (span, unit) = ((end_at - start_at).as_millis(), base.unit().as_millis());
+    Ok(Meta::new(
+        start_at,
+        base.unit(),
+        0,
+        ((span + unit - 1) / unit) as u32,
+    ))
+}
+
+// the slot of base that holds a sample taken at `at`
+#[inline]
+pub(crate) fn slot(base: &Meta, at: Instant) -> usize {
+    ((at - base.start_at()) / base.unit()) as usize
+}
+
+#[inline]
+fn compare(op: BinaryOp, lhs: f64, rhs: f64) -> bool {
+    match op {
+        BinaryOp::Eq => lhs == rhs,
+        BinaryOp::Ne => lhs != rhs,
+        BinaryOp::Lt => lhs < rhs,
+        BinaryOp::Gt => lhs > rhs,
+        BinaryOp::Le => lhs <= rhs,
+        BinaryOp::Ge => lhs >= rhs,
+        _ => unreachable!(),
+    }
+}
+
+// comparisons give 1 or 0, as with the bool modifier
+pub fn arithmetic(op: BinaryOp, lhs: f64, rhs: f64) -> f64 {
+    match op {
+        BinaryOp::Pow => lhs.powf(rhs),
+        BinaryOp::Mul => lhs * rhs,
+        BinaryOp::Div => lhs / rhs,
+        BinaryOp::Mod => lhs % rhs,
+        BinaryOp::Add => lhs + rhs,
+        BinaryOp::Sub => lhs - rhs,
+        BinaryOp::And | BinaryOp::Unless | BinaryOp::Or => unreachable!(),
+        op => compare(op, lhs, rhs) as u8 as f64,
+    }
+}
+
+// `keep` is the sample a filtering comparison lets through
+#[inline]
+fn sample(op: BinaryOp, return_bool: bool, lhs: f64, rhs: f64, keep: f64) -> Option<f64> {
+    if op.is_comparison() && !return_bool {
+        compare(op, lhs, rhs).then_some(keep)
+    } else {
+        Some(arithmetic(op, lhs, rhs))
+    }
+}
+
+#[derive(Debug)]
+pub enum Operand {
+    Scalar(Vec<f64>),
+    Vector {
+        input: ExecutionImpl,
+        labels: Vec<Label>,
+        field: usize,
+    },
+}
+
+#[derive(Debug)]
+pub struct BinaryPlanner {
+    pub(crate) op: BinaryOp,
+    pub(crate) return_bool: bool,
+    pub(crate) matching: Option<VectorMatching>,
+    pub(crate) lhs: Operand,
+    pub(crate) rhs: Operand,
+    pub(crate) labels: Vec<Label>,
+}
+
+impl Planner for BinaryPlanner {
+    type Execution = Binary;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, _: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Binary {
+            planner: Some(self),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Binary {
+    planner: Option<BinaryPlanner>,
+}
+
+impl Execution for Binary {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        Some(planner.evaluate(cx).await)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Series {
+    pub(crate) labels: Vec<Option<LabelValue>>,
+    pub(crate) values: Vec<Option<f64>>,
+}
+
+impl Series {
+    #[inline]
+    fn get(&self, labels: &[Label], name: &str) -> Option<LabelValue> {
+        labels
+            .iter()
+            .position(|label| label.name == name)
+            .and_then(|position| self.labels[position].clone())
+    }
+
+    #[inline]
+    fn signature(&self, labels: &[Label], names: &[String]) -> Vec<Option<LabelValue>> {
+        names.iter().map(|name| self.get(labels, name)).collect()
+    }
+}
+
+pub(crate) struct Side {
+    pub(crate) series: Vec<Series>,
+    pub(crate) labels: Vec<Label>,
+    // where the slots of every series are, None when nothing was collected
+    pub(crate) meta: Option<Meta>,
+}
+
+impl Side {
+    #[inline]
+    fn empty() -> Self {
+        Self {
+            series: vec![],
+            labels: vec![],
+            meta: None,
+        }
+    }
+
+    // rows of a series spread over chunks become one series, with the samples
+    // of every chunk put on the slot of their time
+    pub(crate) async fn collect(
+        cx: &mut Context,
+        mut input: ExecutionImpl,
+        labels: Vec<Label>,
+        field: usize,
+    ) -> Result<Self, DynError> {
+        let mut meta = None;
+        let mut samples = BTreeMap::<_, Vec<_>>::new();
+        while let Some(records) = Box::pin(input.next(cx)).await {
+            let records = records?;
+            // nothing to align when nothing was computed
+            let Some(batch) = &records.meta else {
+                if records.is_empty() {
+                    continue;
+                }
+                return Err(Error::Unaligned.into());
+            };
+            meta = Some(cover(meta.as_ref(), batch)?);
+            for row in 0..records.len() {
+                let values = records.fields[field].get_f64(row).unwrap_or_default();
+                samples
+                    .entry(records.labels.iter().map(|label| label.get(row)).collect())
+                    .or_default()
+                    .extend(
+                        values
+                            .into_iter()
+                            .enumerate()
+                            .filter_map(|(slot, value)| Some((batch.timestamp(slot), value?))),
+                    );
+            }
+        }
+
+        let series = match &meta {
+            Some(meta) => samples
+                .into_iter()
+                .map(|(labels, samples)| {
+                    let mut values = vec![None; meta.width()];
+                    for (at, value) in samples {
+                        values[slot(meta, at)] = Some(value);
+                    }
+                    Series { labels, values }
+                })
+                .collect(),
+            None => vec![],
+        };
+        Ok(Self {
+            series,
+            labels,
+            meta,
+        })
+    }
+
+    // moves every series onto the slots of meta, which cover those of the side
+    pub(crate) fn rebase(&mut self, meta: &Meta) {
+        if let Some(own) = self.meta.replace(meta.clone()) {
+            let offset = slot(meta, own.start_at());
+            for series in self.series.iter_mut() {
+                series.values.splice(0..0, iter::repeat_n(None, offset));
+                series.values.resize(meta.width(), None);
+            }
+        }
+    }
+
+    fn project(&self, series: &Series, labels: &[Label]) -> Vec<Option<LabelValue>> {
+        labels
+            .iter()
+            .map(|label| series.get(&self.labels, &label.name))
+            .collect()
+    }
+
+    // slots where any series of a signature has a sample
+    fn presence(&self, names: &[String]) -> HashMap<Vec<Option<LabelValue>>, Vec<bool>> {
+        let mut presence = HashMap::<_, Vec<bool>>::new();
+        for series in &self.series {
+            let slots = presence
+                .entry(series.signature(&self.labels, names))
+                .or_default();
+            if slots.len() < series.values.len() {
+                slots.resize(series.values.len(), false);
+            }
+            for (slot, value) in slots.iter_mut().zip(&series.values) {
+                *slot |= value.is_some();
+            }
+        }
+        presence
+    }
+}
+
+// a scalar holds one value per slot, or a single value for every slot
+pub(crate) enum Value {
+    Scalar(Vec<f64>),
+    Vector(Side),
+}
+
+#[inline]
+pub(crate) fn scalar_at(values: &[f64], slot: usize) -> f64 {
+    match values {
+        [value] => *value,
+        values => values[slot],
+    }
+}
+
+// comparisons between scalars always carry the bool modifier
+pub(crate) fn scalars(op: BinaryOp, return_bool: bool, lhs: &[f64], rhs: &[f64]) -> Vec<f64> {
+    (0..lhs.len().max(rhs.len()))
+        .map(|slot| {
+            let (lhs, rhs) = (scalar_at(lhs, slot), scalar_at(rhs, slot));
+            sample(op, return_bool, lhs, rhs, lhs).unwrap_or(f64::NAN)
+        })
+        .collect()
+}
+
+impl Value {
+    #[inline]
+    fn meta(&self) -> Option<&Meta> {
+        match self {
+            Value::Scalar(_) => None,
+            Value::Vector(side) => side.meta.as_ref(),
+        }
+    }
+
+    #[inline]
+    fn rebase(&mut self, meta: &Meta) {
+        if let Value::Vector(side) = self {
+            side.rebase(meta);
+        }
+    }
+}
+
+impl Operand {
+    async fn collect(self, cx: &mut Context) -> Result<Value, DynError> {
+        match self {
+            Operand::Scalar(values) => Ok(Value::Scalar(values)),
+            Operand::Vector {
+                input,
+                labels,
+                field,
+            } => Ok(Value::Vector(
+                Side::collect(cx, input, labels, field).await?,
+            )),
+        }
+    }
+}
+
+impl BinaryPlanner {
+    async fn evaluate(self, cx: &mut Context) -> Result<Records, DynError> {
+        let mut lhs = self.lhs.collect(cx).await?;
+        let mut rhs = self.rhs.collect(cx).await?;
+        // both sides are matched slot by slot, so their slots have to mean the same time
+        let mut meta = None;
+        for value in [&lhs, &rhs] {
+            if let Some(other) = value.meta() {
+                meta = Some(cover(meta.as_ref(), other)?);
+            }
+        }
+        if let Some(meta) = &meta {
+            lhs.rebase(meta);
+            rhs.rebase(meta);
+        }
+        let rows = apply(
+            self.op,
+            self.return_bool,
+            self.matching.as_ref(),
+            lhs,
+            rhs,
+            &self.labels,
+        )?;
+        Ok(records(&self.labels, rows, meta))
+    }
+}
+
+// values of every series are matched slot by slot
+pub(crate) fn apply(
+    op: BinaryOp,
+    return_bool: bool,
+    matching: Option<&VectorMatching>,
+    lhs: Value,
+    rhs: Value,
+    labels: &[Label],
+) -> Result<Vec<Series>, Error> {
+    match (lhs, rhs) {
+        (Value::Vector(lhs), Value::Scalar(rhs)) => Ok(lhs
+            .series
+            .into_iter()
+            .map(|series| Series {
+                values: series
+                    .values
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, lhs)| {
+                        sample(op, return_bool, (*lhs)?, scalar_at(&rhs, slot), (*lhs)?)
+                    })
+                    .collect(),
+                labels: series.labels,
+            })
+            .collect()),
+        (Value::Scalar(lhs), Value::Vector(rhs)) => Ok(rhs
+            .series
+            .into_iter()
+            .map(|series| Series {
+                values: series
+                    .values
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, rhs)| {
+                        sample(op, return_bool, scalar_at(&lhs, slot), (*rhs)?, (*rhs)?)
+                    })
+                    .collect(),
+                labels: series.labels,
+            })
+            .collect()),
+        (Value::Vector(lhs), Value::Vector(rhs)) => {
+            let names = signature_names(matching, &lhs.labels, &rhs.labels);
+            if op.is_set() {
+                Ok(set_operation(op, &names, &lhs, &rhs, labels))
+            } else {
+                vector_operation(op, return_bool, matching, &names, &lhs, &rhs, labels)
+            }
+        }
+        (Value::Scalar(_), Value::Scalar(_)) => unreachable!(),
+    }
+}
+
+fn signature_names(matching: Option<&VectorMatching>, lhs: &[Label], rhs: &[Label]) -> Vec<String> {
+    match matching {
+        Some(VectorMatching {
+            action: MatchingAction::On,
+            labels,
+            ..
+        }) => labels.clone(),
+        _ => {
+            let ignoring = matching.map(|matching| &matching.labels[..]).unwrap_or(&[]);
+            let mut names = lhs
+                .iter()
+                .chain(rhs)
+                .map(|label| label.name.clone())
+                .filter(|name| !ignoring.contains(name))
+                .collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            names
+        }
+    }
+}
+
+fn set_operation(
+    op: BinaryOp,
+    names: &[String],
+    lhs: &Side,
+    rhs: &Side,
+    labels: &[Label],
+) -> Vec<Series> {
+    let filter = |side: &Side, other: &Side, keep: bool| {
+        let presence = other.presence(names);
+        side.series
+            .iter()
+            .map(|series| {
+                let slots = presence.get(&series.signature(&side.labels, names));
+                Series {
+                    labels: side.project(series, labels),
+                    values: series
+                        .values
+                        .iter()
+                        .enumerate()
+                        .map(|(slot, value)| {
+                            let present = slots
+                                .and_then(|slots| slots.get(slot))
+                                .copied()
+                                .unwrap_or(false);
+                            value.filter(|_| present == keep)
+                        })
+                        .collect(),
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    match op {
+        BinaryOp::And => filter(lhs, rhs, true),
+        BinaryOp::Unless => filter(lhs, rhs, false),
+        BinaryOp::Or => {
+            let mut series = filter(lhs, &Side::empty(), false);
+            series.extend(filter(rhs, lhs, false));
+            series
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn vector_operation(
+    op: BinaryOp,
+    return_bool: bool,
+    matching: Option<&VectorMatching>,
+    names: &[String],
+    lhs: &Side,
+    rhs: &Side,
+    labels: &[Label],
+) -> Result<Vec<Series>, Error> {
+    let group = matching.and_then(|matching| matching.group.as_ref());
+    let (many, one) = match group.map(|group| &group.side) {
+        Some(GroupSide::Right) => (rhs, lhs),
+        _ => (lhs, rhs),
+    };
+    let include = group.map(|group| &group.labels[..]).unwrap_or(&[]);
+
+    // series only clash when they hold samples at the same slot, a range query
+    // may see a matching label set handed over from one series to another
+    let mut ones = HashMap::new();
+    for series in &one.series {
+        let signature = series.signature(&one.labels, names);
+        for (slot, value) in series.values.iter().enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            if let Entry::Vacant(entry) = ones.entry((signature.clone(), slot)) {
+                entry.insert((series, *value));
+            } else if group.is_some() {
+                return Err(Error::ManyToMany);
+            } else {
+                return Err(Error::ManyToOne);
+            }
+        }
+    }
+
+    let mut matched = HashSet::new();
+    let mut outputs = HashMap::new();
+    let mut inserted = HashSet::new();
+    let mut rows = Vec::<Series>::new();
+    for series in &many.series {
+        let signature = series.signature(&many.labels, names);
+        for (slot, value) in series.values.iter().enumerate() {
+            let Some(value) = value else {
+                continue;
+            };
+            let Some((other, other_value)) = ones.get(&(signature.clone(), slot)) else {
+                continue;
+            };
+            if group.is_none() && !matched.insert((signature.clone(), slot)) {
+                return Err(Error::ManyToOne);
+            }
+
+            let output = labels
+                .iter()
+                .map(|label| {
+                    if include.contains(&label.name) {
+                        other.get(&one.labels, &label.name)
+                    } else {
+                        series.get(&many.labels, &label.name)
+                    }
+                })
+                .collect::<Vec<_>>();
+            if !inserted.insert((output.clone(), slot)) {
+                return Err(Error::DuplicateSeries);
+            }
+            let row = match outputs.entry(output) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    rows.push(Series {
+                        labels: entry.key().clone(),
+                        values: vec![None; series.values.len().max(other.values.len())],
+                    });
+                    *entry.insert(rows.len() - 1)
+                }
+            };
+            let (lhs, rhs) = match group.map(|group| &group.side) {
+                Some(GroupSide::Right) => (*other_value, *value),
+                _ => (*value, *other_value),
+            };
+            rows[row].values[slot] = sample(op, return_bool, lhs, rhs, lhs);
+        }
+    }
+    Ok(rows)
+}
+
+pub(crate) fn records(labels: &[Label], rows: Vec<Series>, meta: Option<Meta>) -> Records {
+    let rows = rows
+        .into_iter()
+        .filter(|series| series.values.iter().any(Option::is_some))
+        .collect::<Vec<_>>();
+    let width = match &meta {
+        Some(meta) => meta.width(),
+        None => rows
+            .iter()
+            .map(|series| series.values.len())
+            .max()
+            .unwrap_or(0),
+    };
+
+    let mut columns = labels
+        .iter()
+        .map(|label| LabelImpl::new(&label.r#type))
+        .collect::<Vec<_>>();
+    let length = rows.len();
+    let mut field = Float64Field::with_capacity(length, width as u32);
+    for mut series in rows {
+        for (column, value) in columns.iter_mut().zip(series.labels) {
+            column.push(value);
+        }
+        series.values.resize(width, None);
+        field.push(OptionalFixedList::from(series.values));
+    }
+    Records {
+        labels: columns,
+        fields: vec![Field::Float64(field).into()],
+        meta: meta.map(|meta| Meta::new(meta.start_at(), meta.unit(), length as u32, width as u32)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chunk::mutable::MutableChunk;
+    use common::{
+        column::{
+            field::Field,
+            label::{Label, LabelValue},
+        },
+        context::Context,
+        time::{Duration, Instant},
+    };
+    use resource::db::tests::test_db;
+
+    use crate::{
+        check::Checker,
+        execute::{
+            plan::{plan, Context as PlanContext},
+            Execution,
+        },
+        parse::Parser,
+        Layer, Pass,
+    };
+
+    type Rows = Vec<(Vec<Option<LabelValue>>, Vec<Option<f64>>)>;
+
+    fn string(value: &str) -> Option<LabelValue> {
+        Some(Label::String(Vec::from(value)))
+    }
+
+    #[test]
+    fn binary_operation() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+
+                let start_at = Instant::now() - Duration::from_secs(10);
+                let mut chunk =
+                    MutableChunk::new(&table.meta.schema, start_at, Duration::SECOND, 1, 2);
+                let samples = [
+                    ("prod", "200", [Some(1.0), Some(2.0)]),
+                    ("prod", "500", [Some(3.0), None]),
+                    ("dev", "200", [Some(10.0), Some(20.0)]),
+                    ("dev", "500", [Some(30.0), Some(40.0)]),
+                ];
+                for (row, (env, status, values)) in samples.into_iter().enumerate() {
+                    chunk.push(vec![string(env), string(status), None, None]).unwrap();
+                    for (step, value) in values.into_iter().enumerate() {
+                        chunk.set(
+                            row,
+                            0,
+                            start_at + Duration::SECOND * step as u32,
+                            value.map(Field::Float64),
+                        ).unwrap();
+                    }
+                }
+                table.shards.get().borrow_mut().mutable.push(chunk);
+
+                let parser = Checker::new(db.clone()).layer(Parser::new().layer(()));
+                let query = |query: &'static str| {
+                    let parser = &parser;
+                    async move {
+                        let expr = parser.apply(query).unwrap();
+                        let mut execution = plan(&mut PlanContext::default(), expr).unwrap();
+                        let mut cx = Context::new(256);
+                        let mut rows = Rows::new();
+                        while let Some(records) = execution.next(&mut cx).await {
+                            let records = records?;
+                            for row in 0..records.len() {
+                                rows.push((
+                                    records.labels.iter().map(|label| label.get(row)).collect(),
+                                    records.fields[0].get_f64(row).unwrap(),
+                                ));
+                            }
+                        }
+                        rows.sort_by(|a, b| a.0.cmp(&b.0));
+                        Ok::<_, common::DynError>(rows)
+                    }
+                };
+
+                assert_eq!(
+                    query(r#"foo.bar.something_used{env="prod"} * 10"#)
+                        .await
+                        .unwrap(),
+                    vec![
+                        (
+                            vec![string("prod"), string("200"), None, None],
+                            vec![Some(10.0), Some(20.0)]
+                        ),
+                        (
+                            vec![string("prod"), string("500"), None, None],
+                            vec![Some(30.0), None]
+                        ),
+                    ]
+                );
+                assert_eq!(
+                    query(
+                        r#"foo.bar.something_used{env="prod"} + on (status) foo.bar.something_used{env="dev"}"#
+                    )
+                    .await
+                    .unwrap(),
+                    vec![
+                        (vec![string("200")], vec![Some(11.0), Some(22.0)]),
+                        (vec![string("500")], vec![Some(33.0), None]),
+                    ]
+                );
+                assert_eq!(
+                    query(r#"foo.bar.something_used{env="dev"} > 15"#)
+                        .await
+                        .unwrap()
+                        .into_iter()
+                        .map(|(_, values)| values)
+                        .collect::<Vec<_>>(),
+                    vec![vec![None, Some(20.0)], vec![Some(30.0), Some(40.0)]]
+                );
+                assert_eq!(
+                    query(r#"15 < bool foo.bar.something_used{env="dev"}"#)
+                        .await
+                        .unwrap()
+                        .into_iter()
+                        .map(|(_, values)| values)
+                        .collect::<Vec<_>>(),
+                    vec![vec![Some(0.0), Some(1.0)], vec![Some(1.0), Some(1.0)]]
+                );
+                assert_eq!(
+                    query(
+                        r#"foo.bar.something_used{env="prod"} and on (status) foo.bar.something_used{env="dev"} > 25"#
+                    )
+                    .await
+                    .unwrap(),
+                    vec![(
+                        vec![string("prod"), string("500"), None, None],
+                        vec![Some(3.0), None]
+                    )]
+                );
+                assert_eq!(
+                    query(
+                        r#"foo.bar.something_used{env="prod"} or foo.bar.something_used{status="500"}"#
+                    )
+                    .await
+                    .unwrap()
+                    .len(),
+                    3
+                );
+                assert!(query(
+                    r#"foo.bar.something_used{env="prod"} + foo.bar.something_used{env="dev"}"#
+                )
+                .await
+                .unwrap()
+                .is_empty());
+                assert!(query(
+                    r#"foo.bar.something_used{env="prod"} + ignoring (env) foo.bar.something_used"#
+                )
+                .await
+                .is_err());
+                assert_eq!(
+                    query(
+                        r#"foo.bar.something_used + ignoring (env) group_left foo.bar.something_used{env="dev"}"#
+                    )
+                    .await
+                    .unwrap()
+                    .len(),
+                    4
+                );
+            });
+    }
+
+    #[test]
+    fn binary_across_chunks() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+
+                // both series continue in chunks that start later than the first one
+                let start_at = Instant::now() - Duration::from_secs(10);
+                for (offset, width, samples) in [
+                    (0, 2, vec![("prod", [Some(1.0), Some(2.0)]), ("dev", [Some(10.0), Some(20.0)])]),
+                    (2, 2, vec![("prod", [Some(3.0), Some(4.0)])]),
+                    (1, 3, vec![("dev", [None, Some(30.0)])]),
+                ] {
+                    let chunk_start = start_at + Duration::SECOND * offset as u32;
+                    let mut chunk = MutableChunk::new(
+                        &table.meta.schema,
+                        chunk_start,
+                        Duration::SECOND,
+                        samples.len() as u32,
+                        width,
+                    );
+                    for (row, (env, values)) in samples.into_iter().enumerate() {
+                        chunk.push(vec![string(env), string("200"), None, None]).unwrap();
+                        for (slot, value) in values.into_iter().enumerate() {
+                            chunk.set(
+                                row,
+                                0,
+                                chunk_start + Duration::SECOND * slot as u32,
+                                value.map(Field::Float64),
+                            ).unwrap();
+                        }
+                    }
+                    table.shards.get().borrow_mut().mutable.push(chunk);
+                }
+
+                let expr = Checker::new(db)
+                    .layer(Parser::new().layer(()))
+                    .apply(
+                        r#"foo.bar.something_used{env="dev"} - on (status) foo.bar.something_used{env="prod"}"#,
+                    )
+                    .unwrap();
+                let mut execution = plan(&mut PlanContext::default(), expr).unwrap();
+                let records = execution.next(&mut Context::new(256)).await.unwrap().unwrap();
+                assert_eq!(records.len(), 1);
+                assert_eq!(records.timestamp(0), Some(start_at));
+                assert_eq!(
+                    records.fields[0].get_f64(0),
+                    Some(vec![Some(9.0), Some(18.0), Some(27.0), None])
+                );
+            });
+    }
+}
@@ -1,80 +1,1272 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::Infallible,
+    ops::Range,
+};
+
 use anyhow::anyhow;
-use chunk::mutable::column::field::FieldItemImpl;
+use chunk::mutable::{
+    column::field::{FieldItemImpl, Float64Field},
+    Meta, Records,
+};
 use common::{
-    scalar::{list::OptionalFixedList, Scalar},
+    array::Array,
+    column::{
+        field::Field,
+        label::{LabelType, LabelValue},
+    },
+    context::Context,
+    primitive::Primitive,
+    scalar::{
+        list::{OptionalFixedList, OptionalFixedSlice},
+        Scalar,
+    },
+    schema::Label,
+    time::{Duration, Instant},
     DynError,
 };
 use paste::paste;
+use regex::Regex;
+
+use super::{
+    aggregate::quantile,
+    binary::{self, records, Series, Side},
+    Execution, ExecutionImpl, Planner,
+};
+use crate::evaluate::is_stale;
+
+pub type Sample = (Instant, f64);
+
+pub type RangeFunction = fn(&[Sample], &Window) -> Option<f64>;
+
+#[derive(Debug, Clone)]
+pub enum Function {
+    Range(RangeFunction),
+    OverTime(OverTime),
+    Trend(Trend),
+    // le is the position of the bucket bound in the input labels
+    HistogramQuantile { quantile: f64, le: usize },
+    Relabel(Relabel),
+    Math(Math),
+    // evaluation time in seconds, a scalar
+    Time,
+    // sample time in seconds of a selector, evaluation time of anything else
+    Timestamp,
+    // label values of the series reported when the input has none
+    Absent(Vec<Option<LabelValue>>),
+}
+
+// the values of the gathered samples of a series, built once and sliced for
+// every window by the functions that only look at values
+pub(crate) fn buffer(samples: &[Sample]) -> OptionalFixedList<f64> {
+    OptionalFixedList::from(
+        samples
+            .iter()
+            .map(|(_, value)| Some(*value))
+            .collect::<Vec<_>>(),
+    )
+}
+
+impl Function {
+    // the value of a range function over the samples of a series inside the window,
+    // which are samples[range] and the same range of the buffer of their values
+    pub(crate) fn fold(
+        &self,
+        samples: &[Sample],
+        buffer: OptionalFixedSlice<'_, f64>,
+        range: Range<usize>,
+        window: &Window,
+    ) -> Option<f64> {
+        match self {
+            Function::Range(function) => function(&samples[range], window),
+            Function::Trend(trend) => trend.apply(&samples[range], window),
+            Function::OverTime(over) => over.apply(buffer.slice(range)),
+            Function::HistogramQuantile { .. }
+            | Function::Relabel(_)
+            | Function::Math(_)
+            | Function::Time
+            | Function::Timestamp
+            | Function::Absent(_) => unreachable!(),
+        }
+    }
+}
+
+// functions that only look at the values inside the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverTime {
+    Avg,
+    Min,
+    Max,
+    Sum,
+    Count,
+    Last,
+    Quantile(f64),
+    Stddev,
+    Present,
+}
+
+impl OverTime {
+    pub fn apply(&self, values: OptionalFixedSlice<'_, f64>) -> Option<f64> {
+        let count = values.count();
+        if count == 0 {
+            return None;
+        }
+        match self {
+            OverTime::Avg => Some(values.sum()? / count as f64),
+            OverTime::Min => values.min(),
+            OverTime::Max => values.max(),
+            OverTime::Sum => values.sum(),
+            OverTime::Count => Some(count as f64),
+            OverTime::Last => values.last(),
+            OverTime::Quantile(q) => Some(quantile(
+                *q,
+                &mut values.into_iter().flatten().copied().collect::<Vec<_>>(),
+            )),
+            OverTime::Stddev => {
+                let mean = values.sum()? / count as f64;
+                Some((values.deviation(mean)? / count as f64).sqrt())
+            }
+            OverTime::Present => Some(1.0),
+        }
+    }
+}
+
+// a range vector selects samples inside (start, end], evaluated at time, which is
+// end before offset and @ moved it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub start: Instant,
+    pub end: Instant,
+    pub time: Instant,
+}
+
+impl Window {
+    #[inline]
+    fn seconds(duration: Duration) -> f64 {
+        duration.as_millis() as f64 / 1000.0
+    }
+}
+
+#[inline]
+pub(crate) fn seconds(at: Instant) -> f64 {
+    at.as_millis() as f64 / 1000.0
+}
+
+pub fn samples(values: &[Option<f64>], meta: &Meta, window: &Window) -> Vec<Sample> {
+    values
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, value)| Some((meta.timestamp(slot), (*value)?)))
+        .filter(|(at, _)| *at > window.start && *at <= window.end)
+        .collect()
+}
+
+// extrapolates the change seen by the samples to the edges of the window,
+// as long as the edges are not too far away from the samples
+fn extrapolate(samples: &[Sample], window: &Window, counter: bool, rate: bool) -> Option<f64> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let (first_at, first) = samples[0];
+    let (last_at, last) = samples[samples.len() - 1];
+
+    let mut result = last - first;
+    if counter {
+        for pair in samples.windows(2) {
+            if pair[1].1 < pair[0].1 {
+                result += pair[0].1;
+            }
+        }
+    }
+
+    let sampled = Window::seconds(last_at - first_at);
+    let average = sampled / (samples.len() - 1) as f64;
+    let mut to_start = Window::seconds(first_at - window.start);
+    let to_end = Window::seconds(window.end - last_at);
+    // a counter can not be extrapolated below zero
+    if counter && result > 0.0 && first >= 0.0 {
+        to_start = to_start.min(sampled * (first / result));
+    }
+
+    let threshold = average * 1.1;
+    let mut interval = sampled;
+    interval += if to_start < threshold {
+        to_start
+    } else {
+        average / 2.0
+    };
+    interval += if to_end < threshold {
+        to_end
+    } else {
+        average / 2.0
+    };
+
+    result *= interval / sampled;
+    if rate {
+        result /= Window::seconds(window.end - window.start);
+    }
+    Some(result)
+}
+
+pub fn rate(samples: &[Sample], window: &Window) -> Option<f64> {
+    extrapolate(samples, window, true, true)
+}
+
+pub fn increase(samples: &[Sample], window: &Window) -> Option<f64> {
+    extrapolate(samples, window, true, false)
+}
+
+pub fn delta(samples: &[Sample], window: &Window) -> Option<f64> {
+    extrapolate(samples, window, false, false)
+}
+
+fn instant(samples: &[Sample], rate: bool) -> Option<f64> {
+    let [.., (previous_at, previous), (last_at, last)] = samples else {
+        return None;
+    };
+    if !rate {
+        return Some(last - previous);
+    }
+    let elapsed = Window::seconds(*last_at - *previous_at);
+    if elapsed <= 0.0 {
+        return None;
+    }
+    // a counter reset drops to zero right after the previous sample
+    let change = if last < previous {
+        *last
+    } else {
+        last - previous
+    };
+    Some(change / elapsed)
+}
+
+pub fn irate(samples: &[Sample], _: &Window) -> Option<f64> {
+    instant(samples, true)
+}
+
+pub fn idelta(samples: &[Sample], _: &Window) -> Option<f64> {
+    instant(samples, false)
+}
+
+// functions fitting the samples of the window, with the parameters that follow it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trend {
+    Deriv,
+    // seconds after the end of the window
+    PredictLinear(f64),
+    // smoothing and trend factors, both inside (0, 1)
+    HoltWinters(f64, f64),
+}
+
+impl Trend {
+    pub fn from_name(name: &str, params: &[f64]) -> Option<Self> {
+        let factor = |factor: f64| factor > 0.0 && factor < 1.0;
+        let trend = match (name, params) {
+            ("deriv", []) => Trend::Deriv,
+            ("predict_linear", [duration]) => Trend::PredictLinear(*duration),
+            ("holt_winters", [smoothing, trend]) if factor(*smoothing) && factor(*trend) => {
+                Trend::HoltWinters(*smoothing, *trend)
+            }
+            _ => return None,
+        };
+        Some(trend)
+    }
+
+    pub fn apply(&self, samples: &[Sample], window: &Window) -> Option<f64> {
+        if samples.len() < 2 {
+            return None;
+        }
+        match self {
+            Trend::Deriv => Some(regression(samples, samples[0].0).0),
+            Trend::PredictLinear(duration) => {
+                let (slope, intercept) = regression(samples, window.time);
+                Some(slope * duration + intercept)
+            }
+            Trend::HoltWinters(smoothing, trend) => {
+                let (mut previous, mut smoothed) = (0.0, samples[0].1);
+                let mut slope = samples[1].1 - samples[0].1;
+                for (i, (_, value)) in samples.iter().enumerate().skip(1) {
+                    if i > 1 {
+                        slope = trend * (smoothed - previous) + (1.0 - trend) * slope;
+                    }
+                    previous = smoothed;
+                    smoothed = smoothing * value + (1.0 - smoothing) * (smoothed + slope);
+                }
+                Some(smoothed)
+            }
+        }
+    }
+}
+
+// least-squares slope per second and the value it gives at the intercept time
+fn regression(samples: &[Sample], intercept_at: Instant) -> (f64, f64) {
+    let first = samples[0].1;
+    if samples.iter().all(|(_, value)| *value == first) {
+        if first.is_infinite() {
+            return (f64::NAN, f64::NAN);
+        }
+        return (0.0, first);
+    }
+    let n = samples.len() as f64;
+    let (mut sum_x, mut sum_y, mut sum_xy, mut sum_x2) = (0.0, 0.0, 0.0, 0.0);
+    for (at, value) in samples {
+        let x = Window::seconds(*at - intercept_at);
+        sum_x += x;
+        sum_y += value;
+        sum_xy += x * value;
+        sum_x2 += x * x;
+    }
+    let covariance = sum_xy - sum_x * sum_y / n;
+    let variance = sum_x2 - sum_x * sum_x / n;
+    let slope = covariance / variance;
+    (slope, sum_y / n - slope * sum_x / n)
+}
+
+// the upper bound of a bucket, "+Inf" or the largest int stands for the last one
+#[inline]
+fn upper_bound(le: &LabelValue) -> Option<f64> {
+    match le {
+        LabelValue::String(le) => std::str::from_utf8(le).ok()?.trim().parse().ok(),
+        LabelValue::Int(i64::MAX) => Some(f64::INFINITY),
+        LabelValue::Int(le) => Some(*le as f64),
+        _ => None,
+    }
+}
+
+// buckets are (upper bound, cumulative count)
+fn bucket_quantile(quantile: f64, buckets: &mut Vec<(f64, f64)>) -> f64 {
+    if quantile.is_nan() {
+        return f64::NAN;
+    }
+    if quantile < 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if quantile > 1.0 {
+        return f64::INFINITY;
+    }
+    buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if buckets.last().map(|(bound, _)| *bound) != Some(f64::INFINITY) {
+        return f64::NAN;
+    }
+    // series of the same bound are summed, and counts never decrease with the bound
+    buckets.dedup_by(|next, kept| {
+        let same = next.0 == kept.0;
+        if same {
+            kept.1 += next.1;
+        }
+        same
+    });
+    let mut max = f64::NEG_INFINITY;
+    for (_, count) in buckets.iter_mut() {
+        max = max.max(*count);
+        *count = max;
+    }
+    if buckets.len() < 2 {
+        return f64::NAN;
+    }
+    let observations = buckets[buckets.len() - 1].1;
+    if observations == 0.0 {
+        return f64::NAN;
+    }
+
+    let mut rank = quantile * observations;
+    let bucket = buckets.partition_point(|(_, count)| *count < rank);
+    if bucket == buckets.len() - 1 {
+        return buckets[buckets.len() - 2].0;
+    }
+    if bucket == 0 && buckets[0].0 <= 0.0 {
+        return buckets[0].0;
+    }
+    let (end, mut count) = buckets[bucket];
+    let mut start = 0.0;
+    if bucket > 0 {
+        start = buckets[bucket - 1].0;
+        count -= buckets[bucket - 1].1;
+        rank -= buckets[bucket - 1].1;
+    }
+    start + (end - start) * (rank / count)
+}
+
+// series are grouped by every label but le, then interpolated slot by slot
+pub(crate) fn histogram_quantile(quantile: f64, le: usize, mut side: Side) -> Side {
+    let mut histograms = BTreeMap::<_, Vec<_>>::new();
+    for mut series in side.series {
+        let Some(bound) = series.labels.remove(le).as_ref().and_then(upper_bound) else {
+            continue;
+        };
+        histograms
+            .entry(series.labels)
+            .or_default()
+            .push((bound, series.values));
+    }
+    side.labels.remove(le);
+    Side {
+        series: histograms
+            .into_iter()
+            .map(|(labels, buckets)| {
+                let width = buckets.iter().map(|(_, values)| values.len()).max();
+                Series {
+                    labels,
+                    values: (0..width.unwrap_or(0))
+                        .map(|slot| {
+                            let mut slot = buckets
+                                .iter()
+                                .filter_map(|(bound, values)| {
+                                    Some((*bound, values.get(slot).copied().flatten()?))
+                                })
+                                .collect::<Vec<_>>();
+                            (!slot.is_empty()).then(|| bucket_quantile(quantile, &mut slot))
+                        })
+                        .collect(),
+                }
+            })
+            .collect(),
+        labels: side.labels,
+        meta: side.meta,
+    }
+}
+
+// element-wise functions, with the parameters that follow the vector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Math {
+    Abs,
+    Ceil,
+    Floor,
+    Round(f64),
+    Sqrt,
+    Exp,
+    Ln,
+    Log2,
+    Log10,
+    Sgn,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Deg,
+    Rad,
+    Clamp(f64, f64),
+    ClampMin(f64),
+    ClampMax(f64),
+    // calendar parts of the value read as seconds since epoch, in UTC
+    Minute,
+    Hour,
+    DayOfWeek,
+    DayOfMonth,
+    DaysInMonth,
+    Month,
+    Year,
+}
+
+impl Math {
+    pub fn from_name(name: &str, params: &[f64]) -> Option<Self> {
+        let math = match (name, params) {
+            ("abs", []) => Math::Abs,
+            ("ceil", []) => Math::Ceil,
+            ("floor", []) => Math::Floor,
+            ("round", []) => Math::Round(1.0),
+            ("round", [to_nearest]) => Math::Round(*to_nearest),
+            ("sqrt", []) => Math::Sqrt,
+            ("exp", []) => Math::Exp,
+            ("ln", []) => Math::Ln,
+            ("log2", []) => Math::Log2,
+            ("log10", []) => Math::Log10,
+            ("sgn", []) => Math::Sgn,
+            ("sin", []) => Math::Sin,
+            ("cos", []) => Math::Cos,
+            ("tan", []) => Math::Tan,
+            ("asin", []) => Math::Asin,
+            ("acos", []) => Math::Acos,
+            ("atan", []) => Math::Atan,
+            ("sinh", []) => Math::Sinh,
+            ("cosh", []) => Math::Cosh,
+            ("tanh", []) => Math::Tanh,
+            ("asinh", []) => Math::Asinh,
+            ("acosh", []) => Math::Acosh,
+            ("atanh", []) => Math::Atanh,
+            ("deg", []) => Math::Deg,
+            ("rad", []) => Math::Rad,
+            ("clamp", [min, max]) => Math::Clamp(*min, *max),
+            ("clamp_min", [min]) => Math::ClampMin(*min),
+            ("clamp_max", [max]) => Math::ClampMax(*max),
+            ("minute", []) => Math::Minute,
+            ("hour", []) => Math::Hour,
+            ("day_of_week", []) => Math::DayOfWeek,
+            ("day_of_month", []) => Math::DayOfMonth,
+            ("days_in_month", []) => Math::DaysInMonth,
+            ("month", []) => Math::Month,
+            ("year", []) => Math::Year,
+            _ => return None,
+        };
+        Some(math)
+    }
+
+    // date functions read the evaluation time when called without a vector
+    #[inline]
+    pub fn is_date(&self) -> bool {
+        matches!(
+            self,
+            Math::Minute
+                | Math::Hour
+                | Math::DayOfWeek
+                | Math::DayOfMonth
+                | Math::DaysInMonth
+                | Math::Month
+                | Math::Year
+        )
+    }
 
-pub type Function = fn(FieldItemImpl) -> Result<FieldItemImpl, DynError>;
+    // clamping with a max below the min drops every sample
+    #[inline]
+    pub fn apply(&self, value: f64) -> Option<f64> {
+        let value = match self {
+            Math::Abs => value.abs(),
+            Math::Ceil => value.ceil(),
+            Math::Floor => value.floor(),
+            Math::Round(to_nearest) => {
+                let inverse = 1.0 / to_nearest;
+                (value * inverse + 0.5).floor() / inverse
+            }
+            Math::Sqrt => value.sqrt(),
+            Math::Exp => value.exp(),
+            Math::Ln => value.ln(),
+            Math::Log2 => value.log2(),
+            Math::Log10 => value.log10(),
+            Math::Sgn if value > 0.0 => 1.0,
+            Math::Sgn if value < 0.0 => -1.0,
+            Math::Sgn => value,
+            Math::Sin => value.sin(),
+            Math::Cos => value.cos(),
+            Math::Tan => value.tan(),
+            Math::Asin => value.asin(),
+            Math::Acos => value.acos(),
+            Math::Atan => value.atan(),
+            Math::Sinh => value.sinh(),
+            Math::Cosh => value.cosh(),
+            Math::Tanh => value.tanh(),
+            Math::Asinh => value.asinh(),
+            Math::Acosh => value.acosh(),
+            Math::Atanh => value.atanh(),
+            Math::Deg => value.to_degrees(),
+            Math::Rad => value.to_radians(),
+            Math::Clamp(min, max) if max < min => return None,
+            Math::Clamp(..) | Math::ClampMin(_) | Math::ClampMax(_) if value.is_nan() => value,
+            Math::Clamp(min, max) => value.min(*max).max(*min),
+            Math::ClampMin(min) => value.max(*min),
+            Math::ClampMax(max) => value.min(*max),
+            date => {
+                let civil = Instant::from_millis(value as i64 * 1000).civil();
+                match date {
+                    Math::Minute => civil.minute as f64,
+                    Math::Hour => civil.hour as f64,
+                    Math::DayOfWeek => civil.weekday as f64,
+                    Math::DayOfMonth => civil.day as f64,
+                    Math::DaysInMonth => civil.days_in_month() as f64,
+                    Math::Month => civil.month as f64,
+                    _ => civil.year as f64,
+                }
+            }
+        };
+        Some(value)
+    }
+}
 
-#[allow(arithmetic_overflow)]
-pub fn rate(item: FieldItemImpl) -> Result<FieldItemImpl, DynError> {
-    macro_rules! rate {
-        ($($label_type:ident), *) => {
+// every numeric field comes out as a float field
+pub fn math(math: &Math, item: FieldItemImpl) -> Result<FieldItemImpl, DynError> {
+    macro_rules! math {
+        ($($field_type:ident), *) => {
             paste! {
             match item {
                 $(
-                FieldItemImpl::$label_type(item) => {
-                    let shift_ref = item.as_ref();
-                    let origin_ref = item.as_ref();
-                    let mut shift = shift_ref.slice_from(1..).into_iter();
-                    let mut origin = origin_ref.into_iter();
-                    // TODO: with capacity
-                    let mut rated = OptionalFixedList::new();
-                    loop {
-                        let (oi, si) = (origin.next(), shift.next());
-                        if oi.is_none() || si.is_none() {
-                            return Ok(FieldItemImpl::$label_type(rated));
-                        }
-                        let (oi, si) = unsafe { (oi.unwrap_unchecked(), si.unwrap_unchecked()) };
-                        let item = match (oi, si) {
-                            (Some(oi), Some(si)) => Some(*si - *oi),
-                            _ => None,
-                        };
-                        rated.push(item);
-                    }
-                }
+                FieldItemImpl::$field_type(item) => Ok(FieldItemImpl::Float64(
+                    item.as_ref()
+                        .into_iter()
+                        .map(|value| value.and_then(|value| math.apply(value.as_f64())))
+                        .collect::<Vec<_>>()
+                        .into(),
+                )),
                 )*
-                FieldItemImpl::Bool(_) => Err(anyhow!("rate function does not support bool type").into()),
+                FieldItemImpl::Bool(_) => {
+                    Err(anyhow!("{:?} function does not support bool type", math).into())
+                }
             }
             }
         };
     }
-    rate!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64)
+    math!(UInt8, UInt16, UInt32, UInt64, Int8, Int16, Int32, Int64, Float32, Float64)
+}
+
+#[derive(Debug)]
+pub struct MathPlanner {
+    pub(crate) math: Math,
+    pub(crate) field: usize,
+}
+
+impl Planner for MathPlanner {
+    type Execution = Mapping;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, inner: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Mapping {
+            planner: self,
+            input: inner,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Mapping {
+    planner: MathPlanner,
+    input: ExecutionImpl,
+}
+
+impl Execution for Mapping {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let input = match Box::pin(self.input.next(cx)).await? {
+            Ok(input) => input,
+            Err(e) => return Some(Err(e)),
+        };
+        let column = &input.fields[self.planner.field];
+        let mut field = Float64Field::with_capacity(input.len(), column.width() as u32);
+        for row in 0..input.len() {
+            match math(&self.planner.math, column.get(row).unwrap()) {
+                Ok(FieldItemImpl::Float64(item)) => field.push(item),
+                Ok(_) => unreachable!(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        Some(Ok(Records {
+            labels: input.labels,
+            fields: vec![Field::Float64(field).into()],
+            meta: input.meta,
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct TimestampPlanner {
+    pub(crate) field: usize,
+}
+
+impl Planner for TimestampPlanner {
+    type Execution = Stamping;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, inner: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Stamping {
+            planner: self,
+            input: inner,
+        })
+    }
+}
+
+// replaces every sample with the time of its slot
+#[derive(Debug)]
+pub struct Stamping {
+    planner: TimestampPlanner,
+    input: ExecutionImpl,
+}
+
+impl Execution for Stamping {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let input = match Box::pin(self.input.next(cx)).await? {
+            Ok(input) => input,
+            Err(e) => return Some(Err(e)),
+        };
+        let Some(meta) = &input.meta else {
+            return Some(Err(anyhow!(
+                "timestamp() requires samples scanned out of a chunk"
+            )
+            .into()));
+        };
+        let column = &input.fields[self.planner.field];
+        let mut field = Float64Field::with_capacity(input.len(), column.width() as u32);
+        for row in 0..input.len() {
+            let values = column.get_f64(row).unwrap_or_default();
+            field.push(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, value)| value.map(|_| seconds(meta.timestamp(slot))))
+                    .collect::<Vec<_>>()
+                    .into(),
+            );
+        }
+        Some(Ok(Records {
+            labels: input.labels,
+            fields: vec![Field::Float64(field).into()],
+            meta: input.meta.clone(),
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Rewrite {
+    // sources are positions in the input labels, a missing one reads as empty
+    Replace {
+        source: Option<usize>,
+        regex: Regex,
+        replacement: String,
+    },
+    Join {
+        sources: Vec<Option<usize>>,
+        separator: String,
+    },
+}
+
+// rewrites one label of every series, which becomes a string label even if the
+// schema has it in another type, or appends it if the schema does not have it
+#[derive(Debug, Clone)]
+pub struct Relabel {
+    pub name: String,
+    pub destination: usize,
+    pub rewrite: Rewrite,
+}
+
+impl Relabel {
+    pub fn labels(&self, mut labels: Vec<Label>) -> Vec<Label> {
+        let label = Label {
+            r#type: LabelType::String(()),
+            name: self.name.clone(),
+        };
+        match labels.get_mut(self.destination) {
+            Some(destination) => *destination = label,
+            None => labels.push(label),
+        }
+        labels
+    }
+
+    pub(crate) fn apply(&self, labels: &mut Vec<Option<LabelValue>>) {
+        let value = |source: &Option<usize>| {
+            source
+                .and_then(|source| labels[source].as_ref())
+                .map(ToString::to_string)
+                .unwrap_or_default()
+        };
+        let rewritten = match &self.rewrite {
+            Rewrite::Replace {
+                source,
+                regex,
+                replacement,
+            } => regex.captures(&value(source)).map(|captures| {
+                let mut rewritten = String::new();
+                captures.expand(replacement, &mut rewritten);
+                rewritten
+            }),
+            Rewrite::Join { sources, separator } => Some(
+                sources
+                    .iter()
+                    .map(value)
+                    .collect::<Vec<_>>()
+                    .join(separator),
+            ),
+        };
+
+        if self.destination == labels.len() {
+            labels.push(None);
+        }
+        let destination = &mut labels[self.destination];
+        *destination = match rewritten {
+            Some(rewritten) => Some(rewritten),
+            None => destination.as_ref().map(ToString::to_string),
+        }
+        .filter(|value| !value.is_empty())
+        .map(|value| LabelValue::String(value.into_bytes()));
+    }
+}
+
+#[derive(Debug)]
+pub struct RelabelPlanner {
+    pub(crate) relabel: Relabel,
+    pub(crate) labels: Vec<Label>,
+    pub(crate) field: usize,
+}
+
+impl Planner for RelabelPlanner {
+    type Execution = Relabeling;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, inner: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Relabeling {
+            planner: Some(self),
+            input: inner,
+        })
+    }
+}
+
+// series are relabeled once collected, as rewriting may leave two of them the same
+#[derive(Debug)]
+pub struct Relabeling {
+    planner: Option<RelabelPlanner>,
+    input: ExecutionImpl,
+}
+
+impl Execution for Relabeling {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        let input = std::mem::replace(&mut self.input, ExecutionImpl::Id(()));
+        let mut side = match Side::collect(cx, input, vec![], planner.field).await {
+            Ok(side) => side,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut seen = HashSet::new();
+        for series in side.series.iter_mut() {
+            planner.relabel.apply(&mut series.labels);
+            if !seen.insert(series.labels.clone()) {
+                return Some(Err(anyhow!(
+                    "vector cannot contain metrics with the same labelset"
+                )
+                .into()));
+            }
+        }
+        Some(Ok(records(&planner.labels, side.series, side.meta)))
+    }
+}
+
+#[derive(Debug)]
+pub struct HistogramPlanner {
+    pub(crate) quantile: f64,
+    pub(crate) le: usize,
+    pub(crate) labels: Vec<Label>,
+    pub(crate) field: usize,
+}
+
+impl Planner for HistogramPlanner {
+    type Execution = Histogram;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, inner: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Histogram {
+            planner: Some(self),
+            input: inner,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Histogram {
+    planner: Option<HistogramPlanner>,
+    input: ExecutionImpl,
+}
+
+impl Execution for Histogram {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        let input = std::mem::replace(&mut self.input, ExecutionImpl::Id(()));
+        let side = match Side::collect(cx, input, planner.labels, planner.field).await {
+            Ok(side) => side,
+            Err(e) => return Some(Err(e)),
+        };
+        let side = histogram_quantile(planner.quantile, planner.le, side);
+        Some(Ok(records(&side.labels, side.series, side.meta)))
+    }
+}
+
+#[derive(Debug)]
+pub struct AbsentPlanner {
+    pub(crate) values: Vec<Option<LabelValue>>,
+    pub(crate) labels: Vec<Label>,
+    pub(crate) field: usize,
+    // slots to report absence on, a single one for the whole input without it
+    pub(crate) meta: Option<Meta>,
+}
+
+impl Planner for AbsentPlanner {
+    type Execution = Absence;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, inner: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Absence {
+            planner: Some(self),
+            input: inner,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Absence {
+    planner: Option<AbsentPlanner>,
+    input: ExecutionImpl,
+}
+
+impl Execution for Absence {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        let input = std::mem::replace(&mut self.input, ExecutionImpl::Id(()));
+        let mut side = match Side::collect(cx, input, vec![], planner.field).await {
+            Ok(side) => side,
+            Err(e) => return Some(Err(e)),
+        };
+        let values = match &planner.meta {
+            Some(meta) => {
+                side.rebase(meta);
+                (0..meta.width())
+                    .map(|slot| {
+                        side.series
+                            .iter()
+                            .all(|series| series.values[slot].is_none())
+                            .then_some(1.0)
+                    })
+                    .collect()
+            }
+            None => {
+                let empty = side
+                    .series
+                    .iter()
+                    .all(|series| series.values.iter().all(Option::is_none));
+                vec![empty.then_some(1.0)]
+            }
+        };
+        let series = Series {
+            labels: planner.values,
+            values,
+        };
+        Some(Ok(records(&planner.labels, vec![series], planner.meta)))
+    }
+}
+
+#[derive(Debug)]
+pub struct CallPlanner {
+    pub(crate) function: Function,
+    pub(crate) window: Window,
+    pub(crate) labels: Vec<Label>,
+    pub(crate) field: usize,
+}
+
+impl Planner for CallPlanner {
+    type Execution = Call;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, inner: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Call {
+            planner: Some(self),
+            input: inner,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Call {
+    planner: Option<CallPlanner>,
+    input: ExecutionImpl,
+}
+
+impl Execution for Call {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        let window = &planner.window;
+        let (unit, gathered) = match gather(cx, &mut self.input, planner.field, window).await {
+            Ok(gathered) => gathered,
+            Err(e) => return Some(Err(e)),
+        };
+        let rows = gathered
+            .into_iter()
+            .map(|(labels, samples)| {
+                let buffer = buffer(&samples);
+                let value =
+                    planner
+                        .function
+                        .fold(&samples, buffer.as_ref(), 0..samples.len(), window);
+                Series {
+                    labels,
+                    values: vec![value],
+                }
+            })
+            .collect();
+        // every series gives one sample, at the end of the window
+        let meta = unit.map(|unit| Meta::new(window.end, unit, 0, 1));
+        Some(Ok(records(&planner.labels, rows, meta)))
+    }
+}
+
+pub(crate) type Gathered = BTreeMap<Vec<Option<LabelValue>>, Vec<Sample>>;
+
+// every sample of every series inside the window in time order, without staleness
+// markers, so a series spread over chunks is folded once over the samples of every
+// chunk, along with the resolution they were stored at
+pub(crate) async fn gather(
+    cx: &mut Context,
+    input: &mut ExecutionImpl,
+    field: usize,
+    window: &Window,
+) -> Result<(Option<Duration>, Gathered), DynError> {
+    let mut gathered = Gathered::new();
+    let mut unit = None;
+    while let Some(records) = Box::pin(input.next(cx)).await {
+        let records = records?;
+        let Some(meta) = &records.meta else {
+            if records.is_empty() {
+                continue;
+            }
+            return Err(binary::Error::Unaligned.into());
+        };
+        unit = Some(meta.unit());
+        for row in 0..records.len() {
+            let values = records.fields[field].get_f64(row).unwrap_or_default();
+            gathered
+                .entry(records.labels.iter().map(|label| label.get(row)).collect())
+                .or_default()
+                .extend(
+                    samples(&values, meta, window)
+                        .into_iter()
+                        .filter(|(_, value)| !is_stale(*value)),
+                );
+        }
+    }
+    for samples in gathered.values_mut() {
+        samples.sort_by_key(|(at, _)| *at);
+        samples.dedup_by_key(|(at, _)| *at);
+    }
+    Ok((unit, gathered))
 }
 
 #[cfg(test)]
 mod tests {
     use chunk::mutable::column::field::FieldItemImpl;
-    use common::scalar::list::OptionalFixedList;
+    use common::{
+        column::label::{LabelType, LabelValue},
+        scalar::{list::OptionalFixedList, Scalar},
+        schema::Label,
+        time::{Duration, Instant},
+    };
+    use regex::Regex;
+
+    use super::{
+        bucket_quantile, delta, histogram_quantile, increase, irate, math, rate, Math, OverTime,
+        Relabel, Rewrite, Sample, Trend, Window,
+    };
+    use crate::execute::binary::{Series, Side};
 
-    use super::rate;
+    fn samples(values: &[(i64, f64)]) -> Vec<Sample> {
+        values
+            .iter()
+            .map(|(secs, value)| (Instant::from_millis(secs * 1000), *value))
+            .collect()
+    }
 
     #[test]
     fn test_rate() {
-        let list = FieldItemImpl::Int32(OptionalFixedList::from(vec![
-            Some(1),
-            Some(3),
-            Some(5),
-            Some(7),
-            Some(9),
-            None,
-            Some(12),
-            Some(13),
-        ]));
-        let result = rate(list).unwrap();
+        let window = Window {
+            start: Instant::from_millis(0),
+            end: Instant::from_millis(0) + Duration::from_secs(60),
+            time: Instant::from_millis(0) + Duration::from_secs(60),
+        };
+
+        // a counter reset from 40 to 5 still counts as an increase of 5, and
+        // the start is extrapolated only to where the counter would be zero
+        let counter = samples(&[(10, 10.0), (20, 20.0), (30, 40.0), (40, 5.0), (50, 15.0)]);
+        let increased = increase(&counter, &window).unwrap();
+        assert!((increased - 66.25).abs() < 1e-9);
+        assert!((rate(&counter, &window).unwrap() - increased / 60.0).abs() < 1e-9);
+        assert_eq!(irate(&counter, &window), Some(1.0));
+
+        let counter = samples(&[(10, 1.0), (20, 2.0), (30, 3.0)]);
+        assert!((increase(&counter, &window).unwrap() - 3.5).abs() < 1e-9);
+
+        let gauge = samples(&[(10, 10.0), (20, 20.0), (30, 5.0)]);
+        assert!((delta(&gauge, &window).unwrap() + 8.75).abs() < 1e-9);
+        assert_eq!(irate(&gauge, &window), Some(0.5));
+
+        assert_eq!(rate(&samples(&[(10, 1.0)]), &window), None);
+    }
+
+    // expected values are the outputs of Prometheus on the same series
+    #[test]
+    fn test_trend() {
+        let window = Window {
+            start: Instant::from_millis(-1),
+            end: Instant::from_millis(3_000_000),
+            time: Instant::from_millis(3_000_000),
+        };
+
+        // 0+10x4 0+10x5 every 5m
+        let reset = samples(
+            &[
+                0.0, 10.0, 20.0, 30.0, 40.0, 0.0, 10.0, 20.0, 30.0, 40.0, 50.0,
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| (i as i64 * 300, value))
+            .collect::<Vec<_>>(),
+        );
+        let deriv = Trend::Deriv.apply(&reset, &window).unwrap();
+        assert!((deriv - 0.010606060606060607).abs() < 1e-12);
+        let predicted = Trend::PredictLinear(3600.0).apply(&reset, &window).unwrap();
+        assert!((predicted - 76.81818181818181).abs() < 1e-9);
+
+        // 0+80x10 every 5m
+        let counter = samples(
+            &(0..=10)
+                .map(|i| (i * 300, i as f64 * 80.0))
+                .collect::<Vec<_>>(),
+        );
+        let deriv = Trend::Deriv.apply(&counter, &window).unwrap();
+        assert!((deriv - 0.26666666666666666).abs() < 1e-12);
+
+        // 0+10x1000 and 0+20x1000 every 10s, read at 8000s over 1m
+        let window = Window {
+            start: Instant::from_millis(7_940_000),
+            end: Instant::from_millis(8_000_000),
+            time: Instant::from_millis(8_000_000),
+        };
+        let holt_winters = Trend::from_name("holt_winters", &[0.01, 0.1]).unwrap();
+        for (factor, expected) in [(1, 8000.0), (2, 16000.0)] {
+            let series = samples(
+                &(795..=800)
+                    .map(|i| (i * 10, (i * 10 * factor) as f64))
+                    .collect::<Vec<_>>(),
+            );
+            let smoothed = holt_winters.apply(&series, &window).unwrap();
+            assert!((smoothed - expected).abs() < 1e-9);
+        }
+
+        assert_eq!(Trend::from_name("holt_winters", &[1.0, 0.5]), None);
+        assert_eq!(
+            Trend::PredictLinear(60.0).apply(&counter[..1], &window),
+            None
+        );
         assert_eq!(
-            result,
-            FieldItemImpl::Int32(OptionalFixedList::from(vec![
-                Some(2),
-                Some(2),
-                Some(2),
-                Some(2),
-                None,
-                None,
-                Some(1)
-            ]))
+            Trend::Deriv.apply(&samples(&[(10, 3.0), (20, 3.0)]), &window),
+            Some(0.0)
         );
     }
+
+    #[test]
+    fn test_over_time() {
+        let list = OptionalFixedList::from(vec![Some(4.0), None, Some(1.0), Some(3.0), None]);
+        let values = list.as_ref();
+        assert_eq!(OverTime::Avg.apply(values.clone()), Some(8.0 / 3.0));
+        assert_eq!(OverTime::Count.apply(values.clone()), Some(3.0));
+        assert_eq!(OverTime::Last.apply(values.clone()), Some(3.0));
+        assert_eq!(OverTime::Quantile(0.5).apply(values.clone()), Some(3.0));
+        assert_eq!(OverTime::Min.apply(values.slice(3..5)), Some(3.0));
+        assert_eq!(OverTime::Present.apply(values.slice(1..2)), None);
+    }
+
+    #[test]
+    fn test_histogram_quantile() {
+        let buckets = [(0.1, 10.0), (0.5, 30.0), (1.0, 40.0), (f64::INFINITY, 40.0)];
+        let quantile = |q: f64| bucket_quantile(q, &mut buckets.to_vec());
+        assert!((quantile(0.5) - 0.3).abs() < 1e-9);
+        assert!((quantile(0.99) - 0.98).abs() < 1e-9);
+        assert_eq!(quantile(1.0), 1.0);
+        assert_eq!(quantile(-1.0), f64::NEG_INFINITY);
+
+        // counts are fixed to never decrease
+        let mut buckets = vec![(0.1, 10.0), (0.5, 8.0), (f64::INFINITY, 10.0)];
+        assert!((bucket_quantile(0.5, &mut buckets) - 0.05).abs() < 1e-9);
+        assert!(bucket_quantile(0.5, &mut vec![(0.1, 10.0), (0.5, 20.0)]).is_nan());
+
+        let side = Side {
+            series: [(1, 5.0), (2, 10.0), (i64::MAX, 10.0)]
+                .into_iter()
+                .map(|(le, count)| Series {
+                    labels: vec![Some(LabelValue::Int(le))],
+                    values: vec![Some(count), None],
+                })
+                .collect(),
+            labels: vec![Label {
+                r#type: LabelType::Int(()),
+                name: "le".into(),
+            }],
+            meta: None,
+        };
+        let side = histogram_quantile(0.75, 0, side);
+        assert!(side.labels.is_empty());
+        assert_eq!(side.series.len(), 1);
+        assert_eq!(side.series[0].values, vec![Some(1.5), None]);
+    }
+
+    #[test]
+    fn test_relabel() {
+        let string = |value: &str| Some(LabelValue::String(value.into()));
+        let replace = Relabel {
+            name: "team".into(),
+            destination: 2,
+            rewrite: Rewrite::Replace {
+                source: Some(0),
+                regex: Regex::new("^(?:(.*)-(.*))$").unwrap(),
+                replacement: "$2".into(),
+            },
+        };
+        let mut labels = vec![string("api-prod"), Some(LabelValue::Int(1))];
+        replace.apply(&mut labels);
+        assert_eq!(labels[2], string("prod"));
+        // no match leaves the label as it was
+        let mut labels = vec![string("api"), None, string("web")];
+        replace.apply(&mut labels);
+        assert_eq!(labels[2], string("web"));
+
+        let join = Relabel {
+            name: "shard".into(),
+            destination: 1,
+            rewrite: Rewrite::Join {
+                sources: vec![Some(0), None, Some(1)],
+                separator: ":".into(),
+            },
+        };
+        let mut labels = vec![string("api"), Some(LabelValue::Int(1))];
+        join.apply(&mut labels);
+        assert_eq!(labels, vec![string("api"), string("api::1")]);
+
+        // an empty value removes the label
+        let join = Relabel {
+            rewrite: Rewrite::Join {
+                sources: vec![None],
+                separator: ":".into(),
+            },
+            ..join
+        };
+        join.apply(&mut labels);
+        assert_eq!(labels, vec![string("api"), None]);
+    }
+
+    #[test]
+    fn test_math() {
+        assert_eq!(Math::Sgn.apply(-3.0), Some(-1.0));
+        assert_eq!(Math::Sgn.apply(0.0), Some(0.0));
+        assert_eq!(Math::Round(1.0).apply(2.5), Some(3.0));
+        assert_eq!(Math::Round(1.0).apply(-2.5), Some(-2.0));
+        assert_eq!(Math::Round(0.5).apply(1.3), Some(1.5));
+        assert_eq!(Math::Clamp(1.0, 3.0).apply(5.0), Some(3.0));
+        assert_eq!(Math::Clamp(3.0, 1.0).apply(5.0), None);
+        assert!(Math::ClampMin(1.0).apply(f64::NAN).unwrap().is_nan());
+        assert_eq!(
+            Math::from_name("clamp_max", &[2.0]),
+            Some(Math::ClampMax(2.0))
+        );
+        assert_eq!(Math::from_name("clamp_max", &[]), None);
+
+        let item = FieldItemImpl::Int32(OptionalFixedList::from(vec![Some(-4), None, Some(9)]));
+        assert_eq!(
+            math(&Math::Abs, item).unwrap(),
+            FieldItemImpl::Float64(OptionalFixedList::from(vec![Some(4.0), None, Some(9.0)]))
+        );
+        let item = FieldItemImpl::Bool(OptionalFixedList::from(vec![Some(true)]));
+        assert!(math(&Math::Abs, item).is_err());
+
+        // 2024-02-29T23:59:59Z, a Thursday
+        let seconds = 1_709_251_199.5;
+        assert_eq!(Math::Minute.apply(seconds), Some(59.0));
+        assert_eq!(Math::Hour.apply(seconds), Some(23.0));
+        assert_eq!(Math::DayOfWeek.apply(seconds), Some(4.0));
+        assert_eq!(Math::DayOfMonth.apply(seconds), Some(29.0));
+        assert_eq!(Math::DaysInMonth.apply(seconds), Some(29.0));
+        assert_eq!(Math::Month.apply(seconds), Some(2.0));
+        assert_eq!(Math::Year.apply(seconds), Some(2024.0));
+    }
 }
@@ -1,13 +1,22 @@
+pub mod aggregate;
+pub mod binary;
 pub mod function;
 pub mod plan;
 pub mod scan;
+pub mod step;
 
 use std::error::Error;
 
 use chunk::mutable::Records;
 use common::{context::Context, DynError};
 
-use self::scan::Scan;
+use self::{
+    aggregate::Aggregate,
+    binary::Binary,
+    function::{Absence, Call, Histogram, Mapping, Relabeling, Stamping},
+    scan::Scan,
+    step::{Clock, Selection, Sliding},
+};
 
 pub trait Planner {
     type Execution: Execution;
@@ -19,6 +28,17 @@ pub trait Planner {
 #[derive(Debug)]
 pub enum ExecutionImpl {
     Scan(Scan),
+    Binary(Box<Binary>),
+    Aggregate(Box<Aggregate>),
+    Call(Box<Call>),
+    Histogram(Box<Histogram>),
+    Relabel(Box<Relabeling>),
+    Math(Box<Mapping>),
+    Timestamp(Box<Stamping>),
+    Absent(Box<Absence>),
+    Select(Box<Selection>),
+    Slide(Box<Sliding>),
+    Clock(Box<Clock>),
     Id(()),
 }
 
@@ -26,6 +46,26 @@ pub trait Execution {
     async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>>;
 }
 
+impl Execution for ExecutionImpl {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        match self {
+            ExecutionImpl::Scan(scan) => scan.next(cx).await,
+            ExecutionImpl::Binary(binary) => binary.next(cx).await,
+            ExecutionImpl::Aggregate(aggregate) => Box::pin(aggregate.next(cx)).await,
+            ExecutionImpl::Call(call) => Box::pin(call.next(cx)).await,
+            ExecutionImpl::Histogram(histogram) => Box::pin(histogram.next(cx)).await,
+            ExecutionImpl::Relabel(relabel) => Box::pin(relabel.next(cx)).await,
+            ExecutionImpl::Math(math) => Box::pin(math.next(cx)).await,
+            ExecutionImpl::Timestamp(timestamp) => Box::pin(timestamp.next(cx)).await,
+            ExecutionImpl::Absent(absent) => Box::pin(absent.next(cx)).await,
+            ExecutionImpl::Select(select) => Box::pin(select.next(cx)).await,
+            ExecutionImpl::Slide(slide) => Box::pin(slide.next(cx)).await,
+            ExecutionImpl::Clock(clock) => clock.next(cx).await,
+            ExecutionImpl::Id(_) => None,
+        }
+    }
+}
+
 impl Execution for () {
     #[inline]
     async fn next(&mut self, _: &mut Context) -> Option<Result<Records, DynError>> {
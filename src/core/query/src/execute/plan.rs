@@ -1,56 +1,313 @@
 use std::error::Error;
 
-use common::{query::Projection, Set};
+use anyhow::anyhow;
+use common::time::{Instant, Range};
 
-use super::{scan::ScanPlanner, ExecutionImpl, Planner};
-use crate::plan::physical::Physical;
+use super::{
+    aggregate::AggregatePlanner,
+    binary::{self, BinaryPlanner, Operand},
+    function::{
+        self, AbsentPlanner, CallPlanner, Function, HistogramPlanner, MathPlanner, RelabelPlanner,
+        TimestampPlanner, Window,
+    },
+    scan::ScanPlanner,
+    step::{ClockPlanner, SelectPlanner, SlidePlanner},
+    ExecutionImpl, Planner,
+};
+use crate::{
+    evaluate::Steps,
+    plan::physical::{Call, Physical, Scan},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Context {
     limit: Option<usize>,
-    projection: Projection<usize>,
+    // evaluation times, samples are read at every step instead of as they are stored
+    steps: Option<Steps>,
 }
 
-impl Default for Context {
-    fn default() -> Self {
+impl Context {
+    #[inline]
+    pub fn with_steps(steps: Steps) -> Self {
         Self {
-            limit: Default::default(),
-            projection: Projection {
-                labels: Set::Universe,
-                fields: Set::Universe,
-            },
+            limit: None,
+            steps: Some(steps),
+        }
+    }
+
+    // inputs are evaluated at the same steps, but are not limited
+    #[inline]
+    fn input(&self) -> Self {
+        Self {
+            limit: None,
+            steps: self.steps,
         }
     }
 }
 
 pub fn plan(cx: &mut Context, expr: Physical) -> Result<ExecutionImpl, Box<dyn Error>> {
     match expr {
-        Physical::Scan(scan) => {
-            cx.projection.append(scan.projection);
-            Ok(ExecutionImpl::Scan(
-                ScanPlanner {
-                    resource: scan.resource,
-                    matcher: scan.matcher,
-                    limit: cx.limit,
-                    projection: cx.projection.clone(),
-                    range: scan.range,
+        Physical::Scan(scan) => match cx.steps {
+            Some(_) if scan.range.start.is_some() => {
+                Err(anyhow!("range vector selector can not be evaluated at a step").into())
+            }
+            Some(steps) => Ok(ExecutionImpl::Select(Box::new(
+                SelectPlanner {
+                    scan,
+                    steps,
+                    timestamps: false,
                 }
-                .plan(ExecutionImpl::Id(()))
-                .map_err(|e| Box::new(e) as Box<_>)?,
-            ))
+                .plan(ExecutionImpl::Id(()))?,
+            ))),
+            None => Ok(ExecutionImpl::Scan(
+                scan_planner(cx, scan)
+                    .plan(ExecutionImpl::Id(()))
+                    .map_err(|e| Box::new(e) as Box<_>)?,
+            )),
+        },
+        Physical::Call(call) => {
+            if let Function::HistogramQuantile { quantile, le } = call.function {
+                let Some(input) = call.args.into_iter().next() else {
+                    return Err(anyhow!("function {} expects an instant vector", call.name).into());
+                };
+                return Ok(ExecutionImpl::Histogram(Box::new(
+                    HistogramPlanner {
+                        quantile,
+                        le,
+                        labels: input.labels(),
+                        field: input.field(),
+                    }
+                    .plan(plan(&mut cx.input(), input)?)?,
+                )));
+            }
+            if let Function::Relabel(relabel) = call.function {
+                let Some(input) = call.args.into_iter().next() else {
+                    return Err(anyhow!("function {} expects an instant vector", call.name).into());
+                };
+                return Ok(ExecutionImpl::Relabel(Box::new(
+                    RelabelPlanner {
+                        relabel,
+                        labels: call.labels,
+                        field: input.field(),
+                    }
+                    .plan(plan(&mut cx.input(), input)?)?,
+                )));
+            }
+            if let Function::Absent(values) = call.function {
+                let Some(input) = call.args.into_iter().next() else {
+                    return Err(anyhow!("function {} expects an instant vector", call.name).into());
+                };
+                return Ok(ExecutionImpl::Absent(Box::new(
+                    AbsentPlanner {
+                        values,
+                        labels: call.labels,
+                        field: input.field(),
+                        meta: cx.steps.map(|steps| steps.meta()),
+                    }
+                    .plan(plan(&mut cx.input(), input)?)?,
+                )));
+            }
+            if let Function::Time = call.function {
+                return Err(anyhow!("function {} needs an evaluation time", call.name).into());
+            }
+            if let Function::Timestamp = call.function {
+                let Some(input) = call.args.into_iter().next() else {
+                    return Err(anyhow!("function {} expects an instant vector", call.name).into());
+                };
+                // at steps a selector reads the time of the samples it picks
+                return match (cx.steps, input) {
+                    (Some(steps), Physical::Scan(scan)) if scan.range.start.is_none() => {
+                        Ok(ExecutionImpl::Select(Box::new(
+                            SelectPlanner {
+                                scan,
+                                steps,
+                                timestamps: true,
+                            }
+                            .plan(ExecutionImpl::Id(()))?,
+                        )))
+                    }
+                    (_, input) => Ok(ExecutionImpl::Timestamp(Box::new(
+                        TimestampPlanner {
+                            field: input.field(),
+                        }
+                        .plan(plan(&mut cx.input(), input)?)?,
+                    ))),
+                };
+            }
+            if let Function::Math(math) = call.function {
+                let Some(input) = call.args.into_iter().next() else {
+                    let Some(steps) = cx.steps else {
+                        return Err(
+                            anyhow!("function {} needs an evaluation time", call.name).into()
+                        );
+                    };
+                    return Ok(ExecutionImpl::Clock(Box::new(
+                        ClockPlanner { math, steps }.plan(ExecutionImpl::Id(()))?,
+                    )));
+                };
+                return Ok(ExecutionImpl::Math(Box::new(
+                    MathPlanner {
+                        math,
+                        field: input.field(),
+                    }
+                    .plan(plan(&mut cx.input(), input)?)?,
+                )));
+            }
+            let labels = call.labels;
+            let Some(Physical::Scan(scan)) = call.args.into_iter().next() else {
+                return Err(anyhow!("function {} expects a range vector", call.name).into());
+            };
+            let (Some(start), Some(end)) = (scan.range.start, scan.range.end) else {
+                return Err(anyhow!("function {} expects a range vector", call.name).into());
+            };
+            if let Some(steps) = cx.steps {
+                return Ok(ExecutionImpl::Slide(Box::new(
+                    SlidePlanner {
+                        function: call.function,
+                        scan,
+                        steps,
+                        labels,
+                    }
+                    .plan(ExecutionImpl::Id(()))?,
+                )));
+            }
+            let field = Physical::Scan(scan.clone()).field();
+            // the scan range includes its start, the window does not
+            let scan = Scan {
+                range: Range {
+                    start: Some(Instant::from_millis(start.as_millis() + 1)),
+                    end: Some(Instant::from_millis(end.as_millis() + 1)),
+                },
+                ..scan
+            };
+            Ok(ExecutionImpl::Call(Box::new(
+                CallPlanner {
+                    function: call.function,
+                    // without steps, @ leaves no evaluation time to go back to
+                    window: Window {
+                        start,
+                        end,
+                        time: scan.offset.map_or(end, |offset| end + offset),
+                    },
+                    labels,
+                    field,
+                }
+                .plan(plan(&mut cx.input(), Physical::Scan(scan))?)?,
+            )))
         }
-        Physical::Call(_) => {
-            unimplemented!()
+        Physical::Binary(binary) => Ok(ExecutionImpl::Binary(Box::new(
+            BinaryPlanner {
+                op: binary.op,
+                return_bool: binary.return_bool,
+                matching: binary.matching,
+                lhs: operand(cx, *binary.lhs)?,
+                rhs: operand(cx, *binary.rhs)?,
+                labels: binary.labels,
+            }
+            .plan(ExecutionImpl::Id(()))?,
+        ))),
+        Physical::Aggregate(aggregate) => {
+            let planner = AggregatePlanner {
+                op: aggregate.op,
+                param: aggregate.param,
+                by: aggregate.by,
+                labels: aggregate.labels,
+                field: aggregate.input.field(),
+            };
+            // selectors are aggregated on the workers reading them
+            Ok(ExecutionImpl::Aggregate(Box::new(
+                match (cx.steps, *aggregate.input) {
+                    (None, Physical::Scan(scan)) => {
+                        planner.plan_partial(scan_planner(&mut cx.input(), scan))
+                    }
+                    (Some(steps), Physical::Scan(scan)) if scan.range.start.is_none() => planner
+                        .plan_selected(SelectPlanner {
+                            scan,
+                            steps,
+                            timestamps: false,
+                        }),
+                    (_, input) => planner.plan(plan(&mut cx.input(), input)?)?,
+                },
+            )))
         }
+        Physical::Number(_) => Err(anyhow!("scalar expression can not be executed alone").into()),
+    }
+}
+
+// scalars read no samples, so they are computed while planning, with a value per step
+// or a single one for every step
+pub fn scalar(cx: &Context, expr: Physical) -> Result<Vec<f64>, Box<dyn Error>> {
+    match expr {
+        Physical::Number(value) => Ok(vec![value]),
+        Physical::Call(Call {
+            function: Function::Time,
+            name,
+            ..
+        }) => match &cx.steps {
+            Some(steps) => Ok(steps.times().map(function::seconds).collect()),
+            None => Err(anyhow!("function {} needs an evaluation time", name).into()),
+        },
+        Physical::Binary(binary) => Ok(binary::scalars(
+            binary.op,
+            binary.return_bool,
+            &scalar(cx, *binary.lhs)?,
+            &scalar(cx, *binary.rhs)?,
+        )),
+        _ => Err(anyhow!("expression is not a scalar").into()),
+    }
+}
+
+// records keep the projection of the scan, so they line up with its labels and field
+pub(crate) fn scan_planner(cx: &mut Context, scan: Scan) -> ScanPlanner {
+    ScanPlanner {
+        resource: scan.resource,
+        matcher: scan.matcher,
+        predicate: scan.predicate,
+        limit: cx.limit,
+        projection: scan.projection,
+        range: scan.range,
+    }
+}
+
+fn operand(cx: &Context, expr: Physical) -> Result<Operand, Box<dyn Error>> {
+    if expr.is_scalar() {
+        return Ok(Operand::Scalar(scalar(cx, expr)?));
     }
+    Ok(Operand::Vector {
+        labels: expr.labels(),
+        field: expr.field(),
+        input: plan(&mut cx.input(), expr)?,
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use resource::db::tests::test_db;
+    use std::sync::Arc;
+
+    use chunk::mutable::MutableChunk;
+    use common::{
+        column::{
+            field::Field,
+            label::{Label, LabelType},
+        },
+        context::Context as ExecuteContext,
+        index::Index,
+        schema::{self, Schema},
+        time::{Duration, Instant},
+    };
+    use resource::{
+        db::tests::test_db,
+        table::{ChunkMeta, Meta, MutableMeta},
+    };
 
     use super::{plan, Context};
-    use crate::{check::Checker, parse::Parser, Layer, Pass};
+    use crate::{
+        check::Checker,
+        execute::Execution,
+        parse::{Evaluation, Parser},
+        plan::physical::Physical,
+        Layer, Pass,
+    };
 
     #[test]
     fn plan_scan() {
@@ -70,4 +327,183 @@ mod tests {
                 println!("plan {:?}", plan);
             });
     }
+
+    #[test]
+    fn plan_projected_field() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                db.write()
+                    .unwrap()
+                    .create_table(
+                        Arc::from("foo.bar.request_size"),
+                        Meta {
+                            chunk: ChunkMeta {
+                                mutable: MutableMeta {
+                                    width: 1,
+                                    length: 1,
+                                    count: 1,
+                                },
+                            },
+                            schema: Arc::new(Schema {
+                                labels: vec![schema::Label {
+                                    r#type: LabelType::String(()),
+                                    name: "env".into(),
+                                }],
+                                fields: vec![
+                                    schema::Field {
+                                        r#type: Field::Bool(()).into(),
+                                        name: "truncated".into(),
+                                    },
+                                    schema::Field {
+                                        r#type: Field::Float64(()).into(),
+                                        name: "value".into(),
+                                    },
+                                ],
+                                index: vec![Index::Inverted(())],
+                            }),
+                        },
+                    )
+                    .unwrap();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.request_size")
+                    .unwrap()
+                    .clone();
+                let start_at = Instant::from_millis(1_000_000);
+                let mut chunk =
+                    MutableChunk::new(&table.meta.schema, start_at, Duration::SECOND, 1, 1);
+                chunk
+                    .push(vec![Some(Label::String("prod".into()))])
+                    .unwrap();
+                chunk.set(0, 0, start_at, Some(Field::Bool(true))).unwrap();
+                chunk
+                    .set(0, 1, start_at, Some(Field::Float64(512.0)))
+                    .unwrap();
+                table.shards.get().borrow_mut().mutable.push(chunk);
+
+                let parser = Checker::new(db).layer(
+                    Parser::with_evaluation(Evaluation::instant(start_at + Duration::SECOND))
+                        .layer(()),
+                );
+                let mir = parser.apply("foo.bar.request_size * 2").unwrap();
+                let mut execution = plan(&mut Context::default(), mir).unwrap();
+                let records = execution
+                    .next(&mut ExecuteContext::new(256))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(records.fields[0].get_f64(0), Some(vec![Some(1024.0)]));
+
+                let mir = parser.apply("foo.bar.request_size").unwrap();
+                let Physical::Scan(scan) = &mir else {
+                    unreachable!()
+                };
+                assert_eq!((mir.field(), scan.field_id()), (0, 1));
+                let mut execution = plan(&mut Context::default(), mir).unwrap();
+                let records = execution
+                    .next(&mut ExecuteContext::new(256))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(records.fields.len(), 1);
+                assert_eq!(records.fields[0].get_f64(0), Some(vec![Some(512.0)]));
+            });
+    }
+
+    #[test]
+    fn plan_functions() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+                let start_at = Instant::from_millis(1_000_000);
+                let mut chunk =
+                    MutableChunk::new(&table.meta.schema, start_at, Duration::SECOND, 1, 1);
+                chunk.push(vec![
+                    Some(Label::String("prod".into())),
+                    None,
+                    None,
+                    Some(Label::Int(3)),
+                ]).unwrap();
+                chunk.set(0, 0, start_at, Some(Field::Float64(1.0))).unwrap();
+                table.shards.get().borrow_mut().mutable.push(chunk);
+
+                let parser = Checker::new(db).layer(
+                    Parser::with_evaluation(Evaluation::instant(start_at + Duration::SECOND))
+                        .layer(()),
+                );
+                let mir = parser
+                    .apply(
+                        r#"clamp_min(label_join(foo.bar.something_used, "region", "-", "env", "shard"), 2)"#,
+                    )
+                    .unwrap();
+                let mut execution = plan(&mut Context::default(), mir).unwrap();
+                let records = execution
+                    .next(&mut ExecuteContext::new(256))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(records.labels.len(), 5);
+                assert_eq!(
+                    records.labels[4].get(0),
+                    Some(Label::String("prod-3".into()))
+                );
+                assert_eq!(records.fields[0].get_f64(0), Some(vec![Some(2.0)]));
+                assert!(records.meta.is_some());
+
+                let mir = parser
+                    .apply(r#"absent(foo.bar.something_used{env="dev"})"#)
+                    .unwrap();
+                let mut execution = plan(&mut Context::default(), mir).unwrap();
+                let records = execution
+                    .next(&mut ExecuteContext::new(256))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(records.labels[0].get(0), Some(Label::String("dev".into())));
+                assert_eq!(records.fields[0].get_f64(0), Some(vec![Some(1.0)]));
+
+                // the same series continues in a second chunk
+                let mut chunk = MutableChunk::new(
+                    &table.meta.schema,
+                    start_at + Duration::SECOND,
+                    Duration::SECOND,
+                    1,
+                    1,
+                );
+                chunk.push(vec![
+                    Some(Label::String("prod".into())),
+                    None,
+                    None,
+                    Some(Label::Int(3)),
+                ]).unwrap();
+                chunk.set(0, 0, start_at + Duration::SECOND, Some(Field::Float64(1.0))).unwrap();
+                table.shards.get().borrow_mut().mutable.push(chunk);
+                let mir = parser
+                    .apply("count_over_time(foo.bar.something_used[2s])")
+                    .unwrap();
+                let mut execution = plan(&mut Context::default(), mir).unwrap();
+                let records = execution
+                    .next(&mut ExecuteContext::new(256))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(records.len(), 1);
+                assert_eq!(records.fields[0].get_f64(0), Some(vec![Some(2.0)]));
+                assert!(execution.next(&mut ExecuteContext::new(256)).await.is_none());
+            });
+    }
 }
@@ -19,10 +19,11 @@ pub enum Error {
     NoColumn { name: String },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ScanPlanner {
     pub(crate) resource: Arc<Table>,
-    pub(crate) matcher: Vec<Option<MatcherOp>>,
+    pub(crate) matcher: Vec<Vec<MatcherOp>>,
+    pub(crate) predicate: Vec<Vec<MatcherOp<f64>>>,
     pub(crate) limit: Option<usize>,
     pub(crate) projection: Projection<usize>,
     pub(crate) range: Range,
@@ -38,20 +39,11 @@ impl Planner for ScanPlanner {
         for id in 0..executor::worker_num() {
             let mut context = Context::new(256);
             let send = send.clone();
-            let resource = self.resource.clone();
-            let projection = self.projection.clone();
-            let matcher = self.matcher.clone();
-            let range = self.range.clone();
+            let planner = self.clone();
             executor::spawn_to(id, move || async move {
+                let resource = planner.resource.clone();
                 let shards = resource.shards.get().borrow();
-                let mut worker = ScanWorker {
-                    iter: shards.mutable.iter(),
-                    projection,
-                    matcher,
-                    limit: self.limit,
-                    count: 0,
-                    range,
-                };
+                let mut worker = planner.worker(&shards.mutable);
                 while let Some(records) = worker.next(&mut context).await {
                     send.send(records).await.unwrap();
                 }
@@ -63,6 +55,21 @@ impl Planner for ScanPlanner {
     }
 }
 
+impl ScanPlanner {
+    #[inline]
+    pub(crate) fn worker(self, chunks: &[MutableChunk]) -> ScanWorker<'_> {
+        ScanWorker {
+            iter: chunks.iter(),
+            projection: self.projection,
+            matcher: self.matcher,
+            predicate: self.predicate,
+            limit: self.limit,
+            count: 0,
+            range: self.range,
+        }
+    }
+}
+
 pub type Scan = async_channel::Receiver<Result<Records, TableScanError>>;
 
 impl Execution for Scan {
@@ -78,26 +85,40 @@ impl Execution for Scan {
 pub struct ScanWorker<'chunks> {
     iter: std::slice::Iter<'chunks, MutableChunk>,
     projection: Projection,
-    matcher: Vec<Option<MatcherOp>>,
+    matcher: Vec<Vec<MatcherOp>>,
+    predicate: Vec<Vec<MatcherOp<f64>>>,
     limit: Option<usize>,
     count: usize,
     range: Range,
 }
 
 impl<'chunks> ScanWorker<'chunks> {
-    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, TableScanError>> {
+    pub(crate) async fn next(
+        &mut self,
+        cx: &mut Context,
+    ) -> Option<Result<Records, TableScanError>> {
         if let Some(limit) = self.limit {
             if self.count >= limit {
                 return None;
             }
         }
-        let chunk = self.iter.next()?;
-        let range = chunk.range() & self.range.clone();
-        if range.is_empty() {
-            return None;
+        let (chunk, range) = loop {
+            let chunk = self.iter.next()?;
+            let range = chunk.range() & self.range.clone();
+            if !range.is_empty() {
+                break (chunk, range);
+            }
+        };
+        let column = unsafe {
+            chunk.filter(
+                cx,
+                &self.matcher,
+                &self.predicate,
+                self.projection.as_ref(),
+                range,
+            )
         }
-        let column =
-            unsafe { chunk.filter(cx, &self.matcher, self.projection.as_ref(), range) }.await;
+        .await;
         match column {
             Ok(records) => {
                 self.count += records.len();
@@ -0,0 +1,378 @@
+use std::{
+    collections::{btree_map::Entry, hash_map::DefaultHasher, BTreeMap},
+    convert::Infallible,
+    hash::{Hash, Hasher},
+};
+
+use chunk::mutable::{Meta, MutableChunk, Records};
+use common::{
+    column::label::LabelValue,
+    context::Context,
+    scalar::Scalar,
+    schema::Label,
+    time::{Duration, Instant, Range},
+    try_yield, DynError, Set,
+};
+use resource::TableScanError;
+
+use super::{
+    binary::{records, Series},
+    function::{self, gather, Function, Math, Window},
+    plan::{scan_planner, Context as PlanContext},
+    Execution, ExecutionImpl, Planner,
+};
+use crate::{
+    evaluate::{is_stale, Steps},
+    plan::physical::{Physical, Scan},
+};
+
+// the time a selector reads at for every step, after @ and offset
+fn read_times(scan: &Scan, steps: &Steps) -> Vec<Instant> {
+    steps
+        .times()
+        .map(|time| {
+            let time = scan.at.unwrap_or(time);
+            match scan.offset {
+                Some(offset) => time - offset,
+                None => time,
+            }
+        })
+        .collect()
+}
+
+// a row a predicate leaves out no longer hides older samples of its series, so
+// the predicate only applies to a chunk if every step reading it reads nothing else
+fn confined(chunk: &MutableChunk, times: &[Instant], lookback: Duration) -> bool {
+    let (start, end) = (chunk.meta.start_at(), chunk.end_at());
+    times
+        .iter()
+        .filter(|time| **time >= start && **time - lookback < end)
+        .all(|time| *time - lookback >= start && *time < end)
+}
+
+// the latest sample of a row inside (time - lookback, time], staleness markers included
+fn latest(
+    meta: &Meta,
+    values: &[Option<f64>],
+    time: Instant,
+    lookback: Duration,
+) -> Option<(Instant, f64)> {
+    if time < meta.start_at() {
+        return None;
+    }
+    let last = ((time - meta.start_at()) / meta.unit()) as usize;
+    values[..values.len().min(last + 1)]
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(slot, value)| (meta.timestamp(slot), value))
+        .take_while(|(at, _)| *at > time - lookback)
+        .find_map(|(at, value)| Some((at, (*value)?)))
+}
+
+type Samples = Vec<(Vec<Option<LabelValue>>, Vec<Option<(Instant, f64)>>)>;
+type Merged = BTreeMap<Vec<Option<LabelValue>>, Vec<Option<(Instant, f64)>>>;
+
+#[derive(Debug, Clone)]
+pub struct SelectPlanner {
+    pub(crate) scan: Scan,
+    pub(crate) steps: Steps,
+    // the time a sample was written at in seconds instead of its value
+    pub(crate) timestamps: bool,
+}
+
+impl Planner for SelectPlanner {
+    type Execution = Selection;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, _: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Selection {
+            planner: Some(self),
+        })
+    }
+}
+
+// the newest sample of every series at every step, unless it is a staleness marker
+#[derive(Debug)]
+pub struct Selection {
+    planner: Option<SelectPlanner>,
+}
+
+impl Execution for Selection {
+    async fn next(&mut self, _: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        Some(planner.select().await)
+    }
+}
+
+// a series spread over chunks keeps the latest sample of every step
+fn merge(merged: &mut Merged, samples: Samples) {
+    for (labels, points) in samples {
+        match merged.entry(labels) {
+            Entry::Vacant(entry) => {
+                entry.insert(points);
+            }
+            Entry::Occupied(mut entry) => {
+                for (merged, point) in entry.get_mut().iter_mut().zip(points) {
+                    if point.map(|(at, _)| at) > merged.map(|(at, _)| at) {
+                        *merged = point;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl SelectPlanner {
+    pub(crate) async fn select(self) -> Result<Records, DynError> {
+        let tasks = (0..executor::worker_num())
+            .map(|id| {
+                let planner = self.clone();
+                executor::spawn_to(id, move || async move { planner.select_shard().await })
+            })
+            .collect::<Vec<_>>();
+
+        let mut merged = BTreeMap::new();
+        for task in tasks {
+            merge(&mut merged, task.await?);
+        }
+        Ok(self.records(merged))
+    }
+
+    // the samples of the shard of the current worker only, with the hashes of
+    // the label sets of every series it holds
+    pub(crate) async fn select_local(&self) -> Result<(Records, Vec<u64>), DynError> {
+        let mut merged = BTreeMap::new();
+        merge(&mut merged, self.select_shard().await?);
+        let hashes = merged
+            .keys()
+            .map(|labels| {
+                let mut hasher = DefaultHasher::new();
+                labels.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        Ok((self.records(merged), hashes))
+    }
+
+    #[allow(clippy::await_holding_refcell_ref)]
+    async fn select_shard(&self) -> Result<Samples, TableScanError> {
+        let (scan, steps) = (&self.scan, &self.steps);
+        let times = read_times(scan, steps);
+        let window = Range {
+            start: times.iter().min().map(|time| *time - steps.lookback),
+            end: times
+                .iter()
+                .max()
+                .map(|time| Instant::from_millis(time.as_millis() + 1)),
+        };
+        let labels = match &scan.projection.labels {
+            Set::Universe => (0..scan.resource.meta.schema.labels.len()).collect(),
+            Set::Some(ids) => ids.clone(),
+        };
+        let field = scan.field_id();
+        let lookback = steps.lookback;
+
+        let shard = scan.resource.shards.get().borrow();
+        let mut cx = Context::new(256);
+        let mut samples = Samples::new();
+        for chunk in shard.mutable.iter() {
+            if (chunk.range() & window.clone()).is_empty() {
+                continue;
+            }
+            let predicate: &[_] = if confined(chunk, &times, lookback) {
+                &scan.predicate
+            } else {
+                &[]
+            };
+            let rows = unsafe { chunk.filter_rows(&mut cx, &scan.matcher, predicate) }.await?;
+            for row in rows.iter().map(|row| row as usize) {
+                let Some(values) = chunk.records.fields[field].get_f64(row) else {
+                    continue;
+                };
+                samples.push((
+                    labels
+                        .iter()
+                        .map(|label| chunk.records.labels[*label].get(row))
+                        .collect(),
+                    times
+                        .iter()
+                        .map(|time| latest(&chunk.meta, &values, *time, lookback))
+                        .collect(),
+                ));
+                try_yield!(cx);
+            }
+        }
+        Ok(samples)
+    }
+
+    fn records(&self, merged: Merged) -> Records {
+        let rows = merged
+            .into_iter()
+            .map(|(labels, points)| Series {
+                labels,
+                values: points
+                    .into_iter()
+                    .map(|point| {
+                        point
+                            .filter(|(_, value)| !is_stale(*value))
+                            .map(|(at, value)| {
+                                if self.timestamps {
+                                    function::seconds(at)
+                                } else {
+                                    value
+                                }
+                            })
+                    })
+                    .collect(),
+            })
+            .collect();
+        records(
+            &Physical::Scan(self.scan.clone()).labels(),
+            rows,
+            Some(self.steps.meta()),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct SlidePlanner {
+    pub(crate) function: Function,
+    pub(crate) scan: Scan,
+    pub(crate) steps: Steps,
+    pub(crate) labels: Vec<Label>,
+}
+
+impl Planner for SlidePlanner {
+    type Execution = Sliding;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, _: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Sliding {
+            planner: Some(self),
+        })
+    }
+}
+
+// slides the range of the selector over the steps, every window leaves one slot
+// in the values of a series
+#[derive(Debug)]
+pub struct Sliding {
+    planner: Option<SlidePlanner>,
+}
+
+impl Execution for Sliding {
+    async fn next(&mut self, cx: &mut Context) -> Option<Result<Records, DynError>> {
+        let planner = self.planner.take()?;
+        Some(planner.slide(cx).await)
+    }
+}
+
+impl SlidePlanner {
+    async fn slide(self, cx: &mut Context) -> Result<Records, DynError> {
+        // the checker only lets range selectors into function calls
+        let (Some(start), Some(end)) = (self.scan.range.start, self.scan.range.end) else {
+            unreachable!()
+        };
+        let range = end - start;
+        let windows = read_times(&self.scan, &self.steps)
+            .into_iter()
+            .zip(self.steps.times())
+            .map(|(end, time)| Window {
+                start: end - range,
+                end,
+                time,
+            })
+            .collect::<Vec<_>>();
+        let (Some(first), Some(last)) = (
+            windows.iter().map(|window| window.start).min(),
+            windows.iter().map(|window| window.end).max(),
+        ) else {
+            unreachable!()
+        };
+
+        // the scan range includes its start, the window does not
+        let field = Physical::Scan(self.scan.clone()).field();
+        let scan = Scan {
+            range: Range {
+                start: Some(Instant::from_millis(first.as_millis() + 1)),
+                end: Some(Instant::from_millis(last.as_millis() + 1)),
+            },
+            ..self.scan
+        };
+        let mut input = ExecutionImpl::Scan(
+            scan_planner(&mut PlanContext::default(), scan).plan(ExecutionImpl::Id(()))?,
+        );
+        let (_, gathered) = gather(
+            cx,
+            &mut input,
+            field,
+            &Window {
+                start: first,
+                end: last,
+                time: last,
+            },
+        )
+        .await?;
+
+        let rows = gathered
+            .into_iter()
+            .map(|(labels, samples)| {
+                let buffer = function::buffer(&samples);
+                Series {
+                    labels,
+                    values: windows
+                        .iter()
+                        .map(|window| {
+                            let start = samples.partition_point(|(at, _)| *at <= window.start);
+                            let end = samples.partition_point(|(at, _)| *at <= window.end);
+                            self.function
+                                .fold(&samples, buffer.as_ref(), start..end, window)
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(records(&self.labels, rows, Some(self.steps.meta())))
+    }
+}
+
+#[derive(Debug)]
+pub struct ClockPlanner {
+    pub(crate) math: Math,
+    pub(crate) steps: Steps,
+}
+
+impl Planner for ClockPlanner {
+    type Execution = Clock;
+    type Error = Infallible;
+
+    #[inline]
+    fn plan(self, _: ExecutionImpl) -> Result<Self::Execution, Self::Error> {
+        Ok(Clock {
+            planner: Some(self),
+        })
+    }
+}
+
+// date functions called without a vector read the time of every step
+#[derive(Debug)]
+pub struct Clock {
+    planner: Option<ClockPlanner>,
+}
+
+impl Execution for Clock {
+    async fn next(&mut self, _: &mut Context) -> Option<Result<Records, DynError>> {
+        let ClockPlanner { math, steps } = self.planner.take()?;
+        let series = Series {
+            labels: vec![],
+            values: steps
+                .times()
+                .map(|time| math.apply(function::seconds(time)))
+                .collect(),
+        };
+        Some(Ok(records(&[], vec![series], Some(steps.meta()))))
+    }
+}
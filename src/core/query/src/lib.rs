@@ -11,7 +11,9 @@ use parse::Parser;
 use resource::db::DB;
 
 pub mod check;
+pub mod evaluate;
 pub mod execute;
+pub mod metadata;
 pub mod parse;
 pub mod plan;
 
@@ -47,6 +49,6 @@ mod tests {
 
     #[test]
     fn make_passes() {
-        let _ = Parser {}.layer(());
+        let _ = Parser::new().layer(());
     }
 }
@@ -0,0 +1,297 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use chunk::mutable::{column::FilterError, MutableChunk};
+use common::{
+    column::label::{Label, LabelValue},
+    context::Context,
+    query::MatcherOp,
+    time::Range,
+};
+use resource::{db::DB, table::Table, TableScanError};
+
+use crate::plan::physical::Scan;
+
+pub const NAME_LABEL: &str = "__name__";
+
+#[derive(Debug, Clone)]
+pub struct Selector {
+    pub resource: Arc<Table>,
+    pub matcher: Vec<Vec<MatcherOp>>,
+    pub range: Range,
+}
+
+impl Selector {
+    #[inline]
+    pub fn all(resource: Arc<Table>, range: Range) -> Self {
+        let matcher = vec![Vec::new(); resource.meta.schema.labels.len()];
+        Self {
+            resource,
+            matcher,
+            range,
+        }
+    }
+
+    #[inline]
+    pub fn every(db: &DB, range: Range) -> Vec<Self> {
+        db.tables()
+            .iter()
+            .map(|table| Self::all(table.clone(), range.clone()))
+            .collect()
+    }
+}
+
+impl From<Scan> for Selector {
+    #[inline]
+    fn from(scan: Scan) -> Self {
+        Self {
+            resource: scan.resource,
+            matcher: scan.matcher,
+            range: scan.range,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Series {
+    pub name: Arc<str>,
+    pub labels: Vec<(String, LabelValue)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Request {
+    Names,
+    Values(usize),
+    Series,
+}
+
+#[derive(Debug)]
+enum Response {
+    Names(Option<Vec<usize>>),
+    Values(Vec<LabelValue>),
+    Series(Vec<Vec<Option<LabelValue>>>),
+}
+
+impl Request {
+    async fn apply(
+        self,
+        cx: &mut Context,
+        chunk: &MutableChunk,
+        matcher: &[Vec<MatcherOp>],
+    ) -> Result<Response, FilterError> {
+        unsafe {
+            match self {
+                Request::Names => chunk.label_names(cx, matcher).await.map(Response::Names),
+                Request::Values(label) => chunk
+                    .label_values(cx, matcher, label)
+                    .await
+                    .map(Response::Values),
+                Request::Series => chunk.series(cx, matcher).await.map(Response::Series),
+            }
+        }
+    }
+}
+
+#[allow(clippy::await_holding_refcell_ref)]
+async fn scan(selector: &Selector, request: Request) -> Result<Vec<Response>, TableScanError> {
+    let mut tasks = Vec::with_capacity(executor::worker_num());
+    for id in 0..executor::worker_num() {
+        let resource = selector.resource.clone();
+        let matcher = selector.matcher.clone();
+        let range = selector.range.clone();
+        tasks.push(executor::spawn_to(id, move || async move {
+            let shard = resource.shards.get().borrow();
+            let mut cx = Context::new(256);
+            let mut responses = Vec::new();
+            for chunk in shard.mutable.iter() {
+                if (chunk.range() & range.clone()).is_empty() {
+                    continue;
+                }
+                responses.push(request.apply(&mut cx, chunk, &matcher).await?);
+            }
+            Ok::<_, TableScanError>(responses)
+        }));
+    }
+
+    let mut responses = Vec::new();
+    for task in tasks {
+        responses.extend(task.await?);
+    }
+    Ok(responses)
+}
+
+pub async fn label_names(selectors: &[Selector]) -> Result<Vec<String>, TableScanError> {
+    let mut names = BTreeSet::new();
+    for selector in selectors {
+        let labels = &selector.resource.meta.schema.labels;
+        for response in scan(selector, Request::Names).await? {
+            if let Response::Names(Some(positions)) = response {
+                names.insert(NAME_LABEL.to_owned());
+                names.extend(positions.into_iter().map(|p| labels[p].name.clone()));
+            }
+        }
+    }
+    Ok(names.into_iter().collect())
+}
+
+pub async fn label_values(
+    selectors: &[Selector],
+    name: &str,
+) -> Result<Vec<LabelValue>, TableScanError> {
+    let mut values = BTreeSet::new();
+    for selector in selectors {
+        if name == NAME_LABEL {
+            let matched = scan(selector, Request::Names)
+                .await?
+                .into_iter()
+                .any(|response| matches!(response, Response::Names(Some(_))));
+            if matched {
+                values.insert(Label::String(Vec::from(selector.resource.name.as_bytes())));
+            }
+            continue;
+        }
+
+        let Some(label) = selector
+            .resource
+            .meta
+            .schema
+            .labels
+            .iter()
+            .position(|label| label.name == name)
+        else {
+            continue;
+        };
+        for response in scan(selector, Request::Values(label)).await? {
+            if let Response::Values(matched) = response {
+                values.extend(matched);
+            }
+        }
+    }
+    Ok(values.into_iter().collect())
+}
+
+pub async fn series(selectors: &[Selector]) -> Result<Vec<Series>, TableScanError> {
+    let mut series = BTreeSet::new();
+    for selector in selectors {
+        let labels = &selector.resource.meta.schema.labels;
+        for response in scan(selector, Request::Series).await? {
+            if let Response::Series(rows) = response {
+                series.extend(rows.into_iter().map(|row| {
+                    Series {
+                        name: selector.resource.name.clone(),
+                        labels: labels
+                            .iter()
+                            .zip(row)
+                            .filter_map(|(label, value)| Some((label.name.clone(), value?)))
+                            .collect(),
+                    }
+                }));
+            }
+        }
+    }
+    Ok(series.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chunk::mutable::MutableChunk;
+    use common::{
+        column::label::{Label, LabelValue},
+        time::{Duration, Instant, Range},
+    };
+    use resource::db::tests::test_db;
+
+    use super::{label_names, label_values, series, Selector, NAME_LABEL};
+    use crate::{check::Checker, parse::Parser, plan::physical::Physical, Layer, Pass};
+
+    fn string(value: &str) -> LabelValue {
+        Label::String(Vec::from(value))
+    }
+
+    #[test]
+    fn metadata() {
+        executor::ExecutorBuilder::new()
+            .worker_num(1)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+
+                let start_at = Instant::now() - Duration::from_secs(10);
+                let mut chunk =
+                    MutableChunk::new(&table.meta.schema, start_at, Duration::SECOND, 1, 1);
+                chunk
+                    .push(vec![Some(string("prod")), Some(string("200")), None, None])
+                    .unwrap();
+                chunk
+                    .push(vec![Some(string("prod")), Some(string("500")), None, None])
+                    .unwrap();
+                chunk
+                    .push(vec![Some(string("dev")), None, None, Some(Label::Int(1))])
+                    .unwrap();
+                table.shards.get().borrow_mut().mutable.push(chunk);
+
+                let all = Selector::every(
+                    &db.read().unwrap(),
+                    Range {
+                        start: None,
+                        end: None,
+                    },
+                );
+                assert_eq!(
+                    label_names(&all).await.unwrap(),
+                    vec![NAME_LABEL, "env", "shard", "status"]
+                );
+                assert_eq!(
+                    label_values(&all, "env").await.unwrap(),
+                    vec![string("dev"), string("prod")]
+                );
+                assert_eq!(
+                    label_values(&all, NAME_LABEL).await.unwrap(),
+                    vec![string("foo.bar.something_used")]
+                );
+                assert!(label_values(&all, "unknown").await.unwrap().is_empty());
+
+                let outdated = vec![Selector::all(
+                    table.clone(),
+                    Range {
+                        start: Some(start_at + Duration::from_secs(60)),
+                        end: None,
+                    },
+                )];
+                assert!(label_names(&outdated).await.unwrap().is_empty());
+
+                let parser = Checker::new(db.clone()).layer(Parser::new().layer(()));
+                let selector = match parser
+                    .apply(r#"foo.bar.something_used{env="prod"}"#)
+                    .unwrap()
+                {
+                    Physical::Scan(scan) => Selector::from(scan),
+                    _ => unreachable!(),
+                };
+                let selectors = vec![selector];
+                assert_eq!(
+                    label_names(&selectors).await.unwrap(),
+                    vec![NAME_LABEL, "env", "status"]
+                );
+                assert_eq!(
+                    label_values(&selectors, "status").await.unwrap(),
+                    vec![string("200"), string("500")]
+                );
+                let matched = series(&selectors).await.unwrap();
+                assert_eq!(matched.len(), 2);
+                assert_eq!(
+                    matched[0].labels,
+                    vec![
+                        ("env".to_owned(), string("prod")),
+                        ("status".to_owned(), string("200"))
+                    ]
+                );
+            });
+    }
+}
@@ -0,0 +1,110 @@
+use common::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Op {
+    Pow(Option<OpMod>),
+    Mul(Option<OpMod>),
+    Div(Option<OpMod>),
+    Mod(Option<OpMod>),
+    Plus(Option<OpMod>),
+    Minus(Option<OpMod>),
+    // comparisons carry the `bool` modifier
+    Eq(bool, Option<OpMod>),
+    Ne(bool, Option<OpMod>),
+    Lt(bool, Option<OpMod>),
+    Gt(bool, Option<OpMod>),
+    Le(bool, Option<OpMod>),
+    Ge(bool, Option<OpMod>),
+    And(Option<OpMod>),
+    Unless(Option<OpMod>),
+    Or(Option<OpMod>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum OpModAction {
+    RestrictTo,
+    Ignore,
+}
+
+// `on (...)` or `ignoring (...)` after a binary operator
+#[derive(Debug, PartialEq)]
+pub(crate) struct OpMod {
+    pub(crate) action: OpModAction,
+    pub(crate) labels: Vec<String>,
+    pub(crate) group: Option<OpGroupMod>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum OpGroupSide {
+    Left,
+    Right,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct OpGroupMod {
+    pub(crate) side: OpGroupSide,
+    pub(crate) labels: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum AggregationAction {
+    Without,
+    By,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct AggregationMod {
+    pub(crate) action: AggregationAction,
+    pub(crate) labels: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum LabelMatchOp {
+    Eq,
+    Ne,
+    REq,
+    RNe,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct LabelMatch {
+    pub(crate) name: String,
+    pub(crate) op: LabelMatchOp,
+    pub(crate) value: String,
+}
+
+// the `@` modifier, `start()` and `end()` resolve against the evaluation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum At {
+    Timestamp(Instant),
+    Start,
+    End,
+}
+
+// the metric name is kept as a `__name__` matcher in front of the others
+#[derive(Debug, PartialEq)]
+pub(crate) struct Vector {
+    pub(crate) labels: Vec<LabelMatch>,
+    pub(crate) range: Option<Duration>,
+    pub(crate) offset: Option<Duration>,
+    pub(crate) at: Option<At>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Node {
+    Operator {
+        x: Box<Node>,
+        op: Op,
+        y: Box<Node>,
+    },
+    Vector(Vector),
+    Scalar(f64),
+    String(String),
+    // aggregations are calls with an optional `by (...)` or `without (...)`
+    Function {
+        name: String,
+        args: Vec<Node>,
+        aggregation: Option<AggregationMod>,
+    },
+    Negation(Box<Node>),
+}
@@ -0,0 +1,760 @@
+use common::time::{Duration, Instant};
+
+use super::{
+    ast::{
+        AggregationAction, AggregationMod, At, LabelMatch, LabelMatchOp, Node, Op, OpGroupMod,
+        OpGroupSide, OpMod, OpModAction, Vector,
+    },
+    Error,
+};
+
+const POWER: u8 = 6;
+
+struct Operator {
+    token: &'static str,
+    precedence: u8,
+    // only comparisons take the `bool` modifier
+    comparison: bool,
+    build: fn(bool, Option<OpMod>) -> Op,
+}
+
+// longer tokens go first so `<=` is not read as `<`
+const OPERATORS: &[Operator] = &[
+    Operator {
+        token: "^",
+        precedence: POWER,
+        comparison: false,
+        build: |_, m| Op::Pow(m),
+    },
+    Operator {
+        token: "*",
+        precedence: 5,
+        comparison: false,
+        build: |_, m| Op::Mul(m),
+    },
+    Operator {
+        token: "/",
+        precedence: 5,
+        comparison: false,
+        build: |_, m| Op::Div(m),
+    },
+    Operator {
+        token: "%",
+        precedence: 5,
+        comparison: false,
+        build: |_, m| Op::Mod(m),
+    },
+    Operator {
+        token: "+",
+        precedence: 4,
+        comparison: false,
+        build: |_, m| Op::Plus(m),
+    },
+    Operator {
+        token: "-",
+        precedence: 4,
+        comparison: false,
+        build: |_, m| Op::Minus(m),
+    },
+    Operator {
+        token: "==",
+        precedence: 3,
+        comparison: true,
+        build: Op::Eq,
+    },
+    Operator {
+        token: "!=",
+        precedence: 3,
+        comparison: true,
+        build: Op::Ne,
+    },
+    Operator {
+        token: "<=",
+        precedence: 3,
+        comparison: true,
+        build: Op::Le,
+    },
+    Operator {
+        token: ">=",
+        precedence: 3,
+        comparison: true,
+        build: Op::Ge,
+    },
+    Operator {
+        token: "<",
+        precedence: 3,
+        comparison: true,
+        build: Op::Lt,
+    },
+    Operator {
+        token: ">",
+        precedence: 3,
+        comparison: true,
+        build: Op::Gt,
+    },
+    Operator {
+        token: "and",
+        precedence: 2,
+        comparison: false,
+        build: |_, m| Op::And(m),
+    },
+    Operator {
+        token: "unless",
+        precedence: 2,
+        comparison: false,
+        build: |_, m| Op::Unless(m),
+    },
+    Operator {
+        token: "or",
+        precedence: 1,
+        comparison: false,
+        build: |_, m| Op::Or(m),
+    },
+];
+
+const UNITS: &[(&str, i64)] = &[
+    ("ms", 1),
+    ("s", 1_000),
+    ("m", 60_000),
+    ("h", 3_600_000),
+    ("d", 86_400_000),
+    ("w", 604_800_000),
+    ("y", 31_536_000_000),
+];
+
+// metric names may contain periods, like `foo.bar`
+#[inline]
+fn is_name(byte: u8, first: bool) -> bool {
+    byte.is_ascii_alphabetic()
+        || matches!(byte, b'_' | b':')
+        || (!first && (byte.is_ascii_digit() || byte == b'.'))
+}
+
+// the escape sequence after a backslash and its length, following the rules of Go
+fn escape(bytes: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let radix = |range: std::ops::Range<usize>, radix: u32| {
+        let digits = std::str::from_utf8(bytes.get(range)?).ok()?;
+        u32::from_str_radix(digits, radix).ok()
+    };
+    let unicode = |value: u32| Some(char::from_u32(value)?.to_string().into_bytes());
+    Some(match bytes.first()? {
+        b'a' => (vec![0x07], 1),
+        b'b' => (vec![0x08], 1),
+        b'f' => (vec![0x0c], 1),
+        b'n' => (vec![b'\n'], 1),
+        b'r' => (vec![b'\r'], 1),
+        b't' => (vec![b'\t'], 1),
+        b'v' => (vec![0x0b], 1),
+        byte @ (b'\\' | b'\'' | b'"') => (vec![*byte], 1),
+        b'0'..=b'7' => (vec![u8::try_from(radix(0..3, 8)?).ok()?], 3),
+        b'x' => (vec![radix(1..3, 16)? as u8], 3),
+        b'u' => (unicode(radix(1..5, 16)?)?, 5),
+        b'U' => (unicode(radix(1..9, 16)?)?, 9),
+        _ => return None,
+    })
+}
+
+struct Grammar<'a> {
+    literal: &'a str,
+    pos: usize,
+}
+
+pub(crate) fn parse(literal: &str) -> Result<Node, Error> {
+    let mut grammar = Grammar { literal, pos: 0 };
+    let expr = grammar.expression(0)?;
+    if grammar.peek().is_some() {
+        return Err(grammar.error("unexpected input"));
+    }
+    Ok(expr)
+}
+
+impl<'a> Grammar<'a> {
+    fn error(&self, message: &str) -> Error {
+        Error::ParsingWrong {
+            err: format!("{} at position {}", message, self.pos),
+        }
+    }
+
+    // whitespace and comments between tokens
+    fn skip(&mut self) {
+        let bytes = self.literal.as_bytes();
+        while let Some(byte) = bytes.get(self.pos) {
+            match byte {
+                b'#' => {
+                    while bytes.get(self.pos).is_some_and(|byte| *byte != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip();
+        self.literal.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip();
+        if self.literal[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), Error> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", token)))
+        }
+    }
+
+    fn peek_word(&mut self) -> Option<&'a str> {
+        self.skip();
+        let rest = &self.literal.as_bytes()[self.pos..];
+        if !rest.first().is_some_and(|byte| is_name(*byte, true)) {
+            return None;
+        }
+        let len = rest
+            .iter()
+            .position(|byte| !is_name(*byte, false))
+            .unwrap_or(rest.len());
+        Some(&self.literal[self.pos..self.pos + len])
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_word() == Some(keyword) {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let name = self
+            .peek_word()
+            .ok_or_else(|| self.error("expected a name"))?;
+        self.pos += name.len();
+        Ok(name.into())
+    }
+
+    fn expression(&mut self, min: u8) -> Result<Node, Error> {
+        let mut lhs = self.unary()?;
+        while let Some(operator) = self.operator().filter(|op| op.precedence >= min) {
+            self.pos += operator.token.len();
+            let boolean = operator.comparison && self.keyword("bool");
+            let op = (operator.build)(boolean, self.matching()?);
+            // `^` is the only right associative operator
+            let next = if operator.precedence == POWER {
+                POWER
+            } else {
+                operator.precedence + 1
+            };
+            let rhs = self.expression(next)?;
+            lhs = Node::Operator {
+                x: Box::new(lhs),
+                op,
+                y: Box::new(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn operator(&mut self) -> Option<&'static Operator> {
+        let word = self.peek_word();
+        let rest = &self.literal[self.pos..];
+        OPERATORS.iter().find(|operator| {
+            if operator.token.as_bytes()[0].is_ascii_alphabetic() {
+                word == Some(operator.token)
+            } else {
+                rest.starts_with(operator.token)
+            }
+        })
+    }
+
+    fn matching(&mut self) -> Result<Option<OpMod>, Error> {
+        let action = if self.keyword("on") {
+            OpModAction::RestrictTo
+        } else if self.keyword("ignoring") {
+            OpModAction::Ignore
+        } else {
+            return Ok(None);
+        };
+        let labels = self.label_list()?;
+        let side = if self.keyword("group_left") {
+            OpGroupSide::Left
+        } else if self.keyword("group_right") {
+            OpGroupSide::Right
+        } else {
+            return Ok(Some(OpMod {
+                action,
+                labels,
+                group: None,
+            }));
+        };
+        let group = OpGroupMod {
+            side,
+            labels: match self.peek() {
+                Some(b'(') => self.label_list()?,
+                _ => vec![],
+            },
+        };
+        Ok(Some(OpMod {
+            action,
+            labels,
+            group: Some(group),
+        }))
+    }
+
+    // unary operators bind looser than `^`, so `-2 ^ 2` is -4
+    fn unary(&mut self) -> Result<Node, Error> {
+        if self.eat("-") {
+            Ok(Node::Negation(Box::new(self.expression(POWER)?)))
+        } else if self.eat("+") {
+            self.expression(POWER)
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, Error> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let expr = self.expression(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(b'"' | b'\'' | b'`') => Ok(Node::String(self.string()?)),
+            Some(b'{') => self.vector(None),
+            Some(byte) if byte.is_ascii_digit() || byte == b'.' => self.number(),
+            Some(_) => {
+                let name = self.name()?;
+                if name.eq_ignore_ascii_case("inf") || name.eq_ignore_ascii_case("nan") {
+                    return Ok(Node::Scalar(name.parse().unwrap()));
+                }
+                match self.peek_word() {
+                    Some("by" | "without") => self.function(name),
+                    _ if self.peek() == Some(b'(') => self.function(name),
+                    _ => self.vector(Some(name)),
+                }
+            }
+            None => Err(self.error("unexpected end of query")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Node, Error> {
+        let rest = &self.literal.as_bytes()[self.pos..];
+        let digits = |from: usize, hex: bool| {
+            rest[from..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit() || (hex && byte.is_ascii_hexdigit()))
+                .count()
+        };
+
+        let value = if rest.starts_with(b"0x") || rest.starts_with(b"0X") {
+            let len = 2 + digits(2, true);
+            let value = u64::from_str_radix(&self.literal[self.pos + 2..self.pos + len], 16)
+                .map(|value| (value as f64, len));
+            value.ok()
+        } else {
+            let mut len = digits(0, false);
+            if rest.get(len) == Some(&b'.') {
+                len += 1 + digits(len + 1, false);
+            }
+            if matches!(rest.get(len), Some(b'e' | b'E')) {
+                let sign = matches!(rest.get(len + 1), Some(b'+' | b'-')) as usize;
+                let exponent = digits(len + 1 + sign, false);
+                if exponent > 0 {
+                    len += 1 + sign + exponent;
+                }
+            }
+            // the text itself is parsed, nothing goes through a narrower float
+            let text = &self.literal[self.pos..self.pos + len];
+            text.parse::<f64>().ok().map(|value| (value, len))
+        };
+        let (value, len) = value.ok_or_else(|| self.error("invalid number"))?;
+        self.pos += len;
+        Ok(Node::Scalar(value))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let bytes = self.literal.as_bytes();
+        let quote = match self.peek() {
+            Some(quote @ (b'"' | b'\'' | b'`')) => quote,
+            _ => return Err(self.error("expected a string")),
+        };
+        let mut pos = self.pos + 1;
+        let mut value = Vec::new();
+        loop {
+            let Some(byte) = bytes.get(pos).copied() else {
+                return Err(self.error("unterminated string"));
+            };
+            pos += 1;
+            match byte {
+                _ if byte == quote => break,
+                // raw strings keep backslashes and newlines
+                b'\\' if quote != b'`' => {
+                    let (escaped, len) = escape(&bytes[pos..])
+                        .ok_or_else(|| self.error("invalid escape sequence in string"))?;
+                    value.extend(escaped);
+                    pos += len;
+                }
+                b'\n' if quote != b'`' => return Err(self.error("unterminated string")),
+                _ => value.push(byte),
+            }
+        }
+        let value = String::from_utf8(value).map_err(|_| self.error("invalid utf-8 string"))?;
+        self.pos = pos;
+        Ok(value)
+    }
+
+    fn duration(&mut self) -> Result<Duration, Error> {
+        self.skip();
+        let bytes = self.literal.as_bytes();
+        let mut millis = 0i64;
+        let mut parts = 0;
+        while bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            let len = bytes[self.pos..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count();
+            let value = self.literal[self.pos..self.pos + len]
+                .parse::<i64>()
+                .map_err(|_| self.error("invalid duration"))?;
+            self.pos += len;
+            let rest = &self.literal[self.pos..];
+            let (unit, scale) = UNITS
+                .iter()
+                .find(|(unit, _)| rest.starts_with(unit))
+                .ok_or_else(|| self.error("invalid duration unit"))?;
+            self.pos += unit.len();
+            millis = value
+                .checked_mul(*scale)
+                .and_then(|value| millis.checked_add(value))
+                .ok_or_else(|| self.error("duration out of range"))?;
+            parts += 1;
+        }
+        if parts == 0 {
+            return Err(self.error("expected a duration"));
+        }
+        Ok(Duration::from_millis(millis))
+    }
+
+    fn label_list(&mut self) -> Result<Vec<String>, Error> {
+        self.expect("(")?;
+        let mut labels = Vec::new();
+        while !self.eat(")") {
+            labels.push(self.name()?);
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(labels)
+    }
+
+    fn aggregation(&mut self) -> Result<Option<AggregationMod>, Error> {
+        let action = if self.keyword("by") {
+            AggregationAction::By
+        } else if self.keyword("without") {
+            AggregationAction::Without
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(AggregationMod {
+            action,
+            labels: self.label_list()?,
+        }))
+    }
+
+    // both `sum by (env) (foo)` and `sum(foo) by (env)` are accepted
+    fn function(&mut self, name: String) -> Result<Node, Error> {
+        let before = self.aggregation()?;
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while !self.eat(")") {
+            args.push(self.expression(0)?);
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        let aggregation = match before {
+            Some(aggregation) => Some(aggregation),
+            None => self.aggregation()?,
+        };
+        Ok(Node::Function {
+            name,
+            args,
+            aggregation,
+        })
+    }
+
+    fn vector(&mut self, name: Option<String>) -> Result<Node, Error> {
+        let mut labels = Vec::new();
+        if let Some(name) = name {
+            labels.push(LabelMatch {
+                name: "__name__".into(),
+                op: LabelMatchOp::Eq,
+                value: name,
+            });
+        }
+        if self.eat("{") {
+            while !self.eat("}") {
+                let name = self.name()?;
+                let op = if self.eat("=~") {
+                    LabelMatchOp::REq
+                } else if self.eat("!~") {
+                    LabelMatchOp::RNe
+                } else if self.eat("!=") {
+                    LabelMatchOp::Ne
+                } else if self.eat("=") {
+                    LabelMatchOp::Eq
+                } else {
+                    return Err(self.error("expected a label matching operator"));
+                };
+                let value = self.string()?;
+                labels.push(LabelMatch { name, op, value });
+                if !self.eat(",") {
+                    self.expect("}")?;
+                    break;
+                }
+            }
+        }
+        if labels.is_empty() {
+            return Err(self.error("vector selector must contain label matchers or metric name"));
+        }
+
+        let range = if self.eat("[") {
+            let range = self.duration()?;
+            self.expect("]")?;
+            Some(range)
+        } else {
+            None
+        };
+        // `offset` and `@` follow in either order, each at most once
+        let (mut offset, mut at) = (None, None);
+        loop {
+            if offset.is_none() && self.keyword("offset") {
+                offset = Some(self.duration()?);
+            } else if at.is_none() && self.eat("@") {
+                at = Some(self.at()?);
+            } else {
+                break;
+            }
+        }
+        Ok(Node::Vector(Vector {
+            labels,
+            range,
+            offset,
+            at,
+        }))
+    }
+
+    fn at(&mut self) -> Result<At, Error> {
+        self.skip();
+        let invalid = |grammar: &Self| {
+            let rest = &grammar.literal[grammar.pos..];
+            Error::InvalidModifier(rest.chars().take(16).collect())
+        };
+        for (name, at) in [("start", At::Start), ("end", At::End)] {
+            if self.keyword(name) {
+                if !(self.eat("(") && self.eat(")")) {
+                    return Err(invalid(self));
+                }
+                return Ok(at);
+            }
+        }
+
+        let negative = self.eat("-");
+        if !negative {
+            self.eat("+");
+        }
+        let start = self.pos;
+        let seconds = match self.peek() {
+            Some(byte) if byte.is_ascii_digit() || byte == b'.' => self.number().ok(),
+            _ => None,
+        };
+        match seconds {
+            Some(Node::Scalar(seconds)) if seconds.is_finite() => {
+                let seconds = if negative { -seconds } else { seconds };
+                Ok(At::Timestamp(Instant::from_millis(
+                    (seconds * 1000.0).round() as i64,
+                )))
+            }
+            _ => {
+                self.pos = start;
+                Err(invalid(self))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::time::{Duration, Instant};
+
+    use super::parse;
+    use crate::parse::{
+        ast::{At, LabelMatch, LabelMatchOp, Node, Op, Vector},
+        Error,
+    };
+
+    fn scalar(value: f64) -> Node {
+        Node::Scalar(value)
+    }
+
+    fn operator(x: Node, op: Op, y: Node) -> Node {
+        Node::Operator {
+            x: Box::new(x),
+            op,
+            y: Box::new(y),
+        }
+    }
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(parse("0.1").unwrap(), scalar(0.1));
+        assert_eq!(
+            parse("0.1 + 0.2").unwrap(),
+            operator(scalar(0.1), Op::Plus(None), scalar(0.2))
+        );
+        assert_eq!(parse("16777217").unwrap(), scalar(16777217.0));
+        assert_eq!(parse("123456789.123").unwrap(), scalar(123456789.123));
+        assert_eq!(parse("1e-3").unwrap(), scalar(0.001));
+        assert_eq!(parse(".5E2").unwrap(), scalar(50.0));
+        assert_eq!(parse("0x1F").unwrap(), scalar(31.0));
+        assert_eq!(parse("Inf").unwrap(), scalar(f64::INFINITY));
+        assert!(matches!(parse("nan").unwrap(), Node::Scalar(value) if value.is_nan()));
+        assert!(parse("1.2.3").is_err());
+    }
+
+    #[test]
+    fn parse_precedence() {
+        assert_eq!(
+            parse("-2 ^ 2").unwrap(),
+            Node::Negation(Box::new(operator(scalar(2.0), Op::Pow(None), scalar(2.0))))
+        );
+        assert_eq!(
+            parse("2 ^ 3 ^ 2").unwrap(),
+            operator(
+                scalar(2.0),
+                Op::Pow(None),
+                operator(scalar(3.0), Op::Pow(None), scalar(2.0))
+            )
+        );
+        assert_eq!(
+            parse("1 - 2 - 3 * 4").unwrap(),
+            operator(
+                operator(scalar(1.0), Op::Minus(None), scalar(2.0)),
+                Op::Minus(None),
+                operator(scalar(3.0), Op::Mul(None), scalar(4.0))
+            )
+        );
+        assert!(matches!(
+            parse("a > bool 1 or b and c").unwrap(),
+            Node::Operator {
+                op: Op::Or(None),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_selectors() {
+        assert_eq!(
+            parse(
+                r#"foo.bar{env="a\"b", path=~'\\d+', raw!=`\n`,} [1h30m] offset 500ms # comment"#
+            )
+            .unwrap(),
+            Node::Vector(Vector {
+                labels: vec![
+                    LabelMatch {
+                        name: "__name__".into(),
+                        op: LabelMatchOp::Eq,
+                        value: "foo.bar".into(),
+                    },
+                    LabelMatch {
+                        name: "env".into(),
+                        op: LabelMatchOp::Eq,
+                        value: "a\"b".into(),
+                    },
+                    LabelMatch {
+                        name: "path".into(),
+                        op: LabelMatchOp::REq,
+                        value: "\\d+".into(),
+                    },
+                    LabelMatch {
+                        name: "raw".into(),
+                        op: LabelMatchOp::Ne,
+                        value: "\\n".into(),
+                    },
+                ],
+                range: Some(Duration::from_secs(5400)),
+                offset: Some(Duration::from_millis(500)),
+                at: None,
+            })
+        );
+        assert!(matches!(
+            parse("sum by (env,) (rate(foo[5m]))").unwrap(),
+            Node::Function {
+                aggregation: Some(_),
+                ..
+            }
+        ));
+        assert!(parse("{}").is_err());
+        assert!(parse(r#"foo{env="a}"#).is_err());
+        assert!(parse("foo[5x]").is_err());
+        assert!(parse("foo bar").is_err());
+    }
+
+    #[test]
+    fn parse_at_modifier() {
+        let at = |query: &str| match parse(query).unwrap() {
+            Node::Vector(vector) => (vector.offset, vector.at),
+            Node::Function { mut args, .. } => match args.remove(0) {
+                Node::Vector(vector) => (vector.offset, vector.at),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let timestamp = |millis: i64| Some(At::Timestamp(Instant::from_millis(millis)));
+
+        assert_eq!(at("foo @ 1609746000"), (None, timestamp(1609746000000)));
+        assert_eq!(at("foo @ -1.5"), (None, timestamp(-1500)));
+        assert_eq!(
+            at("rate(foo[5m] offset 1h @ end())"),
+            (Some(Duration::from_secs(3600)), Some(At::End))
+        );
+        assert_eq!(
+            at("foo{} @ start ( ) offset 5m"),
+            (Some(Duration::from_secs(300)), Some(At::Start))
+        );
+
+        // `@` in a string is only a character, `__at__` is only a label
+        match parse(r#"foo{env="a@b", __at__="0"} @ 100"#).unwrap() {
+            Node::Vector(vector) => {
+                assert_eq!(vector.labels[1].value, "a@b");
+                assert_eq!(vector.labels[2].name, "__at__");
+                assert_eq!(vector.at, timestamp(100000));
+            }
+            _ => unreachable!(),
+        }
+
+        assert!(matches!(
+            parse("foo @ later"),
+            Err(Error::InvalidModifier(_))
+        ));
+        assert!(matches!(parse("foo @ end"), Err(Error::InvalidModifier(_))));
+        assert!(matches!(parse("foo @ Inf"), Err(Error::InvalidModifier(_))));
+        assert!(parse("foo @ 1 @ 2").is_err());
+        assert!(parse("sum(foo) @ 100").is_err());
+    }
+}
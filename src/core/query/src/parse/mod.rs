@@ -1,5 +1,8 @@
+mod ast;
+mod grammar;
 pub mod promql;
 
+use common::time::Instant;
 use regex;
 use thiserror::Error;
 
@@ -15,15 +18,56 @@ pub enum Error {
     UpStream(#[from] Box<dyn std::error::Error>),
     #[error("invalid regex pattern: {}", .0)]
     InvalidRegex(#[from] regex::Error),
+    #[error("{} is not an aggregation operator", .name)]
+    NoAggregation { name: String },
+    #[error("invalid @ modifier near: {}", .0)]
+    InvalidModifier(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evaluation {
+    pub time: Instant,
+    pub start: Instant,
+    pub end: Instant,
+}
+
+impl Evaluation {
+    #[inline]
+    pub fn instant(time: Instant) -> Self {
+        Self {
+            time,
+            start: time,
+            end: time,
+        }
+    }
+
+    #[inline]
+    pub fn range(start: Instant, end: Instant) -> Self {
+        Self {
+            time: end,
+            start,
+            end,
+        }
+    }
 }
 
 #[derive(Default)]
-pub struct Parser;
+pub struct Parser {
+    evaluation: Option<Evaluation>,
+}
 
 impl Parser {
     pub(crate) fn new() -> Self {
         Default::default()
     }
+
+    // without it, queries are evaluated at the time they are applied
+    #[inline]
+    pub fn with_evaluation(evaluation: Evaluation) -> Self {
+        Self {
+            evaluation: Some(evaluation),
+        }
+    }
 }
 
 impl<'input, Input, Inner> Layer<Input, Inner> for Parser
@@ -33,12 +77,16 @@ where
     type Pass = Parse<Inner>;
 
     fn layer(&self, inner: Inner) -> Self::Pass {
-        Parse { inner }
+        Parse {
+            inner,
+            evaluation: self.evaluation,
+        }
     }
 }
 
 pub struct Parse<P> {
     inner: P,
+    evaluation: Option<Evaluation>,
 }
 
 impl<'input, Input, P> Pass<Input> for Parse<P>
@@ -53,6 +101,8 @@ where
             self.inner
                 .apply(input)
                 .map_err(|e| Error::UpStream(Box::new(e)))?,
+            self.evaluation
+                .unwrap_or_else(|| Evaluation::instant(Instant::now())),
         )
     }
 }
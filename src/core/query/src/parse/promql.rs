@@ -1,26 +1,113 @@
 use common::{
-    column::label::Label,
+    column::label::{Label, LabelValue},
     query::{MatcherOp, Projection},
-    time::{Duration, Instant, Range},
+    time::{Instant, Range},
     Set,
 };
 use regex::Regex;
 
-use super::Error;
+use super::{
+    ast::{AggregationAction, At, LabelMatchOp, Node, Op, OpGroupSide, OpMod, OpModAction},
+    grammar, Error, Evaluation,
+};
 use crate::plan::logical::{
-    Aggregate, AggregateAction, Call, Logical, Matcher, Scan, Window, WindowSize,
+    Aggregate, AggregateAction, Binary, BinaryOp, Call, Group, GroupSide, Logical, Matcher,
+    MatchingAction, Scan, VectorMatching, Window, WindowSize,
 };
 
-pub fn parse(literal: &str) -> Result<Logical, Error> {
-    let expr = promql::parse(literal.as_bytes(), true).map_err(|e| Error::ParsingWrong {
-        err: format!("{}", e),
-    })?;
-    translate(expr)
+const AGGREGATIONS: &[&str] = &[
+    "sum",
+    "min",
+    "max",
+    "avg",
+    "group",
+    "stddev",
+    "stdvar",
+    "count",
+    "count_values",
+    "bottomk",
+    "topk",
+    "quantile",
+];
+
+struct Env {
+    evaluation: Evaluation,
+}
+
+impl Env {
+    fn at(&self, at: At) -> Instant {
+        match at {
+            At::Timestamp(at) => at,
+            At::Start => self.evaluation.start,
+            At::End => self.evaluation.end,
+        }
+    }
+}
+
+pub fn parse(literal: &str, evaluation: Evaluation) -> Result<Logical, Error> {
+    translate(&Env { evaluation }, grammar::parse(literal)?)
+}
+
+fn literal_alternation(pattern: &str) -> Option<Vec<LabelValue>> {
+    const META: &[char] = &[
+        '\\', '.', '+', '*', '?', '(', ')', '[', ']', '{', '}', '^', '$',
+    ];
+
+    if !pattern.contains('|') {
+        return None;
+    }
+    let mut values = Vec::new();
+    for alternative in pattern.split('|') {
+        // an empty alternative also matches absent labels
+        if alternative.is_empty() || alternative.contains(META) {
+            return None;
+        }
+        let value = Label::String(alternative.into());
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    Some(values)
+}
+
+fn translate_op(op: Op) -> (BinaryOp, bool, Option<OpMod>) {
+    match op {
+        Op::Pow(m) => (BinaryOp::Pow, false, m),
+        Op::Mul(m) => (BinaryOp::Mul, false, m),
+        Op::Div(m) => (BinaryOp::Div, false, m),
+        Op::Mod(m) => (BinaryOp::Mod, false, m),
+        Op::Plus(m) => (BinaryOp::Add, false, m),
+        Op::Minus(m) => (BinaryOp::Sub, false, m),
+        Op::Eq(b, m) => (BinaryOp::Eq, b, m),
+        Op::Ne(b, m) => (BinaryOp::Ne, b, m),
+        Op::Lt(b, m) => (BinaryOp::Lt, b, m),
+        Op::Gt(b, m) => (BinaryOp::Gt, b, m),
+        Op::Le(b, m) => (BinaryOp::Le, b, m),
+        Op::Ge(b, m) => (BinaryOp::Ge, b, m),
+        Op::And(m) => (BinaryOp::And, false, m),
+        Op::Unless(m) => (BinaryOp::Unless, false, m),
+        Op::Or(m) => (BinaryOp::Or, false, m),
+    }
 }
 
-fn translate(expr: promql::Node) -> Result<Logical, Error> {
-    use promql::{LabelMatchOp, Node};
+fn translate_matching(op_mod: OpMod) -> VectorMatching {
+    VectorMatching {
+        action: match op_mod.action {
+            OpModAction::RestrictTo => MatchingAction::On,
+            OpModAction::Ignore => MatchingAction::Ignoring,
+        },
+        labels: op_mod.labels,
+        group: op_mod.group.map(|group| Group {
+            side: match group.side {
+                OpGroupSide::Left => GroupSide::Left,
+                OpGroupSide::Right => GroupSide::Right,
+            },
+            labels: group.labels,
+        }),
+    }
+}
 
+fn translate(env: &Env, expr: Node) -> Result<Logical, Error> {
     match expr {
         Node::Vector(vector) => {
             let mut name = None;
@@ -37,8 +124,14 @@ fn translate(expr: promql::Node) -> Result<Logical, Error> {
                         LabelMatchOp::Ne => {
                             MatcherOp::LiteralNotEqual(Some(Label::String(label.value.into())))
                         }
-                        LabelMatchOp::REq => MatcherOp::RegexMatch(Regex::new(&label.value)?),
-                        LabelMatchOp::RNe => MatcherOp::RegexNotMatch(Regex::new(&label.value)?),
+                        LabelMatchOp::REq => match literal_alternation(&label.value) {
+                            Some(values) => MatcherOp::In(values),
+                            None => MatcherOp::RegexMatch(Regex::new(&label.value)?),
+                        },
+                        LabelMatchOp::RNe => match literal_alternation(&label.value) {
+                            Some(values) => MatcherOp::NotIn(values),
+                            None => MatcherOp::RegexNotMatch(Regex::new(&label.value)?),
+                        },
                     };
                     matcher.push(Matcher {
                         name: label.name,
@@ -47,21 +140,23 @@ fn translate(expr: promql::Node) -> Result<Logical, Error> {
                 }
             }
 
-            let mut end = Instant::now();
-            if let Some(offset) = vector.offset {
-                end = end - Duration::from_secs(offset as i64);
+            let (offset, at) = (vector.offset, vector.at.map(|at| env.at(at)));
+            let mut end = at.unwrap_or(env.evaluation.time);
+            if let Some(offset) = offset {
+                end = end - offset;
             }
-            let start = vector
-                .range
-                .map(|range| end - Duration::from_secs(range as i64));
+            let start = vector.range.map(|range| end - range);
 
             Ok(Logical::Scan(Scan {
                 resource: name.ok_or(Error::NoName)?,
                 matcher,
+                predicate: vec![],
                 range: Range {
                     start,
                     end: Some(end),
                 },
+                offset,
+                at,
                 projection: Projection {
                     labels: Set::Universe,
                     fields: Set::Some(vec!["value".into()]),
@@ -72,38 +167,57 @@ fn translate(expr: promql::Node) -> Result<Logical, Error> {
             name,
             args: arg_nodes,
             aggregation,
-        } => match aggregation {
-            Some(aggr) => {
-                let action = match aggr.action {
-                    promql::AggregationAction::Without => AggregateAction::Without,
-                    promql::AggregationAction::By => AggregateAction::With,
-                };
-                let mut args = Vec::with_capacity(arg_nodes.len());
-                for arg in arg_nodes {
-                    args.push(translate(arg)?);
-                }
-                args.push(Logical::Literal("value".into()));
-                Ok(Logical::Aggregate(Aggregate {
-                    name,
-                    action,
-                    by: aggr.labels,
-                    args,
-                    window: Window {
-                        op: "first".into(),
-                        size: WindowSize::Depends,
-                    },
-                }))
+        } => {
+            let mut args = Vec::with_capacity(arg_nodes.len() + 1);
+            for arg in arg_nodes {
+                args.push(translate(env, arg)?);
             }
-            None => {
-                let mut args = Vec::with_capacity(arg_nodes.len());
-                for arg in arg_nodes {
-                    args.push(translate(arg)?);
-                }
-                args.push(Logical::Literal("value".into()));
-                Ok(Logical::Call(Call { name, args }))
+            args.push(Logical::Literal("value".into()));
+
+            if !AGGREGATIONS.contains(&name.as_str()) {
+                return match aggregation {
+                    Some(_) => Err(Error::NoAggregation { name }),
+                    None => Ok(Logical::Call(Call { name, args })),
+                };
             }
+            // an aggregation without modifier groups every series together
+            let (action, by) = match aggregation {
+                Some(aggr) => (
+                    match aggr.action {
+                        AggregationAction::Without => AggregateAction::Without,
+                        AggregationAction::By => AggregateAction::With,
+                    },
+                    aggr.labels,
+                ),
+                None => (AggregateAction::With, vec![]),
+            };
+            Ok(Logical::Aggregate(Aggregate {
+                name,
+                action,
+                by,
+                args,
+                window: Window {
+                    op: "first".into(),
+                    size: WindowSize::Depends,
+                },
+            }))
+        }
+        Node::Operator { x, op, y } => {
+            let (op, return_bool, op_mod) = translate_op(op);
+            Ok(Logical::Binary(Binary {
+                op,
+                return_bool,
+                matching: op_mod.map(translate_matching),
+                lhs: Box::new(translate(env, *x)?),
+                rhs: Box::new(translate(env, *y)?),
+            }))
+        }
+        Node::Scalar(value) => Ok(Logical::Number(value)),
+        Node::String(value) => Ok(Logical::String(value)),
+        Node::Negation(expr) => match translate(env, *expr)? {
+            Logical::Number(value) => Ok(Logical::Number(-value)),
+            expr => Ok(Logical::Negation(Box::new(expr))),
         },
-        _ => unimplemented!(),
     }
 }
 
@@ -112,21 +226,33 @@ mod tests {
     use common::{
         column::label::Label,
         query::{MatcherOp, Projection},
-        time::{Instant, Range},
+        time::{Duration, Instant, Range},
         Set,
     };
     use regex::Regex;
 
-    use super::parse;
-    use crate::plan::logical::{
-        Aggregate, AggregateAction, Call, Logical, Matcher, Scan, Window, WindowSize,
+    use super::literal_alternation;
+    use crate::{
+        parse::{Error, Evaluation},
+        plan::logical::{
+            Aggregate, AggregateAction, Binary, BinaryOp, Call, Group, GroupSide, Logical, Matcher,
+            MatchingAction, Scan, VectorMatching, Window, WindowSize,
+        },
     };
 
+    fn parse(literal: &str) -> Result<Logical, Error> {
+        super::parse(literal, Evaluation::instant(Instant::now()))
+    }
+
     #[test]
     fn it_works() {
         let query = r#"sum (rate(foo.bar.something_used{env="production", status!~"4.."}[5m] offset 1w)) by (test)"#;
 
-        let expr = parse(query).unwrap();
+        let expr = super::parse(
+            query,
+            Evaluation::instant(Instant::from_millis(1683357266643)),
+        )
+        .unwrap();
 
         let expected = Logical::Aggregate(Aggregate {
             name: "sum".into(),
@@ -150,10 +276,13 @@ mod tests {
                                     op: MatcherOp::RegexNotMatch(Regex::new("4..").unwrap()),
                                 },
                             ],
+                            predicate: vec![],
                             range: Range {
                                 start: Some(Instant::from_millis(1682752166643)),
                                 end: Some(Instant::from_millis(1682752466643)),
                             },
+                            offset: Some(Duration::from_secs(604800)),
+                            at: None,
                             projection: Projection {
                                 labels: Set::Universe,
                                 fields: Set::Some(vec!["value".into()]),
@@ -172,4 +301,153 @@ mod tests {
 
         assert_eq!(expr, expected);
     }
+
+    #[test]
+    fn alternation_to_set() {
+        let values = |values: &[&str]| {
+            values
+                .iter()
+                .map(|value| Label::String(Vec::from(*value)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            literal_alternation("web-1|web-2|web-1"),
+            Some(values(&["web-1", "web-2"]))
+        );
+        assert_eq!(literal_alternation("web-1"), None);
+        assert_eq!(literal_alternation("web-1|"), None);
+        assert_eq!(literal_alternation("web-.*|db"), None);
+
+        let expr = parse(r#"up{host=~"a|b", env!~"dev|test"}"#).unwrap();
+        match expr {
+            Logical::Scan(scan) => assert_eq!(
+                scan.matcher,
+                vec![
+                    Matcher {
+                        name: "host".into(),
+                        op: MatcherOp::In(values(&["a", "b"])),
+                    },
+                    Matcher {
+                        name: "env".into(),
+                        op: MatcherOp::NotIn(values(&["dev", "test"])),
+                    },
+                ]
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn translate_expression() {
+        assert_eq!(parse("0.1").unwrap(), Logical::Number(0.1));
+        match parse("0.1 + 16777217").unwrap() {
+            Logical::Binary(add) => assert_eq!(
+                (*add.lhs, *add.rhs),
+                (Logical::Number(0.1), Logical::Number(16777217.0))
+            ),
+            _ => unreachable!(),
+        }
+        assert_eq!(parse("-(2)").unwrap(), Logical::Number(-2.0));
+        match parse(r#"count_values("version", up)"#).unwrap() {
+            Logical::Aggregate(aggr) => assert_eq!(aggr.args[0], Logical::String("version".into())),
+            _ => unreachable!(),
+        }
+        assert!(matches!(parse("-up").unwrap(), Logical::Negation(_)));
+
+        let expr = parse("(a + on (job) group_left (env) b) > bool 1").unwrap();
+        let Logical::Binary(cmp) = expr else {
+            unreachable!()
+        };
+        assert_eq!(
+            (cmp.op, cmp.return_bool, cmp.matching),
+            (BinaryOp::Gt, true, None)
+        );
+        assert_eq!(*cmp.rhs, Logical::Number(1.0));
+        let Logical::Binary(add) = *cmp.lhs else {
+            unreachable!()
+        };
+        assert_eq!(add.op, BinaryOp::Add);
+        assert_eq!(
+            add.matching,
+            Some(VectorMatching {
+                action: MatchingAction::On,
+                labels: vec!["job".into()],
+                group: Some(Group {
+                    side: GroupSide::Left,
+                    labels: vec!["env".into()],
+                }),
+            })
+        );
+        assert!(matches!(*add.lhs, Logical::Scan(_)));
+
+        let expr = parse("a unless ignoring (env) b").unwrap();
+        assert!(matches!(
+            expr,
+            Logical::Binary(Binary {
+                op: BinaryOp::Unless,
+                matching: Some(VectorMatching {
+                    action: MatchingAction::Ignoring,
+                    ..
+                }),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn translate_aggregation() {
+        match parse("sum(up)").unwrap() {
+            Logical::Aggregate(aggr) => {
+                assert_eq!((aggr.action, aggr.by), (AggregateAction::With, vec![]))
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            parse("topk without (env) (3, up)").unwrap(),
+            Logical::Aggregate(Aggregate {
+                action: AggregateAction::Without,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse("rate(up[5m]) by (env)"),
+            Err(Error::NoAggregation { name }) if name == "rate"
+        ));
+    }
+
+    #[test]
+    fn evaluation_time() {
+        let secs = |secs: i64| Instant::from_millis(secs * 1000);
+        let range =
+            |query: &str| match super::parse(query, Evaluation::range(secs(1000), secs(2000)))
+                .unwrap()
+            {
+                Logical::Scan(scan) => (scan.range.start, scan.range.end),
+                _ => unreachable!(),
+            };
+
+        assert_eq!(range("up offset 1m"), (None, Some(secs(1940))));
+        assert_eq!(range("up @ 100"), (None, Some(secs(100))));
+        assert_eq!(
+            range("up @ 100.5"),
+            (None, Some(Instant::from_millis(100500)))
+        );
+        assert_eq!(
+            range("up[5m] @ start() offset 1m"),
+            (Some(secs(640)), Some(secs(940)))
+        );
+        assert_eq!(
+            range(r#"up{env="prod"} offset 1m @ end()"#),
+            (None, Some(secs(1940)))
+        );
+        assert_eq!(
+            super::parse("rate(up[5m])", Evaluation::instant(secs(600))).unwrap(),
+            super::parse("rate(up[5m])", Evaluation::instant(secs(600))).unwrap()
+        );
+        assert!(matches!(
+            super::parse("up @ soon", Evaluation::instant(secs(600))),
+            Err(Error::InvalidModifier(_))
+        ));
+    }
 }
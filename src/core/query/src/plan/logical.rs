@@ -1,6 +1,7 @@
 use common::{
+    column::label::LabelValue,
     query::{MatcherOp, Projection},
-    time::{Duration, Range},
+    time::{Duration, Instant, Range},
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -9,6 +10,10 @@ pub enum Logical {
     Call(Call),
     Scan(Scan),
     Literal(String),
+    Number(f64),
+    String(String),
+    Negation(Box<Logical>),
+    Binary(Binary),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -44,24 +49,87 @@ pub struct Call {
     pub args: Vec<Logical>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Pow,
+    Mul,
+    Div,
+    Mod,
+    Add,
+    Sub,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Unless,
+    Or,
+}
+
+impl BinaryOp {
+    #[inline]
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq | BinaryOp::Ne | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge
+        )
+    }
+
+    #[inline]
+    pub fn is_set(&self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Unless | BinaryOp::Or)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatchingAction {
+    On,
+    Ignoring,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupSide {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub side: GroupSide,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorMatching {
+    pub action: MatchingAction,
+    pub labels: Vec<String>,
+    pub group: Option<Group>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binary {
+    pub op: BinaryOp,
+    pub return_bool: bool,
+    pub matching: Option<VectorMatching>,
+    pub lhs: Box<Logical>,
+    pub rhs: Box<Logical>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Scan {
     pub resource: String,
     pub matcher: Vec<Matcher>,
+    pub predicate: Vec<Matcher<String, f64>>,
     pub range: Range,
+    pub offset: Option<Duration>,
+    pub at: Option<Instant>,
     pub projection: Projection<String>,
 }
 
-impl PartialEq for Scan {
-    fn eq(&self, other: &Self) -> bool {
-        self.resource == other.resource
-            && self.matcher == other.matcher
-            && self.projection == other.projection
-    }
-}
-
 #[derive(Debug, Clone, PartialEq)]
-pub struct Matcher<Name = String> {
+pub struct Matcher<Name = String, V = LabelValue> {
     pub name: Name,
-    pub op: MatcherOp,
+    pub op: MatcherOp<V>,
 }
@@ -1,30 +1,143 @@
 use std::sync::Arc;
 
 use common::{
+    column::field::{Field, FieldType},
     query::{MatcherOp, Projection},
-    time::Range,
+    schema::Label,
+    time::{Duration, Instant, Range},
+    Set,
 };
 use resource::table::Table;
 
-use crate::execute::function::Function;
+use super::logical::{BinaryOp, VectorMatching};
+use crate::execute::{
+    aggregate::{AggregateOp, Param},
+    function::{Function, OverTime},
+};
 
 #[derive(Debug, Clone)]
 pub enum Physical {
     Scan(Scan),
     Call(Call),
+    Number(f64),
+    Binary(Binary),
+    Aggregate(Aggregate),
+}
+
+impl Physical {
+    pub fn labels(&self) -> Vec<Label> {
+        match self {
+            Physical::Scan(scan) => {
+                let labels = &scan.resource.meta.schema.labels;
+                match &scan.projection.labels {
+                    Set::Universe => labels.clone(),
+                    Set::Some(ids) => ids.iter().map(|id| labels[*id].clone()).collect(),
+                }
+            }
+            Physical::Call(call) => call.labels.clone(),
+            Physical::Number(_) => vec![],
+            Physical::Binary(binary) => binary.labels.clone(),
+            Physical::Aggregate(aggregate) => aggregate.labels.clone(),
+        }
+    }
+
+    // position of the sample field in the records produced by this expression,
+    // a scan projects it first and everything else computes a single field
+    pub fn field(&self) -> usize {
+        0
+    }
+
+    // scalars hold one value per evaluation time and carry no labels
+    pub fn is_scalar(&self) -> bool {
+        match self {
+            Physical::Number(_) => true,
+            Physical::Call(call) => matches!(call.function, Function::Time),
+            Physical::Binary(binary) => binary.lhs.is_scalar() && binary.rhs.is_scalar(),
+            Physical::Scan(_) | Physical::Aggregate(_) => false,
+        }
+    }
+
+    // type of the field the samples come from, as long as they are passed along
+    // untouched, anything computed out of them is a float
+    pub fn field_type(&self) -> Option<&FieldType> {
+        match self {
+            Physical::Scan(scan) => Some(&scan.resource.meta.schema.fields[scan.field_id()].r#type),
+            Physical::Call(call) => match call.function {
+                Function::Relabel(_) | Function::OverTime(OverTime::Last) => {
+                    call.args.first().and_then(Physical::field_type)
+                }
+                _ => None,
+            },
+            Physical::Aggregate(aggregate) if aggregate.op.keeps_series() => {
+                aggregate.input.field_type()
+            }
+            Physical::Binary(binary) => match binary.op {
+                BinaryOp::And | BinaryOp::Unless => binary.lhs.field_type(),
+                // samples of either side come out, so a bool one decides
+                BinaryOp::Or => match binary.lhs.field_type() {
+                    Some(r#type) if matches!(r#type.as_ref(), Field::Bool(_)) => Some(r#type),
+                    lhs => binary.rhs.field_type().or(lhs),
+                },
+                // filtering keeps the sample of the vector
+                op if op.is_comparison() && !binary.return_bool => {
+                    if binary.lhs.is_scalar() {
+                        binary.rhs.field_type()
+                    } else {
+                        binary.lhs.field_type()
+                    }
+                }
+                _ => None,
+            },
+            Physical::Number(_) | Physical::Aggregate(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Scan {
     pub resource: Arc<Table>,
-    pub matcher: Vec<Option<MatcherOp>>,
+    pub matcher: Vec<Vec<MatcherOp>>,
+    pub predicate: Vec<Vec<MatcherOp<f64>>>,
     pub range: Range,
+    pub offset: Option<Duration>,
+    pub at: Option<Instant>,
     pub projection: Projection,
 }
 
+impl Scan {
+    // id of the sample field in the schema, for checking its type
+    pub fn field_id(&self) -> usize {
+        match &self.projection.fields {
+            Set::Some(ids) => ids.first().copied().unwrap_or(0),
+            Set::Universe => 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Call {
     pub args: Vec<Physical>,
     pub name: String,
     pub function: Function,
+    pub labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Binary {
+    pub op: BinaryOp,
+    pub return_bool: bool,
+    pub matching: Option<VectorMatching>,
+    pub lhs: Box<Physical>,
+    pub rhs: Box<Physical>,
+    pub labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    pub op: AggregateOp,
+    pub param: Param,
+    // positions of the grouping labels in the labels of input
+    pub by: Vec<usize>,
+    pub input: Box<Physical>,
+    pub labels: Vec<Label>,
 }
@@ -30,6 +30,11 @@ impl DB {
         self.index.get(name).map(|id| &self.tables[*id])
     }
 
+    #[inline]
+    pub fn tables(&self) -> &[Arc<Table>] {
+        &self.tables
+    }
+
     pub fn create_table(&mut self, name: Arc<str>, meta: TableMeta) -> Result<(), DBError> {
         match self.index.entry(name.clone()) {
             Entry::Occupied(_) => {
@@ -97,6 +102,46 @@ pub mod tests {
                                 r#type: LabelType::String(()),
                                 name: "status".into(),
                             },
+                            schema::Label {
+                                r#type: LabelType::IPv4(()),
+                                name: "ip".into(),
+                            },
+                            schema::Label {
+                                r#type: LabelType::Int(()),
+                                name: "shard".into(),
+                            },
+                        ],
+                        fields: vec![schema::Field {
+                            r#type: Field::Float64(()).into(),
+                            name: "value".into(),
+                        }],
+                        index: vec![Index::Inverted(())],
+                    }),
+                },
+            )
+            .unwrap();
+        db.write()
+            .unwrap()
+            .create_table(
+                Arc::from("foo.bar.latency_bucket"),
+                Meta {
+                    chunk: ChunkMeta {
+                        mutable: MutableMeta {
+                            width: 1,
+                            length: 1,
+                            count: 1,
+                        },
+                    },
+                    schema: Arc::new(Schema {
+                        labels: vec![
+                            schema::Label {
+                                r#type: LabelType::String(()),
+                                name: "env".into(),
+                            },
+                            schema::Label {
+                                r#type: LabelType::String(()),
+                                name: "le".into(),
+                            },
                         ],
                         fields: vec![schema::Field {
                             r#type: Field::Float64(()).into(),
@@ -107,6 +152,32 @@ pub mod tests {
                 },
             )
             .unwrap();
+        db.write()
+            .unwrap()
+            .create_table(
+                Arc::from("foo.bar.healthy"),
+                Meta {
+                    chunk: ChunkMeta {
+                        mutable: MutableMeta {
+                            width: 1,
+                            length: 1,
+                            count: 1,
+                        },
+                    },
+                    schema: Arc::new(Schema {
+                        labels: vec![schema::Label {
+                            r#type: LabelType::String(()),
+                            name: "env".into(),
+                        }],
+                        fields: vec![schema::Field {
+                            r#type: Field::Bool(()).into(),
+                            name: "value".into(),
+                        }],
+                        index: vec![Index::Inverted(())],
+                    }),
+                },
+            )
+            .unwrap();
         db
     }
 }
@@ -1,6 +1,9 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{borrow::Cow, cell::RefCell, rc::Rc, sync::Arc};
 
-use chunk::mutable::MutableChunk;
+use chunk::mutable::{
+    stats::{Cardinality, Sketch},
+    MutableChunk,
+};
 use common::schema::Schema;
 use executor::utils::ThreadLocal;
 
@@ -33,6 +36,22 @@ impl DataShard {
             mutable: Vec::with_capacity(meta.chunk.mutable.count),
         }
     }
+
+    // the new chunk takes every write from now on, so the one it replaces is
+    // sealed and keeps only sketches of its labels
+    pub fn rotate(&mut self, chunk: MutableChunk) {
+        if let Some(last) = self.mutable.last_mut() {
+            last.seal();
+        }
+        self.mutable.push(chunk);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelStats {
+    pub name: String,
+    pub distinct: Cardinality,
+    pub chunks: Vec<Cardinality>,
 }
 
 #[derive(Debug)]
@@ -47,4 +66,145 @@ impl Table {
         let shards = ThreadLocal::new(|| Rc::new(RefCell::new(DataShard::new(&meta))));
         Self { name, meta, shards }
     }
+
+    // statistics of every shard, each worker reads its own chunks and their
+    // sketches are merged into the distinct count of the table
+    pub async fn label_stats(self: &Arc<Self>) -> Vec<LabelStats> {
+        let tasks = (0..executor::worker_num())
+            .map(|id| {
+                let table = self.clone();
+                executor::spawn_to(id, move || async move {
+                    let shard = table.shards.get().borrow();
+                    (0..table.meta.schema.labels.len())
+                        .map(|label| {
+                            let chunks = shard
+                                .mutable
+                                .iter()
+                                .map(|chunk| chunk.cardinality(label))
+                                .collect::<Vec<_>>();
+                            let sketch = shard
+                                .mutable
+                                .iter()
+                                .map(|chunk| chunk.sketch(label))
+                                .reduce(|mut merged, sketch| {
+                                    merged.to_mut().merge(&sketch);
+                                    merged
+                                })
+                                .map(Cow::into_owned);
+                            (chunks, sketch)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut labels = self
+            .meta
+            .schema
+            .labels
+            .iter()
+            .map(|_| (Vec::new(), None::<Sketch>))
+            .collect::<Vec<_>>();
+        for task in tasks {
+            for ((chunks, merged), (shard, sketch)) in labels.iter_mut().zip(task.await) {
+                chunks.extend(shard);
+                match (merged.as_mut(), sketch) {
+                    (Some(merged), Some(sketch)) => merged.merge(&sketch),
+                    (None, sketch) => *merged = sketch,
+                    (Some(_), None) => {}
+                }
+            }
+        }
+        self.meta
+            .schema
+            .labels
+            .iter()
+            .zip(labels)
+            .map(|(label, (chunks, sketch))| LabelStats {
+                name: label.name.clone(),
+                distinct: match (chunks.as_slice(), sketch) {
+                    ([], _) | (_, None) => Cardinality::Exact(0),
+                    ([only], _) => *only,
+                    (_, Some(sketch)) => Cardinality::Estimated(sketch.count()),
+                },
+                chunks,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chunk::mutable::{stats::Cardinality, MutableChunk};
+    use common::{
+        column::label::LabelValue,
+        time::{Duration, Instant},
+    };
+
+    use crate::db::tests::test_db;
+
+    #[test]
+    fn table_label_stats() {
+        executor::ExecutorBuilder::new()
+            .worker_num(2)
+            .build()
+            .unwrap()
+            .run(|| async {
+                let db = test_db();
+                let table = db
+                    .read()
+                    .unwrap()
+                    .get("foo.bar.something_used")
+                    .unwrap()
+                    .clone();
+                let stats = table.label_stats().await;
+                assert_eq!(stats[0].distinct, Cardinality::Exact(0));
+                assert!(stats[0].chunks.is_empty());
+
+                // the first worker rotates out a chunk, the second one writes its own
+                let start_at = Instant::now();
+                for (worker, chunks) in [
+                    (0, vec![vec!["prod", "dev"], vec!["dev"]]),
+                    (1, vec![vec!["dev", "test"]]),
+                ] {
+                    let table = table.clone();
+                    executor::spawn_to(worker, move || async move {
+                        for (offset, envs) in chunks.into_iter().enumerate() {
+                            let mut chunk = MutableChunk::new(
+                                &table.meta.schema,
+                                start_at + Duration::from_secs(offset as i64),
+                                Duration::from_secs(1),
+                                1,
+                                1,
+                            );
+                            for env in envs {
+                                chunk
+                                    .push(vec![
+                                        Some(LabelValue::String(Vec::from(env))),
+                                        None,
+                                        None,
+                                        None,
+                                    ])
+                                    .unwrap();
+                            }
+                            table.shards.get().borrow_mut().rotate(chunk);
+                        }
+                    })
+                    .await;
+                }
+
+                let stats = table.label_stats().await;
+                assert_eq!(stats[0].name, "env");
+                assert_eq!(
+                    stats[0].chunks,
+                    vec![
+                        Cardinality::Estimated(2),
+                        Cardinality::Exact(1),
+                        Cardinality::Exact(2)
+                    ]
+                );
+                assert_eq!(stats[0].distinct, Cardinality::Estimated(3));
+                assert_eq!(stats[1].distinct, Cardinality::Estimated(0));
+            });
+    }
 }